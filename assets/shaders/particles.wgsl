@@ -0,0 +1,61 @@
+struct VertexInput {
+    @location(0) corner: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(1) center: vec4<f32>,
+    @location(2) axes: vec4<f32>,
+    @location(3) tint: vec4<f32>,
+    @location(4) tex_num: u32,
+    @location(5) tex_pos: vec2<f32>,
+    @location(6) tex_dim: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tint: vec4<f32>,
+    @location(1) tex_num: u32,
+    @location(2) tex_coord: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> view: mat4x4<f32>;
+
+@group(0) @binding(1)
+var<uniform> proj: mat4x4<f32>;
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let offset = instance.axes.xy * vertex.corner.x + instance.axes.zw * vertex.corner.y;
+    // The texture goes down from its top left corner
+    let tex_offset = vec2<f32>(vertex.corner.x + 1.0, 1.0 - vertex.corner.y) / 2.0;
+
+    var result: VertexOutput;
+    result.pos = proj * view * (instance.center + vec4<f32>(offset, 0.0, 0.0));
+    result.tint = instance.tint;
+    result.tex_num = instance.tex_num;
+    result.tex_coord = instance.tex_pos + instance.tex_dim * tex_offset;
+
+    return result;
+}
+
+@group(1) @binding(0)
+var tex_color: binding_array<texture_2d<f32>>;
+
+@group(1) @binding(1)
+var tex_normal: binding_array<texture_2d<f32>>;
+
+fn get_color(tex_num: u32, tex_coord: vec2<f32>) -> vec4<f32> {
+    return textureLoad(tex_color[tex_num], vec2<i32>(tex_coord), 0);
+}
+
+@fragment
+fn fs_main(frag: VertexOutput) -> @location(0) vec4<f32> {
+    let tex_color = get_color(frag.tex_num, frag.tex_coord);
+
+    if tex_color.a == 0.0 {
+        discard;
+    }
+
+    return tex_color * frag.tint;
+}
@@ -5,6 +5,8 @@ struct VertexInput {
     @location(0) pos: vec4<f32>,
     @location(1) tex_num: u32,
     @location(2) tex_coord: vec2<f32>,
+    @location(3) tint: vec4<f32>,
+    @location(4) parallax: vec2<f32>,
 }
 
 struct VertexOutput {
@@ -12,6 +14,7 @@ struct VertexOutput {
     @location(0) @interpolate(linear) depth: f32,
     @location(1) tex_num: u32,
     @location(2) tex_coord: vec2<f32>,
+    @location(3) tint: vec4<f32>,
 };
 
 struct FragmentOutput {
@@ -28,11 +31,17 @@ var<uniform> proj: mat4x4<f32>;
 
 @vertex
 fn vs_main(vertex: VertexInput) -> VertexOutput {
+    // The view matrix does not rotate, so its translation is the negated camera position.
+    let camera = -view[3].xy;
+    // Layers with parallax factor below 1.0 lag behind the camera, above 1.0 move ahead of it.
+    let pos = vec4(vertex.pos.xy + camera * (1.0 - vertex.parallax), vertex.pos.zw);
+
     var result: VertexOutput;
-    result.pos = proj * view * vertex.pos;
-    result.depth = (view * vertex.pos).z;
+    result.pos = proj * view * pos;
+    result.depth = (view * pos).z;
     result.tex_num = vertex.tex_num;
     result.tex_coord = vertex.tex_coord;
+    result.tint = vertex.tint;
 
     return result;
 }
@@ -63,7 +72,7 @@ fn get_specular(tex_num: u32, tex_coord: vec2<f32>) -> f32 {
 
 @fragment
 fn fs_main(frag: VertexOutput) -> FragmentOutput {
-    let tex_color = get_color(frag.tex_num, frag.tex_coord);
+    let tex_color = get_color(frag.tex_num, frag.tex_coord) * frag.tint;
     let tex_normal = get_normal(frag.tex_num, frag.tex_coord);
     let tex_specular = get_specular(frag.tex_num, frag.tex_coord);
 
@@ -73,9 +82,12 @@ fn fs_main(frag: VertexOutput) -> FragmentOutput {
 
     let ambient_strength = 0.1;
 
+    // Ambient color is blended with whatever is behind it.
+    // Translucent layers are drawn by a separate pipeline that only writes the ambient color,
+    // so the lit colors, normals and depths of the layers behind them stay.
     var result: FragmentOutput;
-    result.color_ambient = vec4(ambient_strength * tex_color.rgb, 1.0);
-    result.color_specular = vec4(tex_color.rgb, tex_specular);
+    result.color_ambient = vec4(ambient_strength * tex_color.rgb, tex_color.a);
+    result.color_specular = vec4(tex_color.rgb * tex_color.a, tex_specular);
     result.normal_depth = vec4(tex_normal, frag.depth);
 
     return result;
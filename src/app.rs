@@ -3,17 +3,21 @@ use std::sync::Arc;
 use log::info;
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
     event_loop::ActiveEventLoop,
+    keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowId},
 };
 
-use crate::{assets::Assets, game::Game, view::View};
+use crate::{assets::Assets, game::Game, geo::Point, view::View};
 
 pub struct App<'a> {
     assets: &'a Assets,
     game: Game<'a>,
     view: Option<View>,
+    /// Last known position of the mouse in the window
+    cursor: PhysicalPosition<f64>,
 }
 
 impl<'a> App<'a> {
@@ -22,6 +26,7 @@ impl<'a> App<'a> {
             assets,
             game,
             view: None,
+            cursor: PhysicalPosition::default(),
         }
     }
 }
@@ -64,10 +69,46 @@ impl ApplicationHandler for App<'_> {
             }
             WindowEvent::RedrawRequested => {
                 view.update_lights(&self.game).unwrap();
+                view.update_particles(&self.game).unwrap();
                 view.render().unwrap();
                 // Schedule rendering of the next frame
                 view.request_redraw();
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                let point = self
+                    .game
+                    .camera
+                    .window_to_world(position, view.window_size());
+                self.game.drag_to(Point::new(point.x, point.y));
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let point = self
+                    .game
+                    .camera
+                    .window_to_world(self.cursor, view.window_size());
+                let point = Point::new(point.x, point.y);
+                match (button, state) {
+                    (MouseButton::Left, ElementState::Pressed) => self.game.grab(point),
+                    (MouseButton::Left, ElementState::Released) => self.game.release(),
+                    (MouseButton::Right, ElementState::Pressed) => self.game.explode(point),
+                    _ => (),
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => match key {
+                KeyCode::F5 => self.game.quick_save(),
+                KeyCode::F9 => self.game.quick_load(),
+                _ => (),
+            },
             WindowEvent::Resized(_) => {
                 view.resize().unwrap();
                 view.update_camera(&self.game).unwrap();
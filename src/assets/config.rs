@@ -23,11 +23,30 @@ pub struct Shader {
     pub name: String,
 }
 
+/// A map made by [`crate::assets::map::CaveGenerator`] when the assets are loaded.
+#[derive(Deserialize)]
+pub struct GeneratedMap {
+    pub name: String,
+    /// Relative to the maps directory
+    pub tileset: String,
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Also write the map to `<name>.tmx` in the maps directory, to open it in Tiled
+    #[serde(default)]
+    pub save: bool,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
-    pub max_timestep: f32,
+    /// Of the physics and the particles, seconds
+    pub timestep: f32,
+    /// Name of the map to play on
+    pub map: String,
     pub lights: Vec<LightAnimation>,
     pub shaders: Vec<Shader>,
+    #[serde(default)]
+    pub generated_maps: Vec<GeneratedMap>,
 }
 
 impl Config {
@@ -0,0 +1,156 @@
+use anyhow::Result;
+
+use crate::assets::map::{Map, TileGrid};
+
+/// How the empty tiles of a map are connected, see [`Map::connectivity`].
+/// Positions are in tiles, the Y axis points down, like in Tiled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectivityReport {
+    /// Groups of empty tiles connected through their edges, largest first
+    pub regions: Vec<Region>,
+    /// Empty tiles that can only be entered from one side
+    pub dead_ends: Vec<(i32, i32)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// Row by row, top to bottom
+    pub tiles: Vec<(i32, i32)>,
+}
+
+impl ConnectivityReport {
+    pub fn analyze(grid: &TileGrid) -> Self {
+        let regions = grid
+            .empty_regions()
+            .into_iter()
+            .map(|tiles| Region { tiles })
+            .collect();
+
+        let dead_ends = grid
+            .positions()
+            .filter(|&(x, y)| {
+                let open_sides = TileGrid::EDGE_NEIGHBOURS
+                    .iter()
+                    .filter(|(dx, dy)| !grid.is_solid(x + dx, y + dy))
+                    .count();
+                !grid.is_solid(x, y) && open_sides == 1
+            })
+            .collect();
+
+        Self { regions, dead_ends }
+    }
+
+    /// Regions that can't be reached from the largest one.
+    pub fn sealed_regions(&self) -> &[Region] {
+        self.regions.get(1..).unwrap_or_default()
+    }
+}
+
+impl Region {
+    pub fn size(&self) -> usize {
+        self.tiles.len()
+    }
+}
+
+impl Map {
+    /// Tiles that are solid in any of the occluding layers, including the hidden ones.
+    /// Like the walls of the physics, liquids and one-way tiles are not solid, and layers are
+    /// shifted by their offsets rounded to whole tiles.
+    pub fn solid_grid(&self) -> Result<TileGrid> {
+        let mut grid = TileGrid::new(self.width, self.height);
+
+        for (attributes, layer) in self.tile_layers()? {
+            if !attributes.properties.occluding {
+                continue;
+            }
+
+            // The grid is not flipped, its Y axis points down like in the layers
+            let offset = self.layer_offset(&attributes).round();
+            let (dx, dy) = (offset.x as i32, -offset.y as i32);
+            // Layers are aligned with the bottom of the map
+            let dy = dy + self.height as i32 - layer.height as i32;
+
+            for x in 0..layer.width as i32 {
+                for y in 0..layer.height as i32 {
+                    if self.is_solid(&attributes, layer, x, y) {
+                        grid.set_solid(x + dx, y + dy, true);
+                    }
+                }
+            }
+        }
+
+        Ok(grid)
+    }
+
+    /// Connected empty regions, dead ends and region sizes of the map.
+    /// The map wraps around at the edges, so regions can connect across the seam.
+    pub fn connectivity(&self) -> Result<ConnectivityReport> {
+        Ok(ConnectivityReport::analyze(&self.solid_grid()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use crate::{
+        assets::map::fixtures::{debug_map, debug_map_with_tile},
+        init_logging,
+    };
+
+    use super::*;
+
+    #[test]
+    fn analysis_sealed_and_dead_ends() {
+        let rows = [
+            "########", //
+            "#..#..##", //
+            "####.###", //
+            "..#####.", //
+        ];
+
+        let mut grid = TileGrid::new(8, 4);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                grid.set_solid(x as i32, y as i32, c == '#');
+            }
+        }
+
+        let report = ConnectivityReport::analyze(&grid);
+
+        let sizes: Vec<_> = report.regions.iter().map(Region::size).collect();
+        // The ends of the bottom row are connected across the seam
+        assert_eq!(sizes, [3, 3, 2]);
+        assert_eq!(report.sealed_regions().len(), 2);
+
+        assert_eq!(
+            report.dead_ends,
+            [(1, 1), (2, 1), (5, 1), (4, 2), (1, 3), (7, 3)]
+        );
+    }
+
+    #[test]
+    fn analysis_solid_like_walls() {
+        init_logging();
+
+        let before = debug_map().solid_grid().unwrap();
+
+        // Objects swim through liquids
+        let map = debug_map_with_tile(
+            r#"<property name="Liquid" type="bool" value="true"/>"#,
+            &[(15, 10)],
+        );
+        assert_eq!(map.solid_grid().unwrap(), before);
+
+        // One tile to the right and two tiles up
+        let mut map = debug_map();
+        let (tw, th) = (map.tile_width as f32, map.tile_height as f32);
+        map.find_layer_mut("Foreground").unwrap().offset = vec2(tw, -2.0 * th);
+        map.refresh().unwrap();
+
+        let grid = map.solid_grid().unwrap();
+        for (x, y) in grid.positions() {
+            assert_eq!(grid.is_solid(x + 1, y - 2), before.is_solid(x, y));
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::Result;
+use glam::Vec2;
+
+use crate::{
+    assets::{
+        map::{Layer, LayerKind, Map, MapTile, ObjectLayer},
+        schema::FieldKind,
+    },
+    geo::Point,
+};
+
+/// Force field region, made from a rectangle or an ellipse object with a field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapField {
+    /// Id of the object
+    pub id: u32,
+    /// World coordinates
+    pub center: Point,
+    /// Tiles
+    pub half_width: f32,
+    /// Tiles
+    pub half_height: f32,
+    /// Radians, counterclockwise
+    pub orientation: f32,
+    pub ellipse: bool,
+    pub kind: FieldKind,
+    pub strength: f32,
+    /// Radians, counterclockwise from the right
+    pub direction: f32,
+}
+
+/// Row of liquid tiles next to each other, with the same properties.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapLiquid {
+    /// World coordinates
+    pub center: Point,
+    /// Tiles
+    pub half_width: f32,
+    pub density: f32,
+    pub drag: f32,
+}
+
+impl Map {
+    /// Force field regions of the map.
+    pub fn fields(&self) -> Vec<MapField> {
+        let mut fields = Vec::new();
+
+        for layer in self.layers.iter().flat_map(Layer::flatten) {
+            let LayerKind::Objects(ObjectLayer { objects }) = &layer.kind else {
+                continue;
+            };
+
+            for object in objects {
+                let properties = self.object_properties(object);
+
+                let Some(kind) = properties.field else {
+                    continue;
+                };
+
+                let (width, height, ellipse) = match object.shape {
+                    tiled::ObjectShape::Rect { width, height } => (width, height, false),
+                    tiled::ObjectShape::Ellipse { width, height } => (width, height, true),
+                    _ => continue,
+                };
+
+                fields.push(MapField {
+                    id: object.id,
+                    center: self.object_point_to_world(object, width / 2.0, height / 2.0),
+                    half_width: width / self.tile_width as f32 / 2.0,
+                    half_height: height / self.tile_height as f32 / 2.0,
+                    orientation: -object.rotation.to_radians(),
+                    ellipse,
+                    kind,
+                    strength: properties.strength,
+                    direction: properties.direction.to_radians(),
+                });
+            }
+        }
+
+        fields
+    }
+
+    /// Liquid tiles of all tile layers, including the hidden ones.
+    /// Where several layers with the same offset have liquid tiles, the ones of the layers that come later win.
+    pub fn liquids(&self) -> Result<Vec<MapLiquid>> {
+        let liquid_of = |tile: MapTile| {
+            let properties = self.tile_properties(tile);
+            properties
+                .liquid
+                .then_some((properties.density, properties.drag))
+        };
+
+        let (w, h) = (self.width as i32, self.height as i32);
+        // Density and drag of every tile
+        type Grid = Vec<Option<(f32, f32)>>;
+        // Layers are shifted by their offsets, so there is a grid for every offset
+        let mut grids: Vec<(Vec2, Grid)> = Vec::new();
+
+        for (attributes, layer) in self.tile_layers()? {
+            let offset = self.layer_offset(&attributes);
+            let grid = match grids.iter().position(|(o, _)| *o == offset) {
+                Some(i) => &mut grids[i].1,
+                None => {
+                    grids.push((offset, vec![None; (w * h) as usize]));
+                    &mut grids.last_mut().unwrap().1
+                }
+            };
+
+            for y in 0..h.min(layer.height as i32) {
+                for x in 0..w.min(layer.width as i32) {
+                    let Some(tile) = layer.tile(x, y) else {
+                        continue;
+                    };
+
+                    if let Some(liquid) = liquid_of(tile) {
+                        grid[(y * w + x) as usize] = Some(liquid);
+                    }
+                }
+            }
+        }
+
+        let (map_w2, map_h2) = (w as f32 / 2.0, h as f32 / 2.0);
+        let mut liquids = Vec::new();
+
+        for (offset, grid) in grids {
+            for y in 0..h {
+                let row = &grid[(y * w) as usize..((y + 1) * w) as usize];
+                let mut x = 0;
+                while x < w {
+                    let Some((density, drag)) = row[x as usize] else {
+                        x += 1;
+                        continue;
+                    };
+
+                    let start = x;
+                    while x < w && row[x as usize] == Some((density, drag)) {
+                        x += 1;
+                    }
+
+                    let half_width = (x - start) as f32 / 2.0;
+                    liquids.push(MapLiquid {
+                        center: Point::new(
+                            start as f32 + half_width - map_w2,
+                            (h - 1 - y) as f32 + 0.5 - map_h2,
+                        ) + offset,
+                        half_width,
+                        density,
+                        drag,
+                    });
+                }
+            }
+        }
+
+        Ok(liquids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        assets::map::{MapObject, fixtures::debug_map_with_tile},
+        geo::ToricGeometry,
+        init_logging,
+        phys::{
+            Physics,
+            field::{FieldEffect, ForceField},
+            object::{PhysObject, PhysObjectShape, SceneObject},
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn field_from_objects_and_tiles() {
+        init_logging();
+
+        let mut map = debug_map_with_tile(
+            r#"<property name="Liquid" type="bool" value="true"/>
+            <property name="Density" type="float" value="2"/>"#,
+            &[14, 15, 16, 18].map(|x| (x, 10)),
+        );
+
+        let (tw, th) = (map.tile_width as f32, map.tile_height as f32);
+        map.layers.push(Layer::new(
+            100,
+            "Fields",
+            LayerKind::Objects(ObjectLayer {
+                objects: vec![MapObject {
+                    id: 1,
+                    name: String::new(),
+                    user_type: String::new(),
+                    // The top left corner in the middle of the map
+                    x: map.width as f32 * tw / 2.0,
+                    y: map.height as f32 * th / 2.0,
+                    rotation: 0.0,
+                    visible: true,
+                    shape: tiled::ObjectShape::Rect {
+                        width: 4.0 * tw,
+                        height: 2.0 * th,
+                    },
+                    properties: [
+                        ("Field", tiled::PropertyValue::StringValue("Wind".into())),
+                        ("Strength", tiled::PropertyValue::FloatValue(2.0)),
+                        ("Direction", tiled::PropertyValue::FloatValue(90.0)),
+                    ]
+                    .map(|(k, v)| (k.to_string(), v))
+                    .into(),
+                }],
+            }),
+        ));
+        map.refresh().unwrap();
+
+        let fields = map.fields();
+        assert_eq!(fields.len(), 1);
+        let field = &fields[0];
+        assert_eq!(field.kind, FieldKind::Wind);
+        assert_eq!(field.center, Point::new(2.0, -1.0));
+        assert_eq!((field.half_width, field.half_height), (2.0, 1.0));
+        assert!((field.direction - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+
+        let (w2, h2) = (map.width as f32 / 2.0, map.height as f32 / 2.0);
+        let y = (map.height - 1 - 10) as f32 + 0.5 - h2;
+        assert_eq!(
+            map.liquids().unwrap(),
+            vec![
+                MapLiquid {
+                    center: Point::new(15.5 - w2, y),
+                    half_width: 1.5,
+                    density: 2.0,
+                    drag: 1.0,
+                },
+                MapLiquid {
+                    center: Point::new(18.5 - w2, y),
+                    half_width: 0.5,
+                    density: 2.0,
+                    drag: 1.0,
+                },
+            ]
+        );
+
+        // Liquids move with the layer, like its tiles
+        map.find_layer_mut("Foreground").unwrap().offset = glam::vec2(tw / 2.0, -th);
+        map.refresh().unwrap();
+        let centers: Vec<_> = map.liquids().unwrap().iter().map(|l| l.center).collect();
+        assert_eq!(
+            centers,
+            vec![
+                Point::new(16.0 - w2, y + 1.0),
+                Point::new(19.0 - w2, y + 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn field_liquid_tiles_can_be_entered() {
+        init_logging();
+
+        // A pool in the basin in the middle of the map, on an occluding layer
+        let positions: Vec<_> = (11..=20)
+            .map(|x| (x, 10))
+            .chain((13..=18).map(|x| (x, 11)))
+            .collect();
+        let map = debug_map_with_tile(
+            r#"<property name="Liquid" type="bool" value="true"/>
+            <property name="Density" type="float" value="2"/>"#,
+            &positions,
+        );
+
+        let mut physics = Physics::new(
+            0.01,
+            ToricGeometry {
+                x: map.width as f32,
+                y: map.height as f32,
+            },
+        );
+        for liquid in map.liquids().unwrap() {
+            let shape = PhysObjectShape::Box {
+                half_width: liquid.half_width,
+                half_height: 0.5,
+            };
+            let effect = FieldEffect::Buoyancy {
+                density: liquid.density,
+                drag: liquid.drag,
+            };
+            physics.add_field(ForceField::new(shape, liquid.center, effect));
+        }
+        let scene: Vec<_> = map
+            .occlusion_segments
+            .iter()
+            .map(|s| {
+                let (a, b) = s.ab();
+                SceneObject::new_segment(a, b)
+            })
+            .collect();
+
+        // Half as dense as the liquid, so it floats half-submerged
+        let radius = 0.25;
+        let mass = std::f32::consts::PI * radius * radius;
+        let disc = physics.add(PhysObject::new_disc(Point::new(0.0, 2.0), radius, mass));
+        let mut deepest: f32 = 2.0;
+        for _ in 0..1000 {
+            physics.advance_by(&scene, Duration::from_millis(10));
+            deepest = deepest.min(physics.get(disc).unwrap().center.y);
+        }
+
+        // Dives in instead of landing on the surface, and bobs back up to float on it
+        let surface = (map.height - 1 - 10) as f32 + 1.0 - map.height as f32 / 2.0;
+        assert!(deepest < surface - radius, "{deepest}");
+        let disc = physics.get(disc).unwrap();
+        assert!((disc.center.y - surface).abs() < radius, "{disc:?}");
+    }
+}
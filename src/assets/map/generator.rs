@@ -0,0 +1,368 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use anyhow::{Context, Result, ensure};
+use log::debug;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::assets::{
+    map::{Layer, LayerKind, Map, MapTile, TileGrid, TileLayer},
+    schema::MapProperties,
+};
+
+/// Name of the Wang set in the tileset that describes how walls connect.
+const WANG_SET: &str = "Main";
+
+/// Generates cave maps with cellular automata.
+///
+/// The caves wrap around the map edges, so they connect across the seam like the game world does.
+/// The same seed always produces the same map.
+#[derive(Clone, Debug)]
+pub struct CaveGenerator {
+    /// Tiles
+    pub width: u32,
+    /// Tiles
+    pub height: u32,
+    pub seed: u64,
+    /// Share of the tiles that start as walls
+    pub fill: f32,
+    /// Number of smoothing passes
+    pub iterations: usize,
+    /// Radius (tiles) of the area in the middle of the map that is always empty
+    pub clearing: u32,
+}
+
+impl Default for CaveGenerator {
+    fn default() -> Self {
+        Self {
+            width: 48,
+            height: 32,
+            seed: 0,
+            fill: 0.45,
+            iterations: 5,
+            clearing: 3,
+        }
+    }
+}
+
+impl CaveGenerator {
+    /// Solid and empty tiles of the map.
+    /// All empty tiles are connected, walls fill the caves that were not reachable.
+    pub fn grid(&self) -> TileGrid {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut grid = TileGrid::new(self.width, self.height);
+
+        for (x, y) in grid.positions() {
+            grid.set_solid(x, y, rng.random::<f32>() < self.fill);
+        }
+
+        for _ in 0..self.iterations {
+            let mut next = grid.clone();
+            for (x, y) in grid.positions() {
+                let walls = grid.solid_neighbours(x, y);
+                next.set_solid(x, y, walls >= 5 || (grid.is_solid(x, y) && walls >= 4));
+            }
+            grid = next;
+        }
+
+        self.clear_middle(&mut grid);
+
+        // Objects start in the clearing, so it has to stay empty.
+        // When it is cut off from the largest cave, a tunnel joins them
+        let middle = (self.width as i32 / 2, self.height as i32 / 2);
+        let regions = grid.empty_regions();
+        if let Some(largest) = regions.first()
+            && !largest.contains(&middle)
+        {
+            let distance = |&(x, y): &(i32, i32)| (x - middle.0).pow(2) + (y - middle.1).pow(2);
+            let closest = *largest.iter().min_by_key(|p| distance(p)).unwrap();
+            Self::dig(&mut grid, middle, closest);
+        }
+
+        for region in grid.empty_regions() {
+            if region.contains(&middle) {
+                continue;
+            }
+            for (x, y) in region {
+                grid.set_solid(x, y, true);
+            }
+        }
+
+        grid
+    }
+
+    /// Empty the tiles along the x axis from `from`, and then along the y axis to `to`.
+    fn dig(grid: &mut TileGrid, from: (i32, i32), to: (i32, i32)) {
+        let (mut x, mut y) = from;
+        grid.set_solid(x, y, false);
+        while (x, y) != to {
+            if x != to.0 {
+                x += (to.0 - x).signum();
+            } else {
+                y += (to.1 - y).signum();
+            }
+            grid.set_solid(x, y, false);
+        }
+    }
+
+    fn clear_middle(&self, grid: &mut TileGrid) {
+        let (cx, cy) = (self.width as i32 / 2, self.height as i32 / 2);
+        let r = self.clearing as i32;
+
+        for y in cy - r..=cy + r {
+            for x in cx - r..=cx + r {
+                if (x - cx).pow(2) + (y - cy).pow(2) <= r * r {
+                    grid.set_solid(x, y, false);
+                }
+            }
+        }
+    }
+
+    /// Generate a map that uses the given tileset, which has to have a Wang set named "Main".
+    ///
+    /// `path` is where the map would be saved, the map is named after it.
+    /// `tileset_index` is the index of the tileset in the assets.
+    pub fn generate(
+        &self,
+        path: impl AsRef<Path>,
+        tileset: &Arc<tiled::Tileset>,
+        tileset_index: usize,
+    ) -> Result<Map> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .context("Map path has no file stem?")?
+            .to_str()
+            .context("Map path file stem is not valid UTF-8?")?;
+
+        ensure!(
+            self.width > 0 && self.height > 0 && (0.0..=1.0).contains(&self.fill),
+            "Can't generate map '{name}' of {}x{} tiles with {} of them filled",
+            self.width,
+            self.height,
+            self.fill
+        );
+
+        debug!(
+            "Generating map '{name}' ({}x{}, seed {})...",
+            self.width, self.height, self.seed
+        );
+
+        let autotiler =
+            Autotiler::new(tileset).with_context(|| format!("Can't generate map '{name}'"))?;
+
+        let grid = self.grid();
+        // Keep the tile choice independent of the shape of the caves
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(1));
+
+        let mut background = TileLayer::new(self.width, self.height);
+        let mut foreground = TileLayer::new(self.width, self.height);
+
+        for (x, y) in grid.positions() {
+            background.set_tile(x, y, Some(autotiler.pick(&[1; 8], &mut rng)));
+
+            if grid.is_solid(x, y) {
+                let wang_id = TileGrid::ALL_NEIGHBOURS.map(|(dx, dy)| {
+                    let solid = |dx: i32, dy: i32| grid.is_solid(x + dx, y + dy);
+                    // Corners only count when both of the adjacent edges are solid too
+                    let solid = solid(dx, dy) && solid(dx, 0) && solid(0, dy);
+                    u8::from(solid)
+                });
+                foreground.set_tile(x, y, Some(autotiler.pick(&wang_id, &mut rng)));
+            }
+        }
+
+        let mut background = Layer::new(1, "Background", LayerKind::Tiles(background));
+        background
+            .properties
+            .insert("Z".to_string(), tiled::PropertyValue::FloatValue(-0.5));
+
+        let mut foreground = Layer::new(2, "Foreground", LayerKind::Tiles(foreground));
+        foreground.properties.insert(
+            "Occluding".to_string(),
+            tiled::PropertyValue::BoolValue(true),
+        );
+
+        let mut map = Map {
+            name: name.to_string(),
+            source: path.to_path_buf(),
+            properties: MapProperties::default(),
+            tiled_properties: tiled::Properties::new(),
+            width: self.width,
+            height: self.height,
+            tile_width: tileset.tile_width,
+            tile_height: tileset.tile_height,
+            tilesets: vec![tileset.clone()],
+            tile_properties: Vec::new(),
+            tileset_map: vec![tileset_index],
+            layers: vec![background, foreground],
+            object_properties: BTreeMap::new(),
+            occlusion_segments: Vec::new(),
+            occlusion_materials: Vec::new(),
+        };
+        map.refresh()?;
+
+        Ok(map)
+    }
+}
+
+/// Picks tiles that match the surroundings using the Wang set of a tileset.
+struct Autotiler {
+    /// Wang IDs with the tiles that have them and their probabilities
+    tiles: Vec<([u8; 8], tiled::TileId, f32)>,
+}
+
+impl Autotiler {
+    fn new(tileset: &tiled::Tileset) -> Result<Self> {
+        let wang_set = tileset
+            .wang_sets
+            .iter()
+            .find(|set| set.name == WANG_SET)
+            .with_context(|| format!("Tileset '{}' has no Wang set '{WANG_SET}'", tileset.name))?;
+
+        let mut tiles: Vec<_> = wang_set
+            .wang_tiles
+            .iter()
+            .map(|(&id, tile)| {
+                let probability = tileset.get_tile(id).map_or(1.0, |tile| tile.probability);
+                (tile.wang_id.0, id, probability)
+            })
+            .collect();
+        // The Wang tiles are stored in a hash map, sort them to make the choice reproducible
+        tiles.sort_by_key(|&(_, id, _)| id);
+
+        ensure!(
+            !tiles.is_empty(),
+            "Wang set '{WANG_SET}' of tileset '{}' has no tiles",
+            tileset.name
+        );
+
+        Ok(Self { tiles })
+    }
+
+    /// A random tile out of the ones that match the Wang ID best.
+    fn pick(&self, wang_id: &[u8; 8], rng: &mut impl Rng) -> MapTile {
+        let distance = |other: &[u8; 8]| wang_id.iter().zip(other).filter(|(a, b)| a != b).count();
+
+        let best = self
+            .tiles
+            .iter()
+            .map(|(other, _, _)| distance(other))
+            .min()
+            .expect("Wang set has tiles");
+
+        let candidates: Vec<_> = self
+            .tiles
+            .iter()
+            .filter(|(other, _, _)| distance(other) == best)
+            .collect();
+
+        let total: f32 = candidates.iter().map(|(_, _, p)| p).sum();
+        let mut choice = rng.random::<f32>() * total;
+
+        let mut id = candidates[0].1;
+        for (_, candidate, probability) in candidates {
+            id = *candidate;
+            choice -= probability;
+            if choice < 0.0 {
+                break;
+            }
+        }
+
+        MapTile { tileset: 0, id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::init_logging;
+
+    use super::*;
+
+    #[test]
+    fn generator_reproducible() {
+        let generator = CaveGenerator {
+            seed: 42,
+            ..Default::default()
+        };
+
+        let grid = generator.grid();
+        assert_eq!(grid, generator.grid());
+
+        let other = CaveGenerator {
+            seed: 43,
+            ..Default::default()
+        };
+        assert_ne!(grid, other.grid());
+    }
+
+    #[test]
+    fn generator_connected() {
+        for seed in 0..16 {
+            let generator = CaveGenerator {
+                seed,
+                ..Default::default()
+            };
+
+            let grid = generator.grid();
+            let regions = grid.empty_regions();
+
+            assert_eq!(regions.len(), 1, "seed {seed}");
+            assert!(!grid.is_solid(generator.width as i32 / 2, generator.height as i32 / 2));
+        }
+    }
+
+    #[test]
+    fn generator_clearing_kept() {
+        // Dense caves, where the clearing is often cut off from the largest cave
+        for seed in 0..64 {
+            let generator = CaveGenerator {
+                seed,
+                fill: 0.5,
+                clearing: 2,
+                ..Default::default()
+            };
+
+            let grid = generator.grid();
+            let (cx, cy) = (generator.width as i32 / 2, generator.height as i32 / 2);
+            assert!(!grid.is_solid(cx, cy), "seed {seed}");
+            assert_eq!(grid.empty_regions().len(), 1, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn generator_rejects_bad_config() {
+        init_logging();
+
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let tileset = Arc::new(
+            tiled::Loader::new()
+                .load_tsx_tileset(assets.join("tiles/main.tsx"))
+                .unwrap(),
+        );
+        let path = assets.join("maps/bad.tmx");
+
+        for generator in [
+            CaveGenerator {
+                width: 0,
+                ..Default::default()
+            },
+            CaveGenerator {
+                height: 0,
+                ..Default::default()
+            },
+            CaveGenerator {
+                fill: 1.5,
+                ..Default::default()
+            },
+            CaveGenerator {
+                fill: f32::NAN,
+                ..Default::default()
+            },
+        ] {
+            let Err(error) = generator.generate(&path, &tileset, 0) else {
+                panic!("{generator:?} generated a map");
+            };
+            assert!(error.to_string().contains("'bad'"), "{error}");
+        }
+    }
+}
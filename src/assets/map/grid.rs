@@ -0,0 +1,137 @@
+/// A grid of solid and empty tiles that wraps around at the edges, like the game world does.
+/// The Y axis points down, like in Tiled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    solid: Vec<bool>,
+}
+
+impl TileGrid {
+    /// Offsets of the 4 neighbours that share an edge with a tile.
+    pub const EDGE_NEIGHBOURS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+    /// Offsets of all 8 neighbours of a tile, clockwise starting from the top
+    /// (matches the order of Wang IDs in Tiled).
+    pub const ALL_NEIGHBOURS: [(i32, i32); 8] = [
+        (0, -1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+        (-1, -1),
+    ];
+
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            solid: vec![false; (width * height) as usize],
+        }
+    }
+
+    /// Position of the tile after wrapping around the edges.
+    pub fn wrap(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            x.rem_euclid(self.width as i32),
+            y.rem_euclid(self.height as i32),
+        )
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        let (x, y) = self.wrap(x, y);
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.solid[self.index(x, y)]
+    }
+
+    pub fn set_solid(&mut self, x: i32, y: i32, solid: bool) {
+        let i = self.index(x, y);
+        self.solid[i] = solid;
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (i32, i32)> + use<> {
+        let (w, h) = (self.width as i32, self.height as i32);
+        (0..h).flat_map(move |y| (0..w).map(move |x| (x, y)))
+    }
+
+    /// Number of solid tiles out of all 8 neighbours.
+    pub fn solid_neighbours(&self, x: i32, y: i32) -> usize {
+        Self::ALL_NEIGHBOURS
+            .iter()
+            .filter(|(dx, dy)| self.is_solid(x + dx, y + dy))
+            .count()
+    }
+
+    /// Groups of empty tiles connected through their edges, largest first.
+    pub fn empty_regions(&self) -> Vec<Vec<(i32, i32)>> {
+        let mut visited = vec![false; self.solid.len()];
+        let mut regions = Vec::new();
+
+        for (x, y) in self.positions() {
+            if self.is_solid(x, y) || visited[self.index(x, y)] {
+                continue;
+            }
+
+            let mut region = Vec::new();
+            let mut queue = vec![(x, y)];
+            visited[self.index(x, y)] = true;
+
+            while let Some((x, y)) = queue.pop() {
+                region.push((x, y));
+
+                for (dx, dy) in Self::EDGE_NEIGHBOURS {
+                    let (nx, ny) = self.wrap(x + dx, y + dy);
+                    let ni = self.index(nx, ny);
+                    if !self.solid[ni] && !visited[ni] {
+                        visited[ni] = true;
+                        queue.push((nx, ny));
+                    }
+                }
+            }
+
+            region.sort_unstable_by_key(|&(x, y)| (y, x));
+            regions.push(region);
+        }
+
+        // Stable sort keeps regions of the same size in the scanning order
+        regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+        regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> TileGrid {
+        let mut grid = TileGrid::new(rows[0].len() as u32, rows.len() as u32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                grid.set_solid(x as i32, y as i32, c == '#');
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn grid_regions_wrap() {
+        let g = grid(&[
+            "#####", //
+            "..#..", //
+            "#####", //
+            ".###.", //
+        ]);
+
+        let regions = g.empty_regions();
+
+        // The left and the right parts of the second row are connected through the seam
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0], vec![(0, 1), (1, 1), (3, 1), (4, 1)]);
+        assert_eq!(regions[1], vec![(0, 3), (4, 3)]);
+    }
+}
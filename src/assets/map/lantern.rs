@@ -0,0 +1,138 @@
+use crate::{
+    assets::{
+        map::{Layer, LayerKind, Map, ObjectLayer},
+        schema::LinkKind,
+    },
+    geo::Point,
+};
+
+/// Chain of lanterns hanging from a point object with lanterns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapLanternChain {
+    /// Id of the object
+    pub id: u32,
+    /// World coordinates of the point that the chain hangs from
+    pub anchor: Point,
+    pub lanterns: u32,
+    pub link: LinkKind,
+    /// Tiles
+    pub link_length: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl Map {
+    /// Chains of lanterns of the map, the lanterns hang straight down from the anchors.
+    pub fn lantern_chains(&self) -> Vec<MapLanternChain> {
+        let mut chains = Vec::new();
+
+        for layer in self.layers.iter().flat_map(Layer::flatten) {
+            let LayerKind::Objects(ObjectLayer { objects }) = &layer.kind else {
+                continue;
+            };
+
+            for object in objects {
+                let properties = self.object_properties(object);
+                if properties.lanterns == 0 {
+                    continue;
+                }
+
+                let tiled::ObjectShape::Point(..) = object.shape else {
+                    continue;
+                };
+
+                chains.push(MapLanternChain {
+                    id: object.id,
+                    anchor: self.object_point_to_world(object, 0.0, 0.0),
+                    lanterns: properties.lanterns,
+                    link: properties.link,
+                    link_length: properties.link_length,
+                    stiffness: properties.stiffness,
+                    damping: properties.damping,
+                });
+            }
+        }
+
+        chains
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assets::map::{MapObject, fixtures::debug_map},
+        init_logging,
+    };
+
+    use super::*;
+
+    #[test]
+    fn lantern_from_point() {
+        init_logging();
+
+        let mut map = debug_map();
+
+        // The chains of the debug map hang from the bottom of the hooks
+        let anchors: Vec<_> = map.lantern_chains().iter().map(|c| c.anchor).collect();
+        assert_eq!(anchors, [Point::new(-1.5, 3.0), Point::new(1.5, 3.0)]);
+
+        let (tw, th) = (map.tile_width as f32, map.tile_height as f32);
+        let object = |id, shape, properties: &[(&str, tiled::PropertyValue)]| MapObject {
+            id,
+            name: String::new(),
+            user_type: String::new(),
+            // Two tiles right and one tile up from the middle of the map
+            x: (map.width as f32 / 2.0 + 2.0) * tw,
+            y: (map.height as f32 / 2.0 - 1.0) * th,
+            rotation: 0.0,
+            visible: true,
+            shape,
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        };
+
+        let point = tiled::ObjectShape::Point(0.0, 0.0);
+        let lanterns = ("Lanterns", tiled::PropertyValue::IntValue(3));
+        let link = (
+            "Link",
+            tiled::PropertyValue::StringValue("Spring".to_string()),
+        );
+
+        map.layers.push(Layer::new(
+            100,
+            "Lanterns",
+            LayerKind::Objects(ObjectLayer {
+                objects: vec![
+                    object(101, point.clone(), &[lanterns.clone(), link]),
+                    // Without lanterns
+                    object(102, point, &[]),
+                    // Not a point
+                    object(
+                        103,
+                        tiled::ObjectShape::Rect {
+                            width: tw,
+                            height: th,
+                        },
+                        &[lanterns],
+                    ),
+                ],
+            }),
+        ));
+        map.refresh().unwrap();
+
+        assert_eq!(
+            map.lantern_chains()[2..],
+            [MapLanternChain {
+                id: 101,
+                anchor: Point::new(2.0, 1.0),
+                lanterns: 3,
+                link: LinkKind::Spring,
+                link_length: 0.75,
+                stiffness: 40.0,
+                damping: 1.0,
+            }]
+        );
+    }
+}
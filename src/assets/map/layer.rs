@@ -0,0 +1,240 @@
+use anyhow::{Result, bail, ensure};
+use glam::{Vec2, vec2};
+
+/// A layer of a map, owned by the map so that it can be modified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub id: u32,
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub tint: Option<tiled::Color>,
+    /// Pixels, the Y axis points down
+    pub offset: Vec2,
+    pub parallax: Vec2,
+    /// Raw custom properties, see [`crate::assets::schema::LayerProperties`] for the ones that matter
+    pub properties: tiled::Properties,
+    pub kind: LayerKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayerKind {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+    Group(Vec<Layer>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileLayer {
+    pub width: u32,
+    pub height: u32,
+    /// Row by row, top to bottom
+    tiles: Vec<Option<MapTile>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapTile {
+    /// Index of the tileset in the map
+    pub tileset: usize,
+    /// Index of the tile in the tileset
+    pub id: tiled::TileId,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectLayer {
+    pub objects: Vec<MapObject>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub user_type: String,
+    /// Pixels, the Y axis points down
+    pub x: f32,
+    /// Pixels, the Y axis points down
+    pub y: f32,
+    /// Degrees, clockwise
+    pub rotation: f32,
+    pub visible: bool,
+    /// Text objects are not supported
+    pub shape: tiled::ObjectShape,
+    /// Raw custom properties, see [`crate::assets::schema::ObjectProperties`] for the ones that matter
+    pub properties: tiled::Properties,
+}
+
+impl Layer {
+    pub fn new(id: u32, name: impl Into<String>, kind: LayerKind) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            visible: true,
+            opacity: 1.0,
+            tint: None,
+            offset: Vec2::ZERO,
+            parallax: Vec2::ONE,
+            properties: tiled::Properties::new(),
+            kind,
+        }
+    }
+
+    pub(super) fn from_tiled(layer: tiled::Layer<'_>) -> Result<Self> {
+        let kind = match layer.layer_type() {
+            tiled::LayerType::Tiles(tiled::TileLayer::Finite(tiles)) => {
+                LayerKind::Tiles(TileLayer::from_tiled(&tiles)?)
+            }
+            tiled::LayerType::Tiles(_) => bail!("Only finite tile layers are supported"),
+            tiled::LayerType::Objects(objects) => {
+                let objects = objects
+                    .objects()
+                    .map(|object| MapObject::from_tiled(&object))
+                    .collect::<Result<_>>()?;
+                LayerKind::Objects(ObjectLayer { objects })
+            }
+            tiled::LayerType::Group(group) => {
+                let layers = group
+                    .layers()
+                    .map(Layer::from_tiled)
+                    .collect::<Result<_>>()?;
+                LayerKind::Group(layers)
+            }
+            tiled::LayerType::Image(_) => bail!("Image layers are not supported"),
+        };
+
+        Ok(Self {
+            id: layer.id(),
+            name: layer.name.clone(),
+            visible: layer.visible,
+            opacity: layer.opacity,
+            tint: layer.tint_color,
+            offset: vec2(layer.offset_x, layer.offset_y),
+            parallax: vec2(layer.parallax_x, layer.parallax_y),
+            properties: layer.properties.clone(),
+            kind,
+        })
+    }
+
+    /// Depth-first search for a layer with the given name, including this layer.
+    pub fn find(&self, name: &str) -> Option<&Layer> {
+        if self.name == name {
+            return Some(self);
+        }
+
+        match &self.kind {
+            LayerKind::Group(layers) => layers.iter().find_map(|layer| layer.find(name)),
+            _ => None,
+        }
+    }
+
+    /// Depth-first search for a layer with the given name, including this layer.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        if self.name == name {
+            return Some(self);
+        }
+
+        match &mut self.kind {
+            LayerKind::Group(layers) => layers.iter_mut().find_map(|layer| layer.find_mut(name)),
+            _ => None,
+        }
+    }
+
+    /// This layer and all layers nested in it.
+    pub fn flatten(&self) -> Box<dyn Iterator<Item = &Layer> + '_> {
+        let nested = match &self.kind {
+            LayerKind::Group(layers) => layers.as_slice(),
+            _ => &[],
+        };
+
+        Box::new(std::iter::once(self).chain(nested.iter().flat_map(Layer::flatten)))
+    }
+}
+
+impl TileLayer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![None; (width * height) as usize],
+        }
+    }
+
+    fn from_tiled(layer: &tiled::FiniteTileLayer<'_>) -> Result<Self> {
+        let mut s = Self::new(layer.width(), layer.height());
+
+        for y in 0..s.height as i32 {
+            for x in 0..s.width as i32 {
+                if let Some(tile) = layer.get_tile_data(x, y) {
+                    ensure!(
+                        !tile.flip_d && !tile.flip_h && !tile.flip_v,
+                        "Flipped tiles are not supported"
+                    );
+
+                    s.set_tile(
+                        x,
+                        y,
+                        Some(MapTile {
+                            tileset: tile.tileset_index(),
+                            id: tile.id(),
+                        }),
+                    );
+                }
+            }
+        }
+
+        Ok(s)
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let in_bounds = 0 <= x && x < self.width as i32 && 0 <= y && y < self.height as i32;
+        in_bounds.then(|| y as usize * self.width as usize + x as usize)
+    }
+
+    /// Tile at the given position, the Y axis points down.
+    /// Positions outside of the layer are empty.
+    pub fn tile(&self, x: i32, y: i32) -> Option<MapTile> {
+        self.index(x, y).and_then(|i| self.tiles[i])
+    }
+
+    /// Change the tile at the given position, the Y axis points down.
+    ///
+    /// Panics if the position is outside of the layer.
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: Option<MapTile>) {
+        let i = self
+            .index(x, y)
+            .unwrap_or_else(|| panic!("Tile position ({x}, {y}) is outside of the layer"));
+        self.tiles[i] = tile;
+    }
+
+    /// Rows of tiles, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Option<MapTile>]> {
+        // Layers without columns have no tiles, and chunks can't be empty
+        self.tiles.chunks(self.width.max(1) as usize)
+    }
+}
+
+impl MapObject {
+    fn from_tiled(object: &tiled::Object<'_>) -> Result<Self> {
+        ensure!(
+            object.tile_data().is_none(),
+            "Tile objects are not supported (object {})",
+            object.id()
+        );
+        ensure!(
+            !matches!(object.shape, tiled::ObjectShape::Text { .. }),
+            "Text objects are not supported (object {})",
+            object.id()
+        );
+
+        Ok(Self {
+            id: object.id(),
+            name: object.name.clone(),
+            user_type: object.user_type.clone(),
+            x: object.x,
+            y: object.y,
+            rotation: object.rotation,
+            visible: object.visible,
+            shape: object.shape.clone(),
+            properties: object.properties.clone(),
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    assets::schema::TileProperties,
+    phys::{filter::CollisionFilter, material::Material},
+};
+
+/// How the walls made of a tile behave when objects hit them, see [`TileProperties`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapMaterial {
+    pub restitution: f32,
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+    /// Tiles per second squared
+    pub stickiness: f32,
+    pub kills_light: bool,
+    /// Only the tops of one-way tiles are walls, and they block only the objects coming from above
+    pub one_way: bool,
+    /// Of the layer of the tile
+    pub filter: CollisionFilter,
+}
+
+impl From<&TileProperties> for MapMaterial {
+    fn from(properties: &TileProperties) -> Self {
+        Self {
+            restitution: properties.restitution,
+            static_friction: properties.static_friction,
+            dynamic_friction: properties.dynamic_friction,
+            stickiness: properties.stickiness,
+            kills_light: properties.kills_light,
+            one_way: properties.one_way,
+            filter: CollisionFilter::default(),
+        }
+    }
+}
+
+impl Default for MapMaterial {
+    /// Of tiles without custom properties, and of the map edges.
+    fn default() -> Self {
+        Self::from(&TileProperties::default())
+    }
+}
+
+impl From<&MapMaterial> for Material {
+    /// How objects bounce off the walls, without the effect on lights.
+    fn from(material: &MapMaterial) -> Self {
+        Self {
+            restitution: material.restitution,
+            static_friction: material.static_friction,
+            dynamic_friction: material.dynamic_friction,
+            stickiness: material.stickiness,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assets::map::fixtures::{debug_map, debug_map_with_tile},
+        geo::Segment,
+        init_logging,
+    };
+
+    use super::*;
+
+    #[test]
+    fn material_from_tiles() {
+        init_logging();
+
+        let map = debug_map();
+        assert_eq!(map.occlusion_materials.len(), map.occlusion_segments.len());
+        assert!(
+            map.occlusion_materials
+                .iter()
+                .all(|m| *m == MapMaterial::default())
+        );
+
+        // A lone tile of mud
+        let map = debug_map_with_tile(
+            r#"<property name="Restitution" type="float" value="0.1"/>
+            <property name="Stickiness" type="float" value="5"/>
+            <property name="KillsLight" type="bool" value="true"/>"#,
+            &[(15, 10)],
+        );
+
+        assert_eq!(map.occlusion_materials.len(), map.occlusion_segments.len());
+
+        // All sides of the lone tile are made of mud
+        let (w2, h2) = (map.width as f32 / 2.0, map.height as f32 / 2.0);
+        let (x, y) = (15.0 - w2, (map.height - 1 - 10) as f32 - h2);
+        let top = Segment::new((x, y + 1.0), (x + 1.0, y + 1.0)).unwrap();
+        let i = map
+            .occlusion_segments
+            .iter()
+            .position(|s| *s == top)
+            .unwrap();
+        let material = map.occlusion_materials[i];
+        assert_eq!(
+            material,
+            MapMaterial {
+                restitution: 0.1,
+                stickiness: 5.0,
+                kills_light: true,
+                ..Default::default()
+            }
+        );
+
+        let muddy = map
+            .occlusion_materials
+            .iter()
+            .filter(|m| m.kills_light)
+            .count();
+        assert_eq!(muddy, 4);
+    }
+
+    #[test]
+    fn material_one_way_and_filter() {
+        init_logging();
+
+        let segments_before = debug_map().occlusion_segments.len();
+
+        // A platform of two one-way tiles
+        let mut map = debug_map_with_tile(
+            r#"<property name="OneWay" type="bool" value="true"/>"#,
+            &[(15, 10), (16, 10)],
+        );
+
+        // Only the tops are walls
+        let (w2, h2) = (map.width as f32 / 2.0, map.height as f32 / 2.0);
+        let (x, y) = (15.0 - w2, (map.height - 1 - 10) as f32 - h2);
+        let one_way: Vec<_> = map
+            .occlusion_segments
+            .iter()
+            .zip(&map.occlusion_materials)
+            .filter(|(_, m)| m.one_way)
+            .map(|(s, _)| s.clone())
+            .collect();
+        assert!(
+            one_way
+                == [
+                    Segment::new((x, y + 1.0), (x + 1.0, y + 1.0)).unwrap(),
+                    Segment::new((x + 1.0, y + 1.0), (x + 2.0, y + 1.0)).unwrap(),
+                ]
+        );
+        assert_eq!(map.occlusion_segments.len(), segments_before + 2);
+
+        // The walls of a layer get its filter, the edges of the map collide with everything
+        let foreground = map.find_layer_mut("Foreground").unwrap();
+        foreground.properties.extend([
+            (
+                "CollisionGroup".to_string(),
+                tiled::PropertyValue::IntValue(2),
+            ),
+            (
+                "CollisionMask".to_string(),
+                tiled::PropertyValue::IntValue(6),
+            ),
+        ]);
+        map.refresh().unwrap();
+
+        let (walls, edges) = map
+            .occlusion_materials
+            .split_at(map.occlusion_materials.len() - 4);
+        assert!(walls.iter().all(|m| m.filter == CollisionFilter::new(2, 6)));
+        assert!(edges.iter().all(|m| m.filter == CollisionFilter::default()));
+    }
+}
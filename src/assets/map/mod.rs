@@ -0,0 +1,612 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result, ensure};
+use glam::{Vec2, Vec4, vec2, vec3, vec4};
+use log::warn;
+use winit::dpi::LogicalSize;
+
+use crate::{
+    assets::{
+        properties,
+        schema::{LayerProperties, MapProperties, ObjectProperties, TileProperties},
+    },
+    geo::{Point, Segment, VisibilityPolygon},
+    phys::filter::CollisionFilter,
+    view::Quad,
+};
+
+pub mod analysis;
+mod field;
+mod generator;
+mod grid;
+mod lantern;
+mod layer;
+mod material;
+pub mod path;
+mod tmx;
+
+pub use field::{MapField, MapLiquid};
+pub use generator::CaveGenerator;
+pub use grid::TileGrid;
+pub use layer::{Layer, LayerKind, MapObject, MapTile, ObjectLayer, TileLayer};
+pub use material::MapMaterial;
+
+#[derive(Clone)]
+pub struct Map {
+    pub name: String,
+    /// Where the map was loaded from
+    pub source: PathBuf,
+    pub properties: MapProperties,
+    /// Raw custom properties of the map
+    pub tiled_properties: tiled::Properties,
+
+    /// Tiles
+    pub width: u32,
+    /// Tiles
+    pub height: u32,
+    /// Pixels
+    pub tile_width: u32,
+    /// Pixels
+    pub tile_height: u32,
+
+    pub tilesets: Vec<Arc<tiled::Tileset>>,
+    /// Of the tiles that have any, for each of the tilesets
+    tile_properties: Vec<BTreeMap<tiled::TileId, TileProperties>>,
+    /// Index of each of the map tilesets in the assets
+    tileset_map: Vec<usize>,
+    pub layers: Vec<Layer>,
+    /// Of all objects in the object layers, by their ids
+    object_properties: BTreeMap<u32, ObjectProperties>,
+
+    pub occlusion_segments: Vec<Segment>,
+    /// Of the tile that each of the occlusion segments bounds
+    pub occlusion_materials: Vec<MapMaterial>,
+}
+
+impl Map {
+    pub(super) fn new(inner: tiled::Map, tileset_map: Vec<usize>) -> Result<Self> {
+        let name = inner
+            .source
+            .file_stem()
+            .context("Map path has no file stem?")?
+            .to_str()
+            .context("Map path file stem is not valid UTF-8?")?
+            .to_string();
+
+        ensure!(inner.orientation == tiled::Orientation::Orthogonal);
+
+        let layers = inner
+            .layers()
+            .map(Layer::from_tiled)
+            .collect::<Result<_>>()
+            .with_context(|| format!("Unsupported layer in map '{name}'"))?;
+
+        let mut s = Self {
+            name,
+            source: inner.source.clone(),
+            properties: MapProperties::default(),
+            tiled_properties: inner.properties.clone(),
+            width: inner.width,
+            height: inner.height,
+            tile_width: inner.tile_width,
+            tile_height: inner.tile_height,
+            tilesets: inner.tilesets().to_vec(),
+            tile_properties: Vec::new(),
+            tileset_map,
+            layers,
+            object_properties: BTreeMap::new(),
+            occlusion_segments: Vec::new(),
+            occlusion_materials: Vec::new(),
+        };
+        s.refresh()?;
+        s.warn_about_connectivity()?;
+
+        Ok(s)
+    }
+
+    /// Check the properties and recompute the data derived from the layers.
+    /// Should be called after the map is modified.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.properties = properties::deserialize(&self.tiled_properties)
+            .with_context(|| format!("Invalid properties of map '{}'", self.name))?;
+
+        self.recalculate_object_properties()?;
+        self.recalculate_tile_properties()?;
+        self.recalculate_occlusion_segments()?;
+
+        Ok(())
+    }
+
+    fn warn_about_connectivity(&self) -> Result<()> {
+        let report = self.connectivity()?;
+
+        for region in report.sealed_regions() {
+            let (x, y) = region.tiles[0];
+            warn!(
+                "Map '{}' has a sealed region of {} tiles at ({x}, {y}) that can't be reached from the rest of the map",
+                self.name,
+                region.size(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Depth-first search for a layer with the given name.
+    pub fn find_layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find_map(|layer| layer.find(name))
+    }
+
+    /// Depth-first search for a layer with the given name.
+    pub fn find_layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers
+            .iter_mut()
+            .find_map(|layer| layer.find_mut(name))
+    }
+
+    /// Make sure all objects in the map have valid properties, and keep them.
+    fn recalculate_object_properties(&mut self) -> Result<()> {
+        let mut object_properties = BTreeMap::new();
+
+        for layer in self.layers.iter().flat_map(Layer::flatten) {
+            if let LayerKind::Objects(ObjectLayer { objects }) = &layer.kind {
+                for object in objects {
+                    let properties = properties::deserialize(&object.properties).with_context(
+                        || {
+                            format!(
+                                "Invalid properties of object '{}' (id {}) in layer '{}' of map '{}'",
+                                object.name, object.id, layer.name, self.name,
+                            )
+                        },
+                    )?;
+                    object_properties.insert(object.id, properties);
+                }
+            }
+        }
+
+        self.object_properties = object_properties;
+        Ok(())
+    }
+
+    /// Custom properties of an object, as of the last [`Map::refresh`].
+    fn object_properties(&self, object: &MapObject) -> ObjectProperties {
+        self.object_properties
+            .get(&object.id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn size_tiles(&self) -> LogicalSize<u32> {
+        LogicalSize {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn quads(&self) -> Result<Vec<Quad>> {
+        let mut quads = Vec::new();
+
+        for (attributes, layer) in self.tile_layers()? {
+            if !attributes.visible {
+                continue;
+            }
+
+            quads.extend(self.quads_for_layer(&attributes, layer));
+        }
+
+        Ok(quads)
+    }
+
+    /// Collect all tile layers of the map, including the ones nested in groups,
+    /// together with their attributes combined with the attributes of the parent groups.
+    fn tile_layers(&self) -> Result<Vec<(LayerAttributes, &TileLayer)>> {
+        fn collect<'map>(
+            map: &str,
+            layers: &'map [Layer],
+            parent: &LayerAttributes,
+            result: &mut Vec<(LayerAttributes, &'map TileLayer)>,
+        ) -> Result<()> {
+            for layer in layers {
+                let attributes = parent.inherit(layer).with_context(|| {
+                    format!(
+                        "Invalid properties of layer '{}' in map '{map}'",
+                        layer.name
+                    )
+                })?;
+
+                match &layer.kind {
+                    LayerKind::Tiles(tiles) => {
+                        // The walls and the shadows don't move with the camera
+                        ensure!(
+                            !attributes.properties.occluding || attributes.parallax == Vec2::ONE,
+                            "Occluding layer '{}' in map '{map}' can't have parallax",
+                            layer.name
+                        );
+                        result.push((attributes, tiles))
+                    }
+                    LayerKind::Group(layers) => collect(map, layers, &attributes, result)?,
+                    // Objects are not rendered and don't occlude anything
+                    LayerKind::Objects(_) => (),
+                }
+            }
+
+            Ok(())
+        }
+
+        let mut result = Vec::new();
+        collect(
+            &self.name,
+            &self.layers,
+            &LayerAttributes::root(),
+            &mut result,
+        )?;
+        Ok(result)
+    }
+
+    fn recalculate_tile_properties(&mut self) -> Result<()> {
+        self.tile_properties = self
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                tileset
+                    .tiles()
+                    .map(|(id, tile)| {
+                        let properties =
+                            properties::deserialize(&tile.properties).with_context(|| {
+                                format!(
+                                    "Invalid properties of tile {id} in tileset '{}'",
+                                    tileset.name
+                                )
+                            })?;
+                        Ok((id, properties))
+                    })
+                    .collect::<Result<_>>()
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(())
+    }
+
+    /// Custom properties of a tile, the default ones for tiles without them.
+    fn tile_properties(&self, tile: MapTile) -> TileProperties {
+        self.tile_properties[tile.tileset]
+            .get(&tile.id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Material of the wall at the tile of the layer, `None` if there is no wall.
+    fn wall(
+        &self,
+        attributes: &LayerAttributes,
+        layer: &TileLayer,
+        x: i32,
+        y: i32,
+    ) -> Option<MapMaterial> {
+        let properties = self.tile_properties(layer.tile(x, y)?);
+
+        // Objects float in liquids and light passes through them, so they are not walls
+        (!properties.liquid).then(|| MapMaterial {
+            filter: CollisionFilter::new(
+                attributes.properties.collision_group,
+                attributes.properties.collision_mask,
+            ),
+            ..MapMaterial::from(&properties)
+        })
+    }
+
+    /// Whether the wall at the tile blocks objects from all sides, unlike the tops of one-way tiles.
+    fn is_solid(&self, attributes: &LayerAttributes, layer: &TileLayer, x: i32, y: i32) -> bool {
+        self.wall(attributes, layer, x, y)
+            .is_some_and(|material| !material.one_way)
+    }
+
+    /// Offset of the layer in tiles, with the Y axis pointing up.
+    fn layer_offset(&self, attributes: &LayerAttributes) -> Vec2 {
+        // Tiled offsets are in pixels, and the Y axis points down
+        vec2(
+            attributes.offset.x / self.tile_width as f32,
+            -attributes.offset.y / self.tile_height as f32,
+        )
+    }
+
+    fn quads_for_layer(
+        &self,
+        attributes: &LayerAttributes,
+        layer: &TileLayer,
+    ) -> impl Iterator<Item = Quad> {
+        let map_w = self.width;
+        let map_h = self.height;
+        // Shift all tiles to make (0.0, 0.0) be the map center
+        // TODO: figure out what's going on with the magic numbers
+        let map_offset = vec2(map_w as f32 - 1.0, map_h as f32 + 1.0) / -2.0;
+
+        let layer_offset = self.layer_offset(attributes);
+
+        let z = attributes.properties.z;
+        let tint = attributes
+            .tint
+            .with_w(attributes.tint.w * attributes.opacity);
+        let parallax = attributes.parallax;
+
+        let layer_w = layer.width;
+        let layer_h = layer.height;
+
+        (0..layer_w).flat_map(move |layer_x| {
+            (0..layer_h).filter_map(move |layer_y| {
+                if let Some(layer_tile) = layer.tile(layer_x as i32, layer_y as i32) {
+                    let tileset = &self.tilesets[layer_tile.tileset];
+
+                    let tile_id = layer_tile.id;
+                    let tileset_x = (tile_id % tileset.columns) * tileset.tile_width;
+                    let tileset_y = (tile_id / tileset.columns) * tileset.tile_height;
+
+                    let pos_x = layer_x as f32;
+                    let pos_y = (layer_h - layer_y) as f32;
+
+                    let pos = vec3(pos_x, pos_y, z) + (map_offset + layer_offset).extend(0.0);
+                    let dim = vec2(1.0, 1.0);
+                    let rot = 0.0;
+
+                    let tex_num = self.tileset_map[layer_tile.tileset] as u32;
+                    let tex_pos = vec2(tileset_x as f32, tileset_y as f32);
+                    let tex_dim = vec2(tileset.tile_width as f32, tileset.tile_height as f32);
+
+                    Some(Quad {
+                        pos,
+                        dim,
+                        rot,
+                        tex_num,
+                        tex_pos,
+                        tex_dim,
+                        tint,
+                        parallax,
+                    })
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    fn recalculate_occlusion_segments(&mut self) -> Result<()> {
+        let map_w2 = self.width as f32 / 2.0;
+        let map_h2 = self.height as f32 / 2.0;
+
+        let mut occlusion_segments = Vec::new();
+        let mut occlusion_materials = Vec::new();
+
+        // Note: hidden layers still occlude, visibility only affects rendering
+        for (attributes, layer) in self.tile_layers()? {
+            if !attributes.properties.occluding {
+                continue;
+            }
+
+            let layer_w = layer.width as i32;
+            let layer_h = layer.height as i32;
+            let offset = self.layer_offset(&attributes);
+
+            // One-way tiles do not hide the sides of their neighbours
+            let is_solid = |x: i32, y: i32| {
+                let x = x.clamp(0, layer_w);
+                let y = y.clamp(0, layer_h);
+                self.is_solid(&attributes, layer, x, y)
+            };
+
+            for x in 0..layer_w {
+                for y in 0..layer_h {
+                    let Some(material) = self.wall(&attributes, layer, x, y) else {
+                        continue;
+                    };
+
+                    let empty_up = !is_solid(x, y - 1);
+                    let empty_right = !is_solid(x + 1, y) && !material.one_way;
+                    let empty_down = !is_solid(x, y + 1) && !material.one_way;
+                    let empty_left = !is_solid(x - 1, y) && !material.one_way;
+
+                    let x = x as f32 - map_w2 + offset.x;
+                    let y = (layer_h - 1 - y) as f32 - map_h2 + offset.y;
+
+                    if empty_up {
+                        occlusion_segments
+                            .push(Segment::new((x, y + 1.0), (x + 1.0, y + 1.0)).unwrap());
+                    }
+
+                    if empty_right {
+                        occlusion_segments
+                            .push(Segment::new((x + 1.0, y), (x + 1.0, y + 1.0)).unwrap());
+                    }
+
+                    if empty_down {
+                        occlusion_segments.push(Segment::new((x, y), (x + 1.0, y)).unwrap());
+                    }
+
+                    if empty_left {
+                        occlusion_segments.push(Segment::new((x, y), (x, y + 1.0)).unwrap());
+                    }
+
+                    occlusion_materials.resize(occlusion_segments.len(), material);
+                }
+            }
+        }
+
+        // Also add map edges
+
+        let (map_w2, map_h2) = (map_w2 + 1.0, map_h2 + 1.0);
+
+        // Top edge
+        occlusion_segments.push(Segment::new((-map_w2, map_h2), (map_w2, map_h2)).unwrap());
+
+        // Right edge
+        occlusion_segments.push(Segment::new((map_w2, map_h2), (map_w2, -map_h2)).unwrap());
+
+        // Bottom edge
+        occlusion_segments.push(Segment::new((map_w2, -map_h2), (-map_w2, -map_h2)).unwrap());
+
+        // Left edge
+        occlusion_segments.push(Segment::new((-map_w2, -map_h2), (-map_w2, map_h2)).unwrap());
+
+        occlusion_materials.resize(occlusion_segments.len(), MapMaterial::default());
+
+        self.occlusion_segments = occlusion_segments;
+        self.occlusion_materials = occlusion_materials;
+
+        Ok(())
+    }
+
+    pub fn visibility_for(&self, point: Point) -> VisibilityPolygon {
+        VisibilityPolygon::compute(point, &self.occlusion_segments)
+    }
+}
+
+/// Attributes of a layer combined with the attributes of all of its parent groups.
+struct LayerAttributes {
+    /// Custom properties, the ones of the layer override the ones of the parents
+    tiled_properties: tiled::Properties,
+    properties: LayerProperties,
+    visible: bool,
+    opacity: f32,
+    tint: Vec4,
+    /// Pixels, the Y axis points down
+    offset: Vec2,
+    parallax: Vec2,
+}
+
+impl LayerAttributes {
+    fn root() -> Self {
+        Self {
+            tiled_properties: tiled::Properties::new(),
+            properties: LayerProperties::default(),
+            visible: true,
+            opacity: 1.0,
+            tint: Vec4::ONE,
+            offset: Vec2::ZERO,
+            parallax: Vec2::ONE,
+        }
+    }
+
+    fn inherit(&self, layer: &Layer) -> Result<Self> {
+        let mut tiled_properties = self.tiled_properties.clone();
+        tiled_properties.extend(layer.properties.clone());
+        let properties = properties::deserialize(&tiled_properties)?;
+
+        let tint = match layer.tint {
+            Some(tiled::Color {
+                alpha,
+                red,
+                green,
+                blue,
+            }) => vec4(red as f32, green as f32, blue as f32, alpha as f32) / 255.0,
+            None => Vec4::ONE,
+        };
+
+        Ok(Self {
+            tiled_properties,
+            properties,
+            visible: self.visible && layer.visible,
+            opacity: self.opacity * layer.opacity,
+            tint: self.tint * tint,
+            offset: self.offset + layer.offset,
+            parallax: self.parallax * layer.parallax,
+        })
+    }
+}
+
+/// Maps shared by the tests of the map modules and of the rest of the game.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::{
+        io::Cursor,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use crate::assets::{
+        Assets,
+        config::Config,
+        map::{LayerKind, Map, MapTile},
+    };
+
+    /// Tile of the tileset that [`debug_map_with_tile`] gives custom properties to.
+    pub const TILE: MapTile = MapTile {
+        tileset: 0,
+        id: 200,
+    };
+
+    pub fn load_assets() -> Assets {
+        let dir_assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let config = Config::load(dir_assets.join("config.toml")).unwrap();
+        Assets::resolve(config, dir_assets).unwrap()
+    }
+
+    /// A copy of the `debug-01` map, to modify.
+    pub fn debug_map() -> Map {
+        let assets = load_assets();
+        let (_, map) = assets.find_map("debug-01").unwrap();
+        map.clone()
+    }
+
+    /// A copy of `debug-01` where [`TILE`] has the custom properties,
+    /// given as `<property>` elements of the TSX, and is painted at the positions on the `Foreground` layer.
+    pub fn debug_map_with_tile(properties_xml: &str, positions: &[(i32, i32)]) -> Map {
+        let mut map = debug_map();
+
+        let source = map.tilesets[TILE.tileset].source.clone();
+        let tsx = std::fs::read_to_string(&source).unwrap().replace(
+            "<wangsets>",
+            &format!(
+                r#"<tile id="{}"><properties>{properties_xml}</properties></tile>
+                <wangsets>"#,
+                TILE.id
+            ),
+        );
+        let mut loader = tiled::Loader::with_reader(|_: &Path| {
+            std::io::Result::Ok(Cursor::new(tsx.clone().into_bytes()))
+        });
+        map.tilesets[TILE.tileset] =
+            Arc::new(loader.load_tsx_tileset(PathBuf::from(&source)).unwrap());
+
+        let LayerKind::Tiles(tiles) = &mut map.find_layer_mut("Foreground").unwrap().kind else {
+            panic!("Foreground is not a tile layer");
+        };
+        for &(x, y) in positions {
+            tiles.set_tile(x, y, Some(TILE));
+        }
+
+        map.refresh().unwrap();
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use crate::{assets::map::fixtures::debug_map, geo::Segment, init_logging};
+
+    #[test]
+    fn map_walls_follow_layer_offset() {
+        init_logging();
+
+        let mut map = debug_map();
+        let before = map.occlusion_segments.clone();
+
+        // One tile to the right and two tiles up
+        let (tw, th) = (map.tile_width as f32, map.tile_height as f32);
+        map.find_layer_mut("Foreground").unwrap().offset = vec2(tw, -2.0 * th);
+        map.refresh().unwrap();
+
+        // Apart from the map edges at the end
+        let edges = 4;
+        assert_eq!(map.occlusion_segments.len(), before.len());
+        for (moved, segment) in map.occlusion_segments.iter().zip(&before).rev().skip(edges) {
+            let (a, b) = segment.ab();
+            let shifted = Segment::new(a + vec2(1.0, 2.0), b + vec2(1.0, 2.0)).unwrap();
+            assert!(*moved == shifted, "{:?} != {:?}", moved.ab(), shifted.ab());
+        }
+
+        // Walls can't move with the camera
+        map.find_layer_mut("Foreground").unwrap().parallax = vec2(0.5, 1.0);
+        assert!(map.refresh().is_err());
+    }
+}
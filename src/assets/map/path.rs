@@ -0,0 +1,161 @@
+use crate::{
+    assets::map::{Layer, LayerKind, Map, MapObject, ObjectLayer},
+    geo::Point,
+};
+
+/// Path of a moving platform, made from a polyline or a polygon object with a speed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapPath {
+    /// Id of the object
+    pub id: u32,
+    /// World coordinates
+    pub points: Vec<Point>,
+    /// Polygons are closed, polylines are not
+    pub closed: bool,
+    /// Tiles per second
+    pub speed: f32,
+    pub ping_pong: bool,
+    /// Tiles
+    pub platform_width: f32,
+    /// Tiles
+    pub platform_height: f32,
+}
+
+impl Map {
+    /// Paths of all moving platforms in the map.
+    pub fn paths(&self) -> Vec<MapPath> {
+        let mut paths = Vec::new();
+
+        for layer in self.layers.iter().flat_map(Layer::flatten) {
+            let LayerKind::Objects(ObjectLayer { objects }) = &layer.kind else {
+                continue;
+            };
+
+            for object in objects {
+                let properties = self.object_properties(object);
+
+                if properties.speed <= 0.0 {
+                    continue;
+                }
+
+                let (points, closed) = match &object.shape {
+                    tiled::ObjectShape::Polyline { points } => (points, false),
+                    tiled::ObjectShape::Polygon { points } => (points, true),
+                    _ => continue,
+                };
+
+                paths.push(MapPath {
+                    id: object.id,
+                    points: points
+                        .iter()
+                        .map(|&(x, y)| self.object_point_to_world(object, x, y))
+                        .collect(),
+                    closed,
+                    speed: properties.speed,
+                    ping_pong: properties.ping_pong,
+                    platform_width: properties.platform_width,
+                    platform_height: properties.platform_height,
+                });
+            }
+        }
+
+        paths
+    }
+
+    /// Convert a point of the object, relative to its position, to world coordinates.
+    pub(super) fn object_point_to_world(&self, object: &MapObject, x: f32, y: f32) -> Point {
+        // Clockwise on the screen, as the Y axis points down
+        let (sin, cos) = object.rotation.to_radians().sin_cos();
+        let px = object.x + x * cos - y * sin;
+        let py = object.y + x * sin + y * cos;
+
+        Point::new(
+            px / self.tile_width as f32 - self.width as f32 / 2.0,
+            self.height as f32 / 2.0 - py / self.tile_height as f32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assets::map::fixtures::debug_map, init_logging};
+
+    use super::*;
+
+    #[test]
+    fn path_from_polyline() {
+        init_logging();
+
+        let mut map = debug_map();
+
+        let (tw, th) = (map.tile_width as f32, map.tile_height as f32);
+        let object = |id, shape, properties: &[(&str, tiled::PropertyValue)]| MapObject {
+            id,
+            name: String::new(),
+            user_type: String::new(),
+            // The middle of the map
+            x: map.width as f32 * tw / 2.0,
+            y: map.height as f32 * th / 2.0,
+            rotation: 0.0,
+            visible: true,
+            shape,
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        };
+
+        let polyline = tiled::ObjectShape::Polyline {
+            points: vec![(0.0, 0.0), (2.0 * tw, 0.0), (2.0 * tw, -th)],
+        };
+        let speed = ("Speed", tiled::PropertyValue::FloatValue(1.5));
+        let ping_pong = ("PingPong", tiled::PropertyValue::BoolValue(true));
+
+        map.layers.push(Layer::new(
+            100,
+            "Paths",
+            LayerKind::Objects(ObjectLayer {
+                objects: vec![
+                    object(1, polyline.clone(), &[speed.clone(), ping_pong]),
+                    // Not moving
+                    object(2, polyline, &[]),
+                ],
+            }),
+        ));
+        map.refresh().unwrap();
+
+        let paths = map.paths();
+        assert_eq!(
+            paths,
+            vec![MapPath {
+                id: 1,
+                points: vec![
+                    Point::new(0.0, 0.0),
+                    Point::new(2.0, 0.0),
+                    Point::new(2.0, 1.0)
+                ],
+                closed: false,
+                speed: 1.5,
+                ping_pong: true,
+                platform_width: 2.0,
+                platform_height: 0.5,
+            }]
+        );
+
+        // Invalid properties are found when the map is refreshed, not when the paths are read
+        let LayerKind::Objects(ObjectLayer { objects }) =
+            &mut map.find_layer_mut("Paths").unwrap().kind
+        else {
+            panic!("Paths is not an object layer");
+        };
+        objects[1].properties.insert(
+            "Speed".to_string(),
+            tiled::PropertyValue::StringValue("fast".to_string()),
+        );
+        let error = map.refresh().unwrap_err();
+        assert!(
+            format!("{error:#}").contains("(id 2) in layer 'Paths'"),
+            "{error:#}"
+        );
+    }
+}
@@ -0,0 +1,593 @@
+//! Writing maps back into the TMX format.
+//!
+//! Based on https://doc.mapeditor.org/en/stable/reference/tmx-map-format/
+
+use std::{
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use log::debug;
+
+use crate::assets::map::{Layer, LayerKind, Map, MapObject, TileLayer};
+
+impl Map {
+    /// Save the map into a TMX file.
+    /// Tilesets are referenced relative to the new location of the map.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        debug!("Saving map '{}' to '{}'", self.name, path.to_string_lossy());
+
+        let tmx = self.to_tmx(path)?;
+        std::fs::write(path, tmx)?;
+
+        Ok(())
+    }
+
+    /// Serialize the map into the TMX format, as if it was saved to `path`.
+    pub fn to_tmx(&self, path: impl AsRef<Path>) -> Result<String> {
+        let dir = path
+            .as_ref()
+            .parent()
+            .context("Map path has no parent directory?")?;
+
+        let mut w = TmxWriter::default();
+
+        let next_layer_id = self
+            .layers
+            .iter()
+            .flat_map(Layer::flatten)
+            .map(|layer| layer.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let next_object_id = self
+            .layers
+            .iter()
+            .flat_map(Layer::flatten)
+            .filter_map(|layer| match &layer.kind {
+                LayerKind::Objects(objects) => objects.objects.iter().map(|o| o.id).max(),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        w.line(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        w.open(
+            "map",
+            &[
+                ("version", "1.10".to_string()),
+                ("orientation", "orthogonal".to_string()),
+                ("renderorder", "right-down".to_string()),
+                ("width", self.width.to_string()),
+                ("height", self.height.to_string()),
+                ("tilewidth", self.tile_width.to_string()),
+                ("tileheight", self.tile_height.to_string()),
+                ("infinite", "0".to_string()),
+                ("nextlayerid", next_layer_id.to_string()),
+                ("nextobjectid", next_object_id.to_string()),
+            ],
+        );
+
+        w.properties(&self.tiled_properties);
+
+        for (tileset, first_gid) in self.tilesets.iter().zip(self.first_gids()) {
+            ensure!(
+                tileset.source.extension().is_some_and(|e| e == "tsx"),
+                "Only external tilesets are supported, '{}' is embedded",
+                tileset.name
+            );
+
+            w.empty(
+                "tileset",
+                &[
+                    ("firstgid", first_gid.to_string()),
+                    ("source", relative_path(dir, &tileset.source)?),
+                ],
+            );
+        }
+
+        for layer in &self.layers {
+            self.write_layer(&mut w, layer)?;
+        }
+
+        w.close("map");
+
+        Ok(w.output)
+    }
+
+    fn write_layer(&self, w: &mut TmxWriter, layer: &Layer) -> Result<()> {
+        let mut attrs = vec![("id", layer.id.to_string()), ("name", layer.name.clone())];
+
+        if let LayerKind::Tiles(tiles) = &layer.kind {
+            attrs.push(("width", tiles.width.to_string()));
+            attrs.push(("height", tiles.height.to_string()));
+        }
+
+        if !layer.visible {
+            attrs.push(("visible", "0".to_string()));
+        }
+        if layer.opacity != 1.0 {
+            attrs.push(("opacity", layer.opacity.to_string()));
+        }
+        if let Some(tint) = layer.tint {
+            attrs.push(("tintcolor", color(tint)));
+        }
+        if layer.offset.x != 0.0 {
+            attrs.push(("offsetx", layer.offset.x.to_string()));
+        }
+        if layer.offset.y != 0.0 {
+            attrs.push(("offsety", layer.offset.y.to_string()));
+        }
+        if layer.parallax.x != 1.0 {
+            attrs.push(("parallaxx", layer.parallax.x.to_string()));
+        }
+        if layer.parallax.y != 1.0 {
+            attrs.push(("parallaxy", layer.parallax.y.to_string()));
+        }
+
+        let tag = match &layer.kind {
+            LayerKind::Tiles(_) => "layer",
+            LayerKind::Objects(_) => "objectgroup",
+            LayerKind::Group(_) => "group",
+        };
+
+        w.open(tag, &attrs);
+        w.properties(&layer.properties);
+
+        match &layer.kind {
+            LayerKind::Tiles(tiles) => self.write_tiles(w, tiles)?,
+            LayerKind::Objects(objects) => {
+                for object in &objects.objects {
+                    write_object(w, object)?;
+                }
+            }
+            LayerKind::Group(layers) => {
+                for layer in layers {
+                    self.write_layer(w, layer)?;
+                }
+            }
+        }
+
+        w.close(tag);
+
+        Ok(())
+    }
+
+    /// Tiled assigns global tile ids to the tilesets consecutively, starting from 1.
+    fn first_gids(&self) -> Vec<u32> {
+        self.tilesets
+            .iter()
+            .scan(1, |first_gid, tileset| {
+                let current = *first_gid;
+                *first_gid += tileset.tilecount;
+                Some(current)
+            })
+            .collect()
+    }
+
+    fn write_tiles(&self, w: &mut TmxWriter, tiles: &TileLayer) -> Result<()> {
+        let first_gids = self.first_gids();
+
+        // Tiled puts the CSV data on separate lines, without indentation
+        w.open("data", &[("encoding", "csv".to_string())]);
+
+        let rows: Vec<_> = tiles.rows().collect();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                let gid = match tile {
+                    Some(tile) => {
+                        let first_gid = first_gids
+                            .get(tile.tileset)
+                            .with_context(|| format!("No tileset {}", tile.tileset))?;
+                        first_gid + tile.id
+                    }
+                    None => 0,
+                };
+
+                write!(w.output, "{gid}")?;
+
+                let last = y + 1 == rows.len() && x + 1 == row.len();
+                if !last {
+                    w.output.push(',');
+                }
+            }
+            w.output.push('\n');
+        }
+
+        // The closing tag is not indented either
+        w.depth -= 1;
+        w.output.push_str("</data>\n");
+
+        Ok(())
+    }
+}
+
+fn write_object(w: &mut TmxWriter, object: &MapObject) -> Result<()> {
+    let mut attrs = vec![("id", object.id.to_string())];
+
+    if !object.name.is_empty() {
+        attrs.push(("name", object.name.clone()));
+    }
+    if !object.user_type.is_empty() {
+        attrs.push(("type", object.user_type.clone()));
+    }
+
+    attrs.push(("x", object.x.to_string()));
+    attrs.push(("y", object.y.to_string()));
+
+    match &object.shape {
+        tiled::ObjectShape::Rect { width, height }
+        | tiled::ObjectShape::Ellipse { width, height } => {
+            attrs.push(("width", width.to_string()));
+            attrs.push(("height", height.to_string()));
+        }
+        _ => (),
+    }
+
+    if object.rotation != 0.0 {
+        attrs.push(("rotation", object.rotation.to_string()));
+    }
+    if !object.visible {
+        attrs.push(("visible", "0".to_string()));
+    }
+
+    let points = |points: &[(f32, f32)]| {
+        let points: Vec<_> = points.iter().map(|(x, y)| format!("{x},{y}")).collect();
+        vec![("points", points.join(" "))]
+    };
+
+    let shape = match &object.shape {
+        tiled::ObjectShape::Rect { .. } => None,
+        tiled::ObjectShape::Ellipse { .. } => Some(("ellipse", vec![])),
+        tiled::ObjectShape::Point(_, _) => Some(("point", vec![])),
+        tiled::ObjectShape::Polyline { points: p } => Some(("polyline", points(p))),
+        tiled::ObjectShape::Polygon { points: p } => Some(("polygon", points(p))),
+        tiled::ObjectShape::Text { .. } => bail!("Text objects are not supported"),
+    };
+
+    if object.properties.is_empty() && shape.is_none() {
+        w.empty("object", &attrs);
+        return Ok(());
+    }
+
+    w.open("object", &attrs);
+    w.properties(&object.properties);
+    if let Some((tag, attrs)) = shape {
+        w.empty(tag, &attrs);
+    }
+    w.close("object");
+
+    Ok(())
+}
+
+fn color(color: tiled::Color) -> String {
+    let tiled::Color {
+        alpha,
+        red,
+        green,
+        blue,
+    } = color;
+    format!("#{alpha:02x}{red:02x}{green:02x}{blue:02x}")
+}
+
+/// Resolve `.` and `..` without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if result.file_name().is_some() => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// Path to `to` relative to the directory `from`, with forward slashes like Tiled writes them.
+fn relative_path(from: &Path, to: &Path) -> Result<String> {
+    let from = normalize(from);
+    let to = normalize(to);
+
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let ups = from.components().count() - common;
+    let mut parts: Vec<String> = vec!["..".to_string(); ups];
+
+    for component in to.components().skip(common) {
+        parts.push(
+            component
+                .as_os_str()
+                .to_str()
+                .context("Tileset path is not valid UTF-8")?
+                .to_string(),
+        );
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// Writes indented XML line by line.
+#[derive(Default)]
+struct TmxWriter {
+    output: String,
+    depth: usize,
+}
+
+impl TmxWriter {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.output.push(' ');
+        }
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn tag(tag: &str, attrs: &[(&str, String)]) -> String {
+        let mut result = format!("<{tag}");
+        for (name, value) in attrs {
+            result.push_str(&format!(" {name}=\"{}\"", escape(value)));
+        }
+        result
+    }
+
+    fn open(&mut self, tag: &str, attrs: &[(&str, String)]) {
+        self.line(&format!("{}>", Self::tag(tag, attrs)));
+        self.depth += 1;
+    }
+
+    fn empty(&mut self, tag: &str, attrs: &[(&str, String)]) {
+        self.line(&format!("{}/>", Self::tag(tag, attrs)));
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.line(&format!("</{tag}>"));
+    }
+
+    fn properties(&mut self, properties: &tiled::Properties) {
+        if properties.is_empty() {
+            return;
+        }
+
+        // Tiled writes properties sorted by name
+        let mut properties: Vec<_> = properties.iter().collect();
+        properties.sort_by_key(|(name, _)| *name);
+
+        self.open("properties", &[]);
+
+        for (name, value) in properties {
+            use tiled::PropertyValue as PV;
+
+            let name = ("name", name.clone());
+
+            let (ty, value) = match value {
+                PV::BoolValue(value) => ("bool", value.to_string()),
+                PV::FloatValue(value) => ("float", value.to_string()),
+                PV::IntValue(value) => ("int", value.to_string()),
+                PV::ColorValue(value) => ("color", color(*value)),
+                PV::StringValue(value) => ("string", value.clone()),
+                PV::FileValue(value) => ("file", value.clone()),
+                PV::ObjectValue(value) => ("object", value.to_string()),
+                PV::ClassValue {
+                    property_type,
+                    properties,
+                } => {
+                    self.open(
+                        "property",
+                        &[
+                            name,
+                            ("type", "class".to_string()),
+                            ("propertytype", property_type.clone()),
+                        ],
+                    );
+                    self.properties(properties);
+                    self.close("property");
+                    continue;
+                }
+            };
+
+            if ty == "string" {
+                self.empty("property", &[name, ("value", value)]);
+            } else {
+                self.empty(
+                    "property",
+                    &[name, ("type", ty.to_string()), ("value", value)],
+                );
+            }
+        }
+
+        self.close("properties");
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\n' => result.push_str("&#10;"),
+            '\r' => result.push_str("&#13;"),
+            '\t' => result.push_str("&#9;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Read},
+        path::PathBuf,
+    };
+
+    use crate::{
+        assets::map::{MapTile, ObjectLayer, fixtures::load_assets},
+        init_logging,
+    };
+
+    use super::*;
+
+    /// Serves one file from memory, and the rest from the file system.
+    struct InMemoryReader {
+        path: PathBuf,
+        contents: String,
+    }
+
+    impl tiled::ResourceReader for InMemoryReader {
+        type Resource = Box<dyn Read>;
+        type Error = std::io::Error;
+
+        fn read_from(&mut self, path: &Path) -> std::result::Result<Self::Resource, Self::Error> {
+            if path == self.path {
+                Ok(Box::new(Cursor::new(self.contents.clone().into_bytes())))
+            } else {
+                Ok(Box::new(std::fs::File::open(path)?))
+            }
+        }
+    }
+
+    fn reload(map: &Map) -> Map {
+        let tmx = map.to_tmx(&map.source).unwrap();
+
+        let mut loader = tiled::Loader::with_reader(InMemoryReader {
+            path: map.source.clone(),
+            contents: tmx,
+        });
+
+        Map::new(
+            loader.load_tmx_map(&map.source).unwrap(),
+            map.tileset_map.clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tmx_relative_path() {
+        assert_eq!(
+            relative_path(
+                Path::new("/a/assets/maps"),
+                Path::new("/a/assets/maps/../tiles/main.tsx")
+            )
+            .unwrap(),
+            "../tiles/main.tsx"
+        );
+        assert_eq!(
+            relative_path(Path::new("/a/b"), Path::new("/a/b/c.tsx")).unwrap(),
+            "c.tsx"
+        );
+    }
+
+    #[test]
+    fn tmx_escape() {
+        // Whitespace other than spaces would be normalized away in attributes
+        assert_eq!(
+            escape("<a & \"b\">\tc\r\n"),
+            "&lt;a &amp; &quot;b&quot;&gt;&#9;c&#13;&#10;"
+        );
+    }
+
+    #[test]
+    fn tmx_round_trip() {
+        init_logging();
+
+        let assets = load_assets();
+        let (_, map) = assets.find_map("debug-01").unwrap();
+
+        let reloaded = reload(map);
+
+        assert_eq!(map.layers, reloaded.layers);
+        assert_eq!(map.quads().unwrap(), reloaded.quads().unwrap());
+        assert!(map.occlusion_segments == reloaded.occlusion_segments);
+
+        // The original file should be reproduced exactly
+        let original = std::fs::read_to_string(&map.source).unwrap();
+        let saved = map.to_tmx(&map.source).unwrap();
+        let skip_header = |s: &str| s.lines().skip(2).collect::<Vec<_>>().join("\n");
+        assert_eq!(skip_header(&original), skip_header(&saved));
+    }
+
+    #[test]
+    fn tmx_round_trip_modified() {
+        init_logging();
+
+        let assets = load_assets();
+        let (_, map) = assets.find_map("debug-01").unwrap();
+
+        let mut map = map.clone();
+        let segments_before = map.occlusion_segments.len();
+
+        // Paint a lonely wall tile in the middle of the map
+        let foreground = map.find_layer_mut("Foreground").unwrap();
+        let LayerKind::Tiles(tiles) = &mut foreground.kind else {
+            panic!("Foreground is not a tile layer");
+        };
+        assert_eq!(tiles.tile(16, 10), None);
+        tiles.set_tile(16, 10, Some(MapTile { tileset: 0, id: 17 }));
+
+        // Add a spawn point with a property
+        map.layers.push(Layer::new(
+            100,
+            "Spawns & Stuff",
+            LayerKind::Objects(ObjectLayer {
+                objects: vec![MapObject {
+                    id: 100,
+                    name: "Spawn".to_string(),
+                    user_type: String::new(),
+                    x: 8.0,
+                    y: 24.5,
+                    rotation: 0.0,
+                    visible: true,
+                    shape: tiled::ObjectShape::Point(8.0, 24.5),
+                    properties: [(
+                        "Note".to_string(),
+                        tiled::PropertyValue::StringValue(
+                            "<first> \"one\"\tthen\r\nsecond".to_string(),
+                        ),
+                    )]
+                    .into(),
+                }],
+            }),
+        ));
+
+        map.refresh().unwrap();
+        assert_eq!(map.occlusion_segments.len(), segments_before + 4);
+
+        let reloaded = reload(&map);
+
+        assert_eq!(map.layers, reloaded.layers);
+        assert_eq!(map.quads().unwrap(), reloaded.quads().unwrap());
+        assert!(map.occlusion_segments == reloaded.occlusion_segments);
+    }
+
+    #[test]
+    fn tmx_round_trip_generated() {
+        init_logging();
+
+        let assets = load_assets();
+        let (_, map) = assets.find_map("caves-01").unwrap();
+
+        // The generated map shares the tileset with the hand-made ones
+        assert_eq!(assets.all_tilesets().count(), 1);
+        assert!(map.occlusion_segments.len() > 4);
+
+        let reloaded = reload(map);
+
+        assert_eq!(map.layers, reloaded.layers);
+        assert_eq!(map.quads().unwrap(), reloaded.quads().unwrap());
+        assert!(map.occlusion_segments == reloaded.occlusion_segments);
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::{Context, Result, bail};
 use log::debug;
@@ -8,20 +9,25 @@ use log::debug;
 mod config;
 mod light;
 mod map;
+mod properties;
+mod schema;
 mod texture;
 mod tileset;
 
 pub use config::Config;
 pub use config::Shape;
 pub use light::LightSource;
-pub use map::Map;
+pub use map::{CaveGenerator, Map, MapField, MapLiquid};
+pub use schema::{FieldKind, LinkKind};
 pub use texture::TextureData;
 pub use texture::TexturePixel;
 pub use tileset::Tileset;
 pub use tileset::TilesetId;
 
 pub struct Assets {
-    pub max_timestep: f32,
+    pub timestep: f32,
+    /// Name of the map to play on
+    pub map: String,
 
     lights: Vec<LightSource>,
     shaders: BTreeMap<String, String>,
@@ -33,9 +39,10 @@ pub struct Assets {
 }
 
 impl Assets {
-    fn empty(max_timestep: f32) -> Self {
+    fn empty(timestep: f32, map: String) -> Self {
         Self {
-            max_timestep,
+            timestep,
+            map,
 
             lights: Vec::new(),
             shaders: BTreeMap::new(),
@@ -55,7 +62,7 @@ impl Assets {
             path.to_string_lossy()
         );
 
-        let mut s = Self::empty(config.max_timestep);
+        let mut s = Self::empty(config.timestep, config.map);
 
         let path_lights = path.join("textures");
         for light in config.lights {
@@ -69,7 +76,7 @@ impl Assets {
 
         let path_maps = path.join("maps");
         let path_maps_extension = OsStr::new("tmx");
-        for entry in std::fs::read_dir(path_maps)? {
+        for entry in std::fs::read_dir(&path_maps)? {
             let entry = entry?;
             let entry_path = entry.path();
             if entry_path.is_file() && entry_path.extension() == Some(path_maps_extension) {
@@ -77,6 +84,10 @@ impl Assets {
             }
         }
 
+        for map in config.generated_maps {
+            s.generate_map(map, &path_maps)?;
+        }
+
         Ok(s)
     }
 
@@ -137,20 +148,7 @@ impl Assets {
         let mut tileset_map = Vec::new();
 
         for tileset in map.tilesets() {
-            let tileset_id = TilesetId::new(tileset);
-            match self
-                .tilesets
-                .iter()
-                .enumerate()
-                .find(|(_, t)| tileset_id == t.id())
-            {
-                Some((i, _)) => tileset_map.push(i),
-                None => {
-                    let tileset = Tileset::load_for_map(tileset)?;
-                    self.tilesets.push(tileset);
-                    tileset_map.push(self.tilesets.len() - 1);
-                }
-            }
+            tileset_map.push(self.add_tileset(tileset)?);
         }
 
         self.maps.push(Map::new(map, tileset_map)?);
@@ -158,6 +156,52 @@ impl Assets {
         Ok(())
     }
 
+    fn generate_map(&mut self, map: config::GeneratedMap, path: impl AsRef<Path>) -> Result<()> {
+        if self.find_map(&map.name).is_ok() {
+            bail!("Map with the name '{}' already exists", map.name);
+        }
+
+        let path = path.as_ref();
+        let tileset = Arc::new(
+            self.tiled_loader
+                .load_tsx_tileset(path.join(&map.tileset))?,
+        );
+        let tileset_index = self.add_tileset(&tileset)?;
+
+        let generator = CaveGenerator {
+            width: map.width,
+            height: map.height,
+            seed: map.seed,
+            ..Default::default()
+        };
+        let map_path = path.join(format!("{}.tmx", map.name));
+        let generated = generator.generate(map_path, &tileset, tileset_index)?;
+        if map.save {
+            generated.save(&generated.source)?;
+        }
+        self.maps.push(generated);
+
+        Ok(())
+    }
+
+    /// Index of the tileset in the assets, it is loaded if it's not there yet.
+    fn add_tileset(&mut self, tileset: &Arc<tiled::Tileset>) -> Result<usize> {
+        let tileset_id = TilesetId::new(tileset);
+        match self
+            .tilesets
+            .iter()
+            .enumerate()
+            .find(|(_, t)| tileset_id == t.id())
+        {
+            Some((i, _)) => Ok(i),
+            None => {
+                let tileset = Tileset::load_for_map(tileset)?;
+                self.tilesets.push(tileset);
+                Ok(self.tilesets.len() - 1)
+            }
+        }
+    }
+
     pub fn find_map(&self, name: &str) -> Result<(usize, &Map)> {
         self.maps
             .iter()
@@ -0,0 +1,242 @@
+//! Deserialization of Tiled custom properties into typed structures.
+//!
+//! Property values are mapped onto the serde data model as follows:
+//! - `bool`, `int`, `float` -> the corresponding primitives;
+//! - `string`, `file` -> strings (and unit enum variants);
+//! - `color` -> a `#AARRGGBB` string;
+//! - `object` -> the referenced object id;
+//! - `class` -> a nested map.
+
+use std::fmt::Display;
+
+use serde::{
+    Deserializer,
+    de::{self, DeserializeOwned, IntoDeserializer, MapAccess, Visitor},
+    forward_to_deserialize_any,
+};
+
+#[derive(Debug)]
+pub struct PropertyError(String);
+
+impl PropertyError {
+    fn in_property(self, name: &str) -> Self {
+        Self(format!("property '{name}': {}", self.0))
+    }
+}
+
+impl Display for PropertyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PropertyError {}
+
+impl de::Error for PropertyError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Self(format!("property '{field}' is missing"))
+    }
+}
+
+/// Deserialize custom properties into `T`.
+///
+/// Properties that `T` does not know about are ignored.
+pub fn deserialize<T: DeserializeOwned>(
+    properties: &tiled::Properties,
+) -> Result<T, PropertyError> {
+    T::deserialize(PropertiesDeserializer { properties })
+}
+
+struct PropertiesDeserializer<'p> {
+    properties: &'p tiled::Properties,
+}
+
+impl<'de> Deserializer<'de> for PropertiesDeserializer<'_> {
+    type Error = PropertyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        // Sort the properties to make the errors reproducible
+        let mut properties: Vec<_> = self.properties.iter().collect();
+        properties.sort_by_key(|(name, _)| *name);
+
+        visitor.visit_map(PropertiesAccess {
+            properties: properties.into_iter(),
+            current: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct PropertiesAccess<'p, I: Iterator<Item = (&'p String, &'p tiled::PropertyValue)>> {
+    properties: I,
+    current: Option<(&'p String, &'p tiled::PropertyValue)>,
+}
+
+impl<'de, 'p, I> MapAccess<'de> for PropertiesAccess<'p, I>
+where
+    I: Iterator<Item = (&'p String, &'p tiled::PropertyValue)>,
+{
+    type Error = PropertyError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        self.current = self.properties.next();
+
+        match self.current {
+            Some((name, _)) => seed
+                .deserialize(name.as_str().into_deserializer())
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (name, value) = self
+            .current
+            .take()
+            .expect("next_value_seed is called after next_key_seed");
+
+        seed.deserialize(PropertyValueDeserializer { value })
+            .map_err(|err| err.in_property(name))
+    }
+}
+
+struct PropertyValueDeserializer<'p> {
+    value: &'p tiled::PropertyValue,
+}
+
+impl<'de> Deserializer<'de> for PropertyValueDeserializer<'_> {
+    type Error = PropertyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        use tiled::PropertyValue as PV;
+
+        match self.value {
+            PV::BoolValue(value) => visitor.visit_bool(*value),
+            PV::FloatValue(value) => visitor.visit_f32(*value),
+            PV::IntValue(value) => visitor.visit_i32(*value),
+            PV::ColorValue(tiled::Color {
+                alpha,
+                red,
+                green,
+                blue,
+            }) => visitor.visit_string(format!("#{alpha:02x}{red:02x}{green:02x}{blue:02x}")),
+            PV::StringValue(value) | PV::FileValue(value) => visitor.visit_str(value),
+            PV::ObjectValue(value) => visitor.visit_u32(*value),
+            PV::ClassValue { properties, .. } => {
+                PropertiesDeserializer { properties }.deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value {
+            tiled::PropertyValue::StringValue(value) => {
+                visitor.visit_enum(value.as_str().into_deserializer())
+            }
+            _ => self.deserialize_any(visitor).map_err(|err| {
+                PropertyError(format!("{err}, expected one of {variants:?} for {name}"))
+            }),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use tiled::PropertyValue as PV;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Example {
+        z: f32,
+        #[serde(default)]
+        occluding: bool,
+        kind: Option<Kind>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Kind {
+        Wind,
+        Water,
+    }
+
+    fn properties(entries: &[(&str, PV)]) -> tiled::Properties {
+        entries
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn properties_typed() {
+        let props = properties(&[
+            ("Z", PV::FloatValue(-0.5)),
+            ("Kind", PV::StringValue("Water".to_string())),
+            ("Unrelated", PV::IntValue(1)),
+        ]);
+
+        let example: Example = deserialize(&props).unwrap();
+
+        assert_eq!(
+            example,
+            Example {
+                z: -0.5,
+                occluding: false,
+                kind: Some(Kind::Water),
+            }
+        );
+    }
+
+    #[test]
+    fn properties_errors() {
+        let props = properties(&[]);
+        let err = deserialize::<Example>(&props).unwrap_err().to_string();
+        assert_eq!(err, "property 'Z' is missing");
+
+        let props = properties(&[("Z", PV::FloatValue(0.0)), ("Occluding", PV::IntValue(1))]);
+        let err = deserialize::<Example>(&props).unwrap_err().to_string();
+        assert!(
+            err.starts_with("property 'Occluding': invalid type"),
+            "{err}"
+        );
+
+        let props = properties(&[
+            ("Z", PV::FloatValue(0.0)),
+            ("Kind", PV::StringValue("Lava".to_string())),
+        ]);
+        let err = deserialize::<Example>(&props).unwrap_err().to_string();
+        assert!(err.starts_with("property 'Kind': unknown variant"), "{err}");
+    }
+}
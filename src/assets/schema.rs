@@ -0,0 +1,236 @@
+//! Custom properties understood by the game.
+//!
+//! Field names are converted to PascalCase to match the property names in Tiled.
+//! Fields without `#[serde(default)]` are required.
+
+use serde::Deserialize;
+
+/// Properties of the map itself.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MapProperties {
+    /// Acceleration towards the bottom of the map, tiles per second squared
+    #[serde(default = "default_gravity")]
+    pub gravity: f32,
+}
+
+impl Default for MapProperties {
+    fn default() -> Self {
+        Self {
+            gravity: default_gravity(),
+        }
+    }
+}
+
+fn default_gravity() -> f32 {
+    3.0
+}
+
+/// Properties of tile and group layers, inherited by the layers nested in groups.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LayerProperties {
+    /// Depth of the layer, higher is closer to the camera
+    #[serde(default)]
+    pub z: f32,
+    /// Whether the tiles of the layer block light and physics objects
+    #[serde(default)]
+    pub occluding: bool,
+    /// Bits of the collision layers that the walls of the layer are on
+    #[serde(default = "default_collision_group")]
+    pub collision_group: u32,
+    /// Bits of the collision layers of the objects that the walls of the layer block
+    #[serde(default = "default_collision_mask")]
+    pub collision_mask: u32,
+}
+
+impl Default for LayerProperties {
+    fn default() -> Self {
+        Self {
+            z: 0.0,
+            occluding: false,
+            collision_group: default_collision_group(),
+            collision_mask: default_collision_mask(),
+        }
+    }
+}
+
+/// Same as [`crate::phys::filter::CollisionFilter::default`].
+fn default_collision_group() -> u32 {
+    1
+}
+
+fn default_collision_mask() -> u32 {
+    u32::MAX
+}
+
+/// Properties of individual tiles in a tileset.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TileProperties {
+    /// Whether objects float in the tile
+    #[serde(default)]
+    pub liquid: bool,
+    /// Of the liquid, objects with the same mass per unit of area float half-submerged
+    #[serde(default = "default_liquid_density")]
+    pub density: f32,
+    /// Part of the velocity that submerged objects lose per second
+    #[serde(default = "default_liquid_drag")]
+    pub drag: f32,
+    /// Of the walls made of the tile, share of the approaching velocity that objects bounce back with
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    /// Of the walls, share of the normal impulse up to which objects do not slide
+    #[serde(default = "default_static_friction")]
+    pub static_friction: f32,
+    /// Of the walls, share of the normal impulse that is applied against sliding
+    #[serde(default = "default_dynamic_friction")]
+    pub dynamic_friction: f32,
+    /// Of the walls, acceleration that it takes to pull a touching object off, tiles per second squared
+    #[serde(default)]
+    pub stickiness: f32,
+    /// Whether lights that touch the walls go out
+    #[serde(default)]
+    pub kills_light: bool,
+    /// Whether the tile is a platform that objects can jump onto from below, only its top blocks them
+    #[serde(default)]
+    pub one_way: bool,
+}
+
+impl Default for TileProperties {
+    fn default() -> Self {
+        Self {
+            liquid: false,
+            density: default_liquid_density(),
+            drag: default_liquid_drag(),
+            restitution: default_restitution(),
+            static_friction: default_static_friction(),
+            dynamic_friction: default_dynamic_friction(),
+            stickiness: 0.0,
+            kills_light: false,
+            one_way: false,
+        }
+    }
+}
+
+fn default_liquid_density() -> f32 {
+    1.0
+}
+
+fn default_liquid_drag() -> f32 {
+    1.0
+}
+
+fn default_restitution() -> f32 {
+    0.9
+}
+
+fn default_static_friction() -> f32 {
+    0.4
+}
+
+fn default_dynamic_friction() -> f32 {
+    0.3
+}
+
+/// Properties of objects in object layers.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ObjectProperties {
+    /// Polylines and polygons with a speed are paths of moving platforms, tiles per second
+    #[serde(default)]
+    pub speed: f32,
+    /// Whether the platform goes back and forth instead of going around the path
+    #[serde(default)]
+    pub ping_pong: bool,
+    /// Tiles
+    #[serde(default = "default_platform_width")]
+    pub platform_width: f32,
+    /// Tiles
+    #[serde(default = "default_platform_height")]
+    pub platform_height: f32,
+    /// Rectangles and ellipses with a field are force field regions
+    #[serde(default)]
+    pub field: Option<FieldKind>,
+    /// Acceleration of wind, attractors and repellers, tiles per second squared.
+    /// For drag, the part of the velocity that is lost per second
+    #[serde(default)]
+    pub strength: f32,
+    /// Where the wind blows to, degrees counterclockwise from the right
+    #[serde(default)]
+    pub direction: f32,
+    /// Points with lanterns have a chain of that many lights hanging from them
+    #[serde(default)]
+    pub lanterns: u32,
+    /// What links the lanterns of a chain to each other
+    #[serde(default)]
+    pub link: LinkKind,
+    /// Between the lanterns, and between the point and the first lantern of ropes and springs, tiles
+    #[serde(default = "default_link_length")]
+    pub link_length: f32,
+    /// Of spring links, acceleration per tile of stretch
+    #[serde(default = "default_link_stiffness")]
+    pub stiffness: f32,
+    /// Of spring links, acceleration per tile per second of the speed of stretching
+    #[serde(default = "default_link_damping")]
+    pub damping: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum FieldKind {
+    Wind,
+    Attractor,
+    Repeller,
+    ZeroGravity,
+    Drag,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum LinkKind {
+    /// Keeps the lanterns at the same distance, the first lantern swings around the point
+    Rod,
+    /// Lets the lanterns come closer
+    #[default]
+    Rope,
+    /// Pulls the lanterns to the distance, they bounce around it
+    Spring,
+}
+
+impl Default for ObjectProperties {
+    fn default() -> Self {
+        Self {
+            speed: 0.0,
+            ping_pong: false,
+            platform_width: default_platform_width(),
+            platform_height: default_platform_height(),
+            field: None,
+            strength: 0.0,
+            direction: 0.0,
+            lanterns: 0,
+            link: LinkKind::default(),
+            link_length: default_link_length(),
+            stiffness: default_link_stiffness(),
+            damping: default_link_damping(),
+        }
+    }
+}
+
+fn default_platform_width() -> f32 {
+    2.0
+}
+
+fn default_platform_height() -> f32 {
+    0.5
+}
+
+fn default_link_length() -> f32 {
+    0.75
+}
+
+fn default_link_stiffness() -> f32 {
+    40.0
+}
+
+fn default_link_damping() -> f32 {
+    1.0
+}
@@ -1,5 +1,5 @@
-use glam::{Mat4, Vec2, Vec3};
-use winit::dpi::{LogicalSize, PhysicalSize};
+use glam::{Mat4, Vec2, Vec3, vec2};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
 
 pub struct Camera {
     map_size: LogicalSize<u32>,
@@ -44,4 +44,19 @@ impl Camera {
             Self::DISTANCE * 2.0,
         )
     }
+
+    /// The point of the map that is shown at the position in the window.
+    /// It can be outside of the map, where the window shows more than the map.
+    pub fn window_to_world(
+        &self,
+        position: PhysicalPosition<f64>,
+        win_size: PhysicalSize<u32>,
+    ) -> Vec2 {
+        let ndc = vec2(
+            2.0 * position.x as f32 / win_size.width as f32 - 1.0,
+            1.0 - 2.0 * position.y as f32 / win_size.height as f32,
+        );
+        let inverse = (self.matrix_proj(win_size) * self.matrix_view()).inverse();
+        inverse.project_point3(ndc.extend(0.0)).truncate()
+    }
 }
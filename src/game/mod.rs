@@ -1,50 +1,117 @@
 use std::{
-    f32::consts::{FRAC_PI_8, PI},
-    time::Instant,
+    f32::consts::{FRAC_PI_2, FRAC_PI_8, PI, TAU},
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
-use glam::{Vec2, Vec3, Vec4, vec2};
+use glam::{Vec2, Vec3, Vec4, vec2, vec4};
+use log::{debug, info};
 use palette::{FromColor, LinSrgb, OklabHue, Oklch};
 
 pub mod camera;
 
 use crate::{
-    assets::{Assets, LightSource, Map, Shape},
+    assets::{Assets, FieldKind, LightSource, LinkKind, Map, MapField, MapLiquid, Shape},
     game::camera::Camera,
     geo::{Point, ToricGeometry},
     phys::{
-        Physics,
-        object::{PhysObject, SceneObject},
+        Physics, PhysicsSnapshot,
+        events::{ContactPair, ContactPhase},
+        field::{FieldEffect, ForceField},
+        handle::Handle,
+        joint::{Joint, JointEnd},
+        kinematic::PathFollower,
+        material::Material,
+        object::{PhysObject, PhysObjectShape, SceneObject},
+        particle::{ParticleEmitter, ParticleStyle, Particles},
     },
     view::{DeferredLight, QuadEmitter},
 };
 
 const LIGHT_COUNT: usize = 12;
 
+/// Sparks that fly off a wall per unit of the impulse of a light hitting it
+const SPARKS_PER_IMPULSE: f32 = 4.0;
+const MAX_SPARKS_PER_HIT: usize = 24;
+/// Embers that a moving light leaves behind per tile
+const EMBERS_PER_TILE: f32 = 6.0;
+
+/// How far from the mouse lights can be grabbed, tiles
+const GRAB_RADIUS: f32 = 0.5;
+/// How fast a grabbed light follows the mouse, per second of the distance to it
+const GRAB_SPEED: f32 = 10.0;
+/// Explosions push the lights that they reach away with this speed, tiles per second at the center
+const EXPLOSION_SPEED: f32 = 10.0;
+/// Tiles
+const EXPLOSION_RADIUS: f32 = 5.0;
+/// Other objects shield the lights behind them from the rays of the explosion
+const EXPLOSION_RAYS: usize = 64;
+
+/// How often the energy of the physics is logged, to see whether it stays steady
+const DIAGNOSTICS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Of the lanterns that hang on chains from the map
+const LANTERN_COLOR: Vec3 = Vec3::new(1.0, 0.8, 0.5);
+/// Chains are drawn as dots this far apart, tiles
+const CHAIN_DOT_SPACING: f32 = 0.15;
+const CHAIN_DOT_SIZE: f32 = 0.08;
+const CHAIN_COLOR: Vec4 = Vec4::new(0.4, 0.35, 0.3, 1.0);
+
+const LIGHT_MATERIAL: Material = Material {
+    restitution: 1.0,
+    static_friction: 0.4,
+    dynamic_friction: 0.3,
+    stickiness: 0.0,
+};
+
+/// Material of the moving platforms, the walls of the map get the materials of their tiles
+const WALL_MATERIAL: Material = Material {
+    restitution: 0.9,
+    static_friction: 0.4,
+    dynamic_friction: 0.3,
+    stickiness: 0.0,
+};
+
+#[derive(Clone)]
 pub enum GameObject<'assets> {
     Light {
         color: Vec4,
         light_id: u32,
         light_asset: &'assets LightSource,
+        /// Trail of the light
+        embers: ParticleEmitter,
     },
+    /// Moves along a path from the map, only collides and is not drawn
+    Platform,
 }
 
 pub struct Game<'assets> {
     pub map: &'assets Map,
     pub camera: Camera,
 
+    geometry: ToricGeometry,
     physics: Physics<GameObject<'assets>>,
     physics_scene: Vec<SceneObject>,
+    /// Light that follows the mouse, and where the mouse is
+    grabbed: Option<(Handle, Point)>,
+    /// Of the last quick save
+    saved: Option<PhysicsSnapshot<GameObject<'assets>>>,
+
+    particles: Particles,
+    /// Style of the particles that fly off the walls that lights hit
+    sparks: usize,
+    /// Whose texture the particles are drawn with
+    particle_light: (u32, &'assets LightSource),
 
     start: Instant,
     last_advance: Instant,
+    /// When the energy of the physics was last logged
+    last_diagnostics: Instant,
 }
 
 impl<'assets> Game<'assets> {
     pub fn new(assets: &'assets Assets) -> Result<Self> {
-        let map_name = "debug-01";
-        let (_, map) = assets.find_map(map_name)?;
+        let (_, map) = assets.find_map(&assets.map)?;
 
         let camera = Camera::new(vec2(0.0, 0.0), map.size_tiles());
 
@@ -52,18 +119,66 @@ impl<'assets> Game<'assets> {
         let (light_id, light_asset) = assets.find_light(light_name)?;
 
         let map_size = map.size_tiles();
-        let mut physics = Physics::new(
-            assets.max_timestep,
-            ToricGeometry {
-                x: map_size.width as f32,
-                y: map_size.height as f32,
-            },
-        );
+        let geometry = ToricGeometry {
+            x: map_size.width as f32,
+            y: map_size.height as f32,
+        };
+        let mut physics = Physics::new(assets.timestep, geometry.clone());
+        physics.set_gravity(vec2(0.0, -map.properties.gravity));
+        let gravity = physics.gravity();
+
+        let mut particles =
+            Particles::new(assets.timestep, geometry.clone(), &map.occlusion_segments);
+        particles.set_gravity(gravity);
+        let sparks = particles.add_style(ParticleStyle {
+            lifetime: (0.2, 0.5),
+            speed: (2.0, 5.0),
+            spread: FRAC_PI_2,
+            size: 0.1,
+            colors: vec![
+                (0.0, vec4(1.0, 1.0, 0.8, 1.0)),
+                (0.3, vec4(1.0, 0.7, 0.2, 1.0)),
+                (1.0, vec4(0.8, 0.2, 0.0, 0.0)),
+            ],
+            gravity_scale: 1.0,
+            drag: 1.0,
+            restitution: 0.4,
+            friction: 0.3,
+        });
+        let embers = particles.add_style(ParticleStyle {
+            lifetime: (0.5, 1.2),
+            speed: (0.1, 0.5),
+            spread: PI,
+            size: 0.15,
+            colors: vec![
+                (0.0, vec4(1.0, 0.6, 0.1, 0.8)),
+                (1.0, vec4(0.5, 0.1, 0.0, 0.0)),
+            ],
+            // Embers are light and float up a bit
+            gravity_scale: -0.1,
+            drag: 2.0,
+            restitution: 0.2,
+            friction: 0.5,
+        });
 
         let light_brightness = 2.5;
         let light_angle_start = FRAC_PI_8;
         let light_angle_end = PI - FRAC_PI_8;
 
+        let Shape::Disc { radius } = light_asset.shape;
+        let light = |origin: Point, color: Vec3| {
+            let meta = GameObject::Light {
+                color: color.extend(light_brightness),
+                light_id: light_id as u32,
+                light_asset,
+                embers: ParticleEmitter::new(embers, 0.0),
+            };
+
+            PhysObject::new_disc(origin, radius, 1.0)
+                .with_material(LIGHT_MATERIAL)
+                .with_meta(meta)
+        };
+
         for light_i in 0..LIGHT_COUNT {
             let angle = light_angle_start
                 + (light_angle_end - light_angle_start)
@@ -72,8 +187,8 @@ impl<'assets> Game<'assets> {
             let v0 = vec2(angle_cos, angle_sin) * 5.0;
 
             let time = 1.0;
-            let origin = Point::ZERO + v0 * time + vec2(0.0, -3.0) * time * time;
-            let velocity = v0 + 2.0 * vec2(0.0, -3.0) * time;
+            let origin = Point::ZERO + v0 * time + gravity * time * time;
+            let velocity = v0 + 2.0 * gravity * time;
 
             let ok_hue = OklabHue::new(360.0 / (LIGHT_COUNT - 1) as f32 * light_i as f32);
             let ok_color = Oklch {
@@ -83,86 +198,416 @@ impl<'assets> Game<'assets> {
             };
             let lsrgb_color = LinSrgb::from_color(ok_color);
             let color: [f32; 3] = lsrgb_color.into();
-            let color: Vec3 = color.into();
 
-            let meta = GameObject::Light {
-                color: color.extend(light_brightness),
-                light_id: light_id as u32,
-                light_asset,
+            physics.add(light(origin, color.into()).with_velocity(velocity));
+        }
+
+        for chain in map.lantern_chains() {
+            let top = match chain.link {
+                LinkKind::Rod => chain.anchor - vec2(0.0, radius),
+                _ => chain.anchor - vec2(0.0, chain.link_length),
             };
+            let mut above = JointEnd::World(chain.anchor);
+            for i in 0..chain.lanterns {
+                let origin = top - vec2(0.0, chain.link_length * i as f32);
+                let handle = physics.add(light(origin, LANTERN_COLOR));
 
-            let Shape::Disc { radius } = light_asset.shape;
+                let end = JointEnd::Object {
+                    handle,
+                    anchor: Vec2::ZERO,
+                };
+                let joint = match (chain.link, above) {
+                    // The rods start with a lantern that touches the anchor, and swings around it
+                    (LinkKind::Rod, JointEnd::World(anchor)) => {
+                        Joint::pin(handle, vec2(0.0, radius), anchor)
+                    }
+                    (LinkKind::Rod, _) => Joint::distance(above, end, chain.link_length),
+                    (LinkKind::Rope, _) => Joint::rope(above, end, chain.link_length),
+                    (LinkKind::Spring, _) => Joint::spring(
+                        above,
+                        end,
+                        chain.link_length,
+                        chain.stiffness,
+                        chain.damping,
+                    ),
+                };
+                physics.add_joint(joint);
+                above = end;
+            }
+        }
 
-            let obj = PhysObject::new_disc(origin, radius, 1.0)
-                .with_meta(meta)
-                .with_velocity(velocity);
+        for path in map.paths() {
+            let platform = PhysObject::new_box(
+                Point::ZERO,
+                path.platform_width / 2.0,
+                path.platform_height / 2.0,
+                1.0,
+            )
+            .with_material(WALL_MATERIAL)
+            .with_meta(GameObject::Platform);
 
-            physics.add(obj);
+            let follower = PathFollower::new(path.points, path.closed, path.speed, path.ping_pong);
+            physics.add_kinematic(platform, follower);
+        }
+
+        for field in map.fields() {
+            physics.add_field(force_field(&field));
+        }
+
+        for liquid in map.liquids()? {
+            physics.add_field(buoyancy(&liquid));
         }
 
         let physics_scene = map
             .occlusion_segments
             .iter()
-            .map(|s| {
+            .zip(&map.occlusion_materials)
+            .map(|(s, material)| {
                 let (a, b) = s.ab();
-                SceneObject::new_segment(a, b)
+                SceneObject::new_segment_classified(a, b)
+                    .with_material(material.into())
+                    .with_filter(material.filter)
+                    .with_one_way(material.one_way)
             })
             .collect();
 
         Ok(Self {
             map,
             camera,
+            geometry,
             physics,
             physics_scene,
+            grabbed: None,
+            saved: None,
+            particles,
+            sparks,
+            particle_light: (light_id as u32, light_asset),
             start: Instant::now(),
             last_advance: Instant::now(),
+            last_diagnostics: Instant::now(),
         })
     }
 
     pub fn advance(&mut self) {
-        self.physics
-            .advance_by(&self.physics_scene, self.last_advance.elapsed());
+        let elapsed = self.last_advance.elapsed();
+        self.follow_mouse();
+        let events = self.physics.advance_by(&self.physics_scene, elapsed);
+        self.particles.advance_by(elapsed);
         self.last_advance = Instant::now();
+
+        if self.last_diagnostics.elapsed() >= DIAGNOSTICS_INTERVAL {
+            let diagnostics = self.physics.diagnostics();
+            debug!(
+                "Energy {:.3} (kinetic {:.3}, potential {:.3}), momentum {:.3}",
+                diagnostics.total_energy(),
+                diagnostics.kinetic_energy,
+                diagnostics.potential_energy,
+                diagnostics.momentum.length(),
+            );
+            self.last_diagnostics = Instant::now();
+        }
+
+        // The scene is made of the occlusion segments, in the same order
+        for event in events {
+            let ContactPair::Scene(handle, scene_index) = event.pair else {
+                continue;
+            };
+            if event.phase != ContactPhase::Begin {
+                continue;
+            }
+
+            if !self.is_light(handle) {
+                continue;
+            }
+
+            // The harder the light hits the wall, the more sparks fly off it
+            let sparks = (event.impulse * SPARKS_PER_IMPULSE) as usize;
+            self.particles.burst(
+                self.sparks,
+                sparks.min(MAX_SPARKS_PER_HIT),
+                event.point,
+                event.normal,
+                Vec2::ZERO,
+            );
+
+            if self.map.occlusion_materials[scene_index].kills_light {
+                self.physics.remove(handle);
+                self.physics
+                    .retain_joints(|joint| !joint.attached_to(handle));
+            }
+        }
+
+        let lights: Vec<_> = self
+            .physics
+            .iter()
+            .filter(|(_, obj)| matches!(obj.meta, GameObject::Light { .. }))
+            .map(|(handle, _)| handle)
+            .collect();
+        for handle in lights {
+            let Some(obj) = self.physics.get_mut(handle) else {
+                continue;
+            };
+            let (center, velocity) = (obj.center, obj.velocity_linear);
+            let GameObject::Light { embers, .. } = &mut obj.meta else {
+                continue;
+            };
+
+            embers.rate = EMBERS_PER_TILE * velocity.length();
+            self.particles.emit(
+                embers,
+                elapsed.as_secs_f32(),
+                center,
+                -velocity,
+                velocity * 0.2,
+            );
+        }
     }
 
-    pub fn light_deferred_data(&self) -> impl ExactSizeIterator<Item = DeferredLight> {
-        self.physics.iter().map(|obj| {
-            let GameObject::Light { color, .. } = obj.meta;
-            let pos = obj.center;
-            let visibility = self.map.visibility_for(pos).segments;
-            DeferredLight {
-                position: (pos.x, pos.y, 1.0, 1.0).into(),
-                color,
-                visibility,
+    /// Start dragging the light under the mouse, if there is one.
+    pub fn grab(&mut self, point: Point) {
+        let light = self
+            .physics
+            .overlapping_disc(point, GRAB_RADIUS)
+            .into_iter()
+            .find(|handle| self.is_light(*handle));
+        self.grabbed = light.map(|handle| (handle, point));
+    }
+
+    pub fn drag_to(&mut self, point: Point) {
+        if let Some((_, target)) = &mut self.grabbed {
+            *target = point;
+        }
+    }
+
+    /// Let go of the grabbed light, it keeps the velocity that it was dragged with.
+    pub fn release(&mut self) {
+        self.grabbed = None;
+    }
+
+    /// Push the lights around the point away from it.
+    pub fn explode(&mut self, point: Point) {
+        let mut pushed: Vec<(Handle, Vec2)> = Vec::new();
+        for i in 0..EXPLOSION_RAYS {
+            let direction = Vec2::from_angle(TAU * i as f32 / EXPLOSION_RAYS as f32);
+            let Some(hit) = self.physics.ray_cast(point, direction, EXPLOSION_RADIUS) else {
+                continue;
+            };
+            if !self.is_light(hit.handle) || pushed.iter().any(|(h, _)| *h == hit.handle) {
+                continue;
             }
-        })
+
+            let speed = EXPLOSION_SPEED * (1.0 - hit.distance / EXPLOSION_RADIUS);
+            pushed.push((hit.handle, direction * speed));
+        }
+
+        for (handle, velocity) in pushed {
+            if let Some(obj) = self.physics.get_mut(handle) {
+                obj.velocity_linear += velocity;
+                obj.wake();
+            }
+        }
+
+        self.particles
+            .burst(self.sparks, MAX_SPARKS_PER_HIT, point, Vec2::Y, Vec2::ZERO);
+    }
+
+    /// Steer the grabbed light towards the mouse, without ramming it into the objects in the way.
+    fn follow_mouse(&mut self) {
+        let Some((handle, target)) = self.grabbed else {
+            return;
+        };
+        let Some(obj) = self.physics.get(handle) else {
+            // The light went out
+            self.grabbed = None;
+            return;
+        };
+
+        let offset = self.geometry.dir(obj.center, target);
+        let distance = offset.length();
+        let free = self
+            .physics
+            .shape_cast(&obj.shape, obj.center, obj.orientation, offset, distance)
+            .map_or(distance, |hit| hit.distance);
+
+        let velocity = offset.normalize_or_zero() * free * GRAB_SPEED;
+        if let Some(obj) = self.physics.get_mut(handle) {
+            obj.velocity_linear = velocity;
+            obj.wake();
+        }
+    }
+
+    /// Remember the state of the physics, to go back to it with [`Game::quick_load`].
+    pub fn quick_save(&mut self) {
+        info!(
+            "Quick save of {} physics objects",
+            self.physics.iter().len()
+        );
+        self.saved = Some(self.physics.snapshot());
+    }
+
+    pub fn quick_load(&mut self) {
+        let Some(saved) = &self.saved else {
+            info!("Nothing to load, quick save first");
+            return;
+        };
+
+        info!("Quick load of {} physics objects", saved.objects.len());
+        self.physics.restore(saved.clone());
+        // The grabbed light may not exist in the saved state
+        self.grabbed = None;
+    }
+
+    fn is_light(&self, handle: Handle) -> bool {
+        self.physics
+            .get(handle)
+            .is_some_and(|obj| matches!(obj.meta, GameObject::Light { .. }))
+    }
+
+    pub fn light_deferred_data(&self) -> impl ExactSizeIterator<Item = DeferredLight> {
+        let lights: Vec<_> = self
+            .physics
+            .iter()
+            .filter_map(|(_, obj)| {
+                let GameObject::Light { color, .. } = obj.meta else {
+                    return None;
+                };
+                let (pos, _) = self.physics.interpolated(obj);
+                let visibility = self.map.visibility_for(pos).segments;
+                Some(DeferredLight {
+                    position: (pos.x, pos.y, 1.0, 1.0).into(),
+                    color,
+                    visibility,
+                })
+            })
+            .collect();
+        lights.into_iter()
     }
 
     pub fn light_quad_data(&self) -> impl ExactSizeIterator<Item = QuadEmitter> {
         let time_ms = (self.start.elapsed().as_millis() % usize::MAX as u128) as usize;
-        self.physics.iter().map(move |obj| {
-            let pos = obj.center;
-            let GameObject::Light {
-                color,
-                light_id,
-                light_asset,
-            } = obj.meta;
-
-            let rot = Vec2::X.angle_to(obj.velocity_linear);
-
-            let frame = (time_ms / light_asset.ms_per_frame) % light_asset.frames;
-            let frame_w = light_asset.frame_size[0] as f32;
-            let frame_h = light_asset.frame_size[1] as f32;
-
-            QuadEmitter {
-                pos: (pos.x, pos.y, 1.0).into(),
-                dim: vec2(1.0, 1.0),
-                rot,
-                tex_num: light_id,
-                tex_pos: vec2(frame_w * frame as f32, 0.0),
-                tex_dim: vec2(frame_w, frame_h),
-                tint: color.truncate(),
-            }
-        })
+        let quads: Vec<_> = self
+            .physics
+            .iter()
+            .filter_map(|(_, obj)| {
+                let GameObject::Light {
+                    color,
+                    light_id,
+                    light_asset,
+                    ..
+                } = obj.meta
+                else {
+                    return None;
+                };
+                let (pos, rot) = self.physics.interpolated(obj);
+
+                let frame = (time_ms / light_asset.ms_per_frame) % light_asset.frames;
+                let frame_w = light_asset.frame_size[0] as f32;
+                let frame_h = light_asset.frame_size[1] as f32;
+
+                Some(QuadEmitter {
+                    pos: (pos.x, pos.y, 1.0).into(),
+                    dim: vec2(1.0, 1.0),
+                    rot,
+                    tex_num: light_id,
+                    tex_pos: vec2(frame_w * frame as f32, 0.0),
+                    tex_dim: vec2(frame_w, frame_h),
+                    tint: color.truncate().extend(1.0),
+                })
+            })
+            .collect();
+        quads.into_iter()
+    }
+
+    pub fn particle_quad_data(&self) -> impl ExactSizeIterator<Item = QuadEmitter> {
+        let (light_id, light_asset) = self.particle_light;
+        let frame_w = light_asset.frame_size[0] as f32;
+        let frame_h = light_asset.frame_size[1] as f32;
+
+        let quad = |pos: Point, size: f32, tint: Vec4| QuadEmitter {
+            pos: (pos.x, pos.y, 1.0).into(),
+            dim: Vec2::splat(size),
+            rot: 0.0,
+            tex_num: light_id,
+            tex_pos: Vec2::ZERO,
+            tex_dim: vec2(frame_w, frame_h),
+            tint,
+        };
+
+        // Links of the lantern chains, as dots along them
+        let links = self.physics.joint_endpoints().flat_map(|(start, end)| {
+            let dots = ((end - start).length() / CHAIN_DOT_SPACING).ceil().max(1.0) as usize;
+            (0..=dots).map(move |i| {
+                let pos = start + (end - start) * (i as f32 / dots as f32);
+                quad(pos, CHAIN_DOT_SIZE, CHAIN_COLOR)
+            })
+        });
+
+        let quads: Vec<_> = links
+            .chain(self.particles.iter().map(|particle| {
+                let style = self.particles.style(particle.style);
+                let pos = self.particles.interpolated(particle);
+
+                // Every particle flickers through the frames from its birth
+                let age_ms = (particle.age * 1000.0) as usize;
+                let frame = (age_ms / light_asset.ms_per_frame) % light_asset.frames;
+
+                QuadEmitter {
+                    tex_pos: vec2(frame_w * frame as f32, 0.0),
+                    ..quad(pos, style.size, style.color(particle.life()))
+                }
+            }))
+            .collect();
+        quads.into_iter()
     }
 }
+
+fn force_field(field: &MapField) -> ForceField {
+    let effect = match field.kind {
+        FieldKind::Wind => FieldEffect::Wind(Vec2::from_angle(field.direction) * field.strength),
+        FieldKind::Attractor => FieldEffect::Radial(field.strength),
+        FieldKind::Repeller => FieldEffect::Radial(-field.strength),
+        FieldKind::ZeroGravity => FieldEffect::ZeroGravity,
+        FieldKind::Drag => FieldEffect::Drag(field.strength),
+    };
+
+    let (w, h) = (field.half_width, field.half_height);
+    let (shape, orientation) = match field.ellipse {
+        false => (
+            PhysObjectShape::Box {
+                half_width: w,
+                half_height: h,
+            },
+            0.0,
+        ),
+        // Ellipses are close enough to capsules along their longer axis
+        true if w >= h => (
+            PhysObjectShape::Capsule {
+                half_length: w - h,
+                radius: h,
+            },
+            0.0,
+        ),
+        true => (
+            PhysObjectShape::Capsule {
+                half_length: h - w,
+                radius: w,
+            },
+            FRAC_PI_2,
+        ),
+    };
+
+    ForceField::new(shape, field.center, effect).with_orientation(field.orientation + orientation)
+}
+
+fn buoyancy(liquid: &MapLiquid) -> ForceField {
+    let shape = PhysObjectShape::Box {
+        half_width: liquid.half_width,
+        half_height: 0.5,
+    };
+    let effect = FieldEffect::Buoyancy {
+        density: liquid.density,
+        drag: liquid.drag,
+    };
+
+    ForceField::new(shape, liquid.center, effect)
+}
@@ -22,6 +22,7 @@ impl Display for SegmentSide {
     }
 }
 
+#[derive(Clone)]
 pub struct Segment {
     a: Point,
     b: Point,
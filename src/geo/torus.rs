@@ -1,12 +1,22 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
 use crate::geo::Point;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ToricGeometry {
     pub x: f32,
     pub y: f32,
 }
 
 impl ToricGeometry {
+    /// The shortest vector `dir` such that `from + dir` wraps to `to`.
+    pub fn dir(&self, from: Point, to: Point) -> Vec2 {
+        let d = from.dir(to);
+        let shortest = |d: f32, size: f32| d - (d / size).round() * size;
+        Vec2::new(shortest(d.x, self.x), shortest(d.y, self.y))
+    }
+
     pub fn wrap(&self, point: &mut Point) {
         if point.x < -self.x / 2.0 {
             point.x += self.x;
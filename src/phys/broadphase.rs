@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+use crate::{
+    geo::ToricGeometry,
+    phys::object::{PhysObject, SceneObject},
+};
+
+/// Uniform grid that covers the torus and finds pairs of objects that might touch,
+/// so that the narrowphase doesn't have to check every pair.
+///
+/// Cells wrap around the edges of the torus, so objects near opposite edges end up in the same cells.
+pub struct SpatialHash {
+    geometry: ToricGeometry,
+    /// Number of cells along each axis
+    cells_x: i32,
+    cells_y: i32,
+    /// Indices of the objects whose bounding boxes overlap the cell
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    pairs: Vec<(usize, usize)>,
+    scene_pairs: Vec<(usize, usize)>,
+    /// Cells covered by one object or scene object
+    covered: Vec<(i32, i32)>,
+}
+
+impl SpatialHash {
+    /// Smaller cells don't make the search any faster
+    const MIN_CELL_SIZE: f32 = 0.1;
+
+    pub fn new(geometry: ToricGeometry) -> Self {
+        Self {
+            geometry,
+            cells_x: 1,
+            cells_y: 1,
+            cells: HashMap::new(),
+            pairs: Vec::new(),
+            scene_pairs: Vec::new(),
+            covered: Vec::new(),
+        }
+    }
+
+    /// Pick the number of cells so that the cells are at least `size` wide and tall
+    /// and tile the torus exactly.
+    fn resize(&mut self, size: f32) {
+        let cells_x = ((self.geometry.x / size).floor() as i32).max(1);
+        let cells_y = ((self.geometry.y / size).floor() as i32).max(1);
+
+        if (cells_x, cells_y) != (self.cells_x, self.cells_y) {
+            self.cells_x = cells_x;
+            self.cells_y = cells_y;
+            self.cells.clear();
+        }
+    }
+
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        let cell_w = self.geometry.x / self.cells_x as f32;
+        let cell_h = self.geometry.y / self.cells_y as f32;
+
+        let cx = ((x + self.geometry.x / 2.0) / cell_w).floor() as i32;
+        let cy = ((y + self.geometry.y / 2.0) / cell_h).floor() as i32;
+
+        (cx, cy)
+    }
+
+    /// Find the cells that the bounding box overlaps, wrapped around the torus.
+    fn cover(&mut self, min: Vec2, max: Vec2) {
+        let (x0, y0) = self.cell(min.x, min.y);
+        let (x1, y1) = self.cell(max.x, max.y);
+
+        // A box can't cover a cell twice, even when it's larger than the torus
+        self.covered.clear();
+        for cy in y0..=y1.min(y0 + self.cells_y - 1) {
+            for cx in x0..=x1.min(x0 + self.cells_x - 1) {
+                self.covered
+                    .push((cx.rem_euclid(self.cells_x), cy.rem_euclid(self.cells_y)));
+            }
+        }
+    }
+
+    /// Sorted pairs of indices `(i, j)`, `i < j`, of the objects whose bounding boxes share a cell.
+    /// Every pair of objects that touch is included, but not every included pair touches.
+    pub fn pairs<M>(&mut self, objects: &[PhysObject<M>]) -> &[(usize, usize)] {
+        let largest = objects
+            .iter()
+            .map(|obj| obj.shape.bounding_radius())
+            .fold(0.0, f32::max);
+        // The smallest cells that can't be skipped over by an object
+        self.resize((largest * 2.0).max(Self::MIN_CELL_SIZE));
+
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+
+        for (i, obj) in objects.iter().enumerate() {
+            let r = Vec2::splat(obj.shape.bounding_radius());
+            self.cover(obj.center.vec() - r, obj.center.vec() + r);
+
+            for &cell in &self.covered {
+                self.cells.entry(cell).or_default().push(i);
+            }
+        }
+
+        self.pairs.clear();
+        for cell in self.cells.values() {
+            for (n, &i) in cell.iter().enumerate() {
+                for &j in &cell[n + 1..] {
+                    self.pairs.push((i, j));
+                }
+            }
+        }
+
+        // Objects that share several cells are paired several times
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+
+        &self.pairs
+    }
+
+    /// Sorted pairs of indices `(i, s)` of the objects and the scene objects whose bounding boxes
+    /// share a cell, for the objects of the last call to [`SpatialHash::pairs`].
+    /// Like there, every object and scene object that touch are included.
+    pub fn scene_pairs(&mut self, scene: &[SceneObject]) -> &[(usize, usize)] {
+        self.scene_pairs.clear();
+
+        for (s, with) in scene.iter().enumerate() {
+            let (a, b) = with.endpoints();
+            let (a, b) = (a.vec(), b.vec());
+            self.cover(a.min(b), a.max(b));
+
+            for cell in &self.covered {
+                let Some(objects) = self.cells.get(cell) else {
+                    continue;
+                };
+                self.scene_pairs.extend(objects.iter().map(|&i| (i, s)));
+            }
+        }
+
+        self.scene_pairs.sort_unstable();
+        self.scene_pairs.dedup();
+
+        &self.scene_pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f32::consts::TAU,
+        time::{Duration, Instant},
+    };
+
+    use log::info;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use crate::{geo::Point, init_logging, phys::Physics};
+
+    use super::*;
+
+    const GEOMETRY: ToricGeometry = ToricGeometry { x: 48.0, y: 32.0 };
+
+    fn random_discs(count: usize, radius: f32) -> Vec<PhysObject<()>> {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        (0..count)
+            .map(|_| {
+                let x = rng.random_range(-GEOMETRY.x / 2.0..GEOMETRY.x / 2.0);
+                let y = rng.random_range(-GEOMETRY.y / 2.0..GEOMETRY.y / 2.0);
+                PhysObject::new_disc((x, y).into(), radius, 1.0)
+            })
+            .collect()
+    }
+
+    /// Pairs of discs that overlap, possibly across the edges of the torus.
+    fn brute_force_pairs(discs: &[PhysObject<()>], radius: f32) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+
+        for i in 0..discs.len() {
+            for j in i + 1..discs.len() {
+                let d = discs[i].center.dir(discs[j].center);
+                let dx = d.x.abs().min(GEOMETRY.x - d.x.abs());
+                let dy = d.y.abs().min(GEOMETRY.y - d.y.abs());
+                if dx * dx + dy * dy <= (2.0 * radius) * (2.0 * radius) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+
+        pairs
+    }
+
+    #[test]
+    fn broadphase_finds_all_pairs() {
+        init_logging();
+
+        let radius = 0.4;
+        let mut discs = random_discs(1000, radius);
+        // Touching across the corner of the torus
+        discs.push(PhysObject::new_disc(Point::new(23.9, 15.9), radius, 1.0));
+        discs.push(PhysObject::new_disc(Point::new(-23.9, -15.9), radius, 1.0));
+
+        let expected = brute_force_pairs(&discs, radius);
+        assert!(expected.contains(&(discs.len() - 2, discs.len() - 1)));
+
+        let mut broadphase = SpatialHash::new(GEOMETRY);
+        let pairs = broadphase.pairs(&discs);
+
+        for pair in &expected {
+            assert!(pairs.binary_search(pair).is_ok(), "{pair:?} is missing");
+        }
+
+        // Far from checking every pair
+        assert!(pairs.len() < discs.len() * 10, "{} pairs", pairs.len());
+    }
+
+    #[test]
+    fn broadphase_finds_all_scene_pairs() {
+        init_logging();
+
+        let radius = 0.4;
+        let discs = random_discs(1000, radius);
+
+        // Short walls all over the map, and the long edges of the map just outside of the torus
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut scene: Vec<_> = (0..200)
+            .map(|_| {
+                let x = rng.random_range(-GEOMETRY.x / 2.0..GEOMETRY.x / 2.0);
+                let y = rng.random_range(-GEOMETRY.y / 2.0..GEOMETRY.y / 2.0);
+                let d = Vec2::from_angle(rng.random_range(0.0..TAU)) * 2.0;
+                SceneObject::new_segment_classified((x, y).into(), (x + d.x, y + d.y).into())
+            })
+            .collect();
+        let (w2, h2) = (GEOMETRY.x / 2.0 + 0.2, GEOMETRY.y / 2.0 + 0.2);
+        scene.push(SceneObject::new_segment_h((-w2, h2).into(), 2.0 * w2));
+        scene.push(SceneObject::new_segment_v((w2, h2).into(), -2.0 * h2));
+
+        let mut broadphase = SpatialHash::new(GEOMETRY);
+        broadphase.pairs(&discs);
+        let pairs = broadphase.scene_pairs(&scene);
+
+        let mut expected = 0;
+        for (i, disc) in discs.iter().enumerate() {
+            for (s, with) in scene.iter().enumerate() {
+                // Distance from the center of the disc to the closest point of the segment
+                let (a, b) = with.endpoints();
+                let (ab, ac) = (a.dir(b), a.dir(disc.center));
+                let t = (ac.dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+                if (ac - ab * t).length() <= radius {
+                    expected += 1;
+                    assert!(
+                        pairs.binary_search(&(i, s)).is_ok(),
+                        "{:?} is missing",
+                        (i, s)
+                    );
+                }
+            }
+        }
+        assert!(expected > 0);
+
+        // Far from checking every pair
+        assert!(pairs.len() < discs.len() * 10, "{} pairs", pairs.len());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn broadphase_benchmark() {
+        init_logging();
+
+        let radius = 0.05;
+        let discs = random_discs(10_000, radius);
+        let mut broadphase = SpatialHash::new(GEOMETRY);
+
+        let start = Instant::now();
+        let expected = brute_force_pairs(&discs, radius);
+        info!("Brute force: {:?}", start.elapsed());
+
+        let runs = 100;
+        let start = Instant::now();
+        for _ in 0..runs {
+            broadphase.pairs(&discs);
+        }
+        info!(
+            "Spatial hash: {:?} per run, {} candidate pairs for {} touching ones",
+            start.elapsed() / runs,
+            broadphase.pairs(&discs).len(),
+            expected.len(),
+        );
+
+        let mut physics = Physics::new(0.016, GEOMETRY);
+        for disc in discs {
+            physics.add(disc);
+        }
+
+        let start = Instant::now();
+        for _ in 0..runs {
+            physics.advance_by(&[], Duration::from_millis(16));
+        }
+        info!("Physics step: {:?}", start.elapsed() / runs);
+    }
+}
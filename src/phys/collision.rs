@@ -2,29 +2,270 @@
 
 use glam::{Vec2, vec2};
 use log::trace;
-
-use crate::phys::object::{PhysObject, PhysObjectShape, SceneObject, SceneObjectShape};
-
-#[derive(Clone, Debug, Default)]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geo::Point,
+    phys::{
+        material::Material,
+        narrowphase::{Contact, ContactManifold, ContactPoint, RoundedHull, contact, manifold},
+        object::{PhysObject, PhysObjectShape, SceneObject, SceneObjectShape},
+    },
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct VelocityAccumulator {
     pub velocity_linear: Vec2,
     pub velocity_angular: f32,
 }
 
-pub trait CollideWith<Obj> {
-    fn collide(&mut self, with: Obj);
+/// One of the two sides of a contact, as far as the impulse is concerned.
+/// Immovable objects have zero inverse mass and inertia.
+#[derive(Clone, Copy, Debug)]
+pub struct ContactSide {
+    pub inverse_mass: f32,
+    pub inverse_inertia: f32,
+    /// From the center of mass to the contact point
+    pub arm: Vec2,
+    /// Velocity of the contact point
+    pub velocity: Vec2,
 }
 
-impl<M> CollideWith<&SceneObject> for PhysObject<M> {
-    fn collide(&mut self, with: &SceneObject) {
+impl ContactSide {
+    pub const IMMOVABLE: Self = Self {
+        inverse_mass: 0.0,
+        inverse_inertia: 0.0,
+        arm: Vec2::ZERO,
+        velocity: Vec2::ZERO,
+    };
+
+    /// Kinematic objects are immovable, but their contact points still move.
+    pub fn new<M>(obj: &PhysObject<M>, point: Point) -> Self {
+        let arm = obj.center.dir(point);
+        let (inverse_mass, inverse_inertia) = if obj.kinematic {
+            (0.0, 0.0)
+        } else {
+            (1.0 / obj.mass, 1.0 / obj.inertia())
+        };
+
+        Self {
+            inverse_mass,
+            inverse_inertia,
+            arm,
+            velocity: obj.velocity_at(arm),
+        }
+    }
+
+    /// How hard it is to change the velocity of the contact point along the direction.
+    pub fn inverse_effective_mass(&self, direction: Vec2) -> f32 {
+        self.inverse_mass + self.inverse_inertia * self.arm.perp_dot(direction).powi(2)
+    }
+}
+
+/// Impulse that has to be applied to `b` (and the opposite one to `a`) to resolve their contact.
+/// `normal` is a unit vector that points from `a` to `b`.
+///
+/// The normal part follows the restitution of the material,
+/// and the tangential part follows the Coulomb friction model.
+pub fn contact_impulse(a: &ContactSide, b: &ContactSide, normal: Vec2, material: Material) -> Vec2 {
+    let velocity = b.velocity - a.velocity;
+    let velocity_normal = velocity.dot(normal);
+
+    if velocity_normal >= 0.0 {
+        // Not approaching each other
+        return Vec2::ZERO;
+    }
+
+    let k_normal = a.inverse_effective_mass(normal) + b.inverse_effective_mass(normal);
+    let j_normal = -(1.0 + material.restitution) * velocity_normal / k_normal;
+
+    let tangent = (velocity - velocity_normal * normal).normalize_or_zero();
+    if tangent == Vec2::ZERO {
+        return j_normal * normal;
+    }
+
+    // Impulse that would stop the sliding completely
+    let k_tangent = a.inverse_effective_mass(tangent) + b.inverse_effective_mass(tangent);
+    let j_tangent = -velocity.dot(tangent) / k_tangent;
+
+    let j_tangent = if j_tangent.abs() <= material.static_friction * j_normal {
+        j_tangent
+    } else {
+        -material.dynamic_friction * j_normal
+    };
+
+    j_normal * normal + j_tangent * tangent
+}
+
+/// Outcome of [`CollideWith::collide`] when the objects touch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collision {
+    pub point: Point,
+    /// Unit vector that points from `self` to the other object,
+    /// or from the scene object to `self`
+    pub normal: Vec2,
+    /// Magnitude of the impulse that was applied to resolve the contact
+    pub impulse: f32,
+}
+
+/// First contact of a moving object with a scene object, see [`PhysObject::impact_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct Impact {
+    /// From now
+    pub seconds: f32,
+    pub point: Point,
+    /// Unit vector that points from the scene object to the moving object
+    pub normal: Vec2,
+}
+
+impl<M> PhysObject<M> {
+    /// Where the object would first touch the scene object if it kept moving with its current velocity.
+    /// `None` if that doesn't happen within `seconds`, or if the objects already overlap.
+    ///
+    /// Shapes other than discs are approximated with the largest disc that fits into them,
+    /// so they may end up overlapping the scene object a bit.
+    pub fn impact_with(&self, with: &SceneObject, seconds: f32) -> Option<Impact> {
+        if with.lets_through(self.center, self.velocity_linear) {
+            return None;
+        }
+
+        let motion = self.velocity_linear * seconds;
+        let (sa, sb) = with.endpoints();
+        let radius = self.shape.inner_radius();
+
+        let (t, point, normal) = sweep_disc_segment(self.center, radius, motion, sa, sb)?;
+        Some(Impact {
+            seconds: t * seconds,
+            point,
+            normal,
+        })
+    }
+
+    /// Bounce off a scene object at the point of contact.
+    pub fn bounce_off(&mut self, with: &SceneObject, point: Point, normal: Vec2) -> Collision {
+        let side_self = ContactSide::new(self, point);
+        let material = self.material.combine(with.material);
+        let impulse = contact_impulse(&ContactSide::IMMOVABLE, &side_self, normal, material);
+        self.apply_impulse(impulse, side_self.arm);
+
+        Collision {
+            point,
+            normal,
+            impulse: impulse.length(),
+        }
+    }
+
+    /// Where the objects overlap, without any response, with the other object centered at `with_center`.
+    /// The normal points from `self` to the other object, the impulse is zero.
+    pub fn overlap<M2>(&self, with: &PhysObject<M2>, with_center: Point) -> Option<Collision> {
+        let with = RoundedHull::of_shape(&with.shape, with_center, with.orientation);
+        let contact = contact(&RoundedHull::of_object(self), &with)?;
+        Some(Collision {
+            point: contact.point,
+            normal: contact.normal,
+            impulse: 0.0,
+        })
+    }
+
+    /// Like [`PhysObject::overlap`], the normal points from the scene object to `self`.
+    pub fn overlap_scene(&self, with: &SceneObject) -> Option<Collision> {
+        let contact = contact(&RoundedHull::of_scene(with), &RoundedHull::of_object(self))?;
+        Some(Collision {
+            point: contact.point,
+            normal: contact.normal,
+            impulse: 0.0,
+        })
+    }
+
+    /// Where the object touches the scene object, without any response.
+    /// The normal points from the scene object to `self`.
+    pub fn contact_points_scene(&self, with: &SceneObject) -> Option<ContactManifold> {
+        if let PhysObjectShape::Disc { .. } = self.shape {
+            let contact = self.touch_scene(with)?;
+            return Some(ContactManifold {
+                normal: contact.normal,
+                points: vec![ContactPoint {
+                    point: contact.point,
+                    depth: contact.depth,
+                }],
+            });
+        }
+
+        if with.lets_through(self.center, self.velocity_linear) {
+            return None;
+        }
+
+        manifold(&RoundedHull::of_scene(with), &RoundedHull::of_object(self))
+    }
+
+    /// Like [`PhysObject::contact_points_scene`], the normal points from `self` to the other object.
+    /// The other object is centered at `with_center`, so that it can be its image across the edges of the torus.
+    pub fn contact_points<M2>(
+        &self,
+        with: &PhysObject<M2>,
+        with_center: Point,
+    ) -> Option<ContactManifold> {
+        let with = RoundedHull::of_shape(&with.shape, with_center, with.orientation);
+        manifold(&RoundedHull::of_object(self), &with)
+    }
+
+    /// Contact of a disc with the scene object that it touches at `location`.
+    fn disc_contact_at(&self, location: Point, radius: f32) -> Contact {
+        Contact {
+            point: location,
+            normal: location.dir(self.center).normalize_or_zero(),
+            depth: radius - self.center.dir(location).length(),
+        }
+    }
+
+    /// Where the object touches the scene object, with the normal from the scene object to `self`.
+    /// Discs against segments are handled without the general narrowphase.
+    fn touch_scene(&self, with: &SceneObject) -> Option<Contact> {
         use PhysObjectShape::*;
         use SceneObjectShape::*;
 
-        match (self.shape, with.shape) {
-            (Disc { radius }, SegmentH { dx }) => todo!(),
-            (Disc { radius }, SegmentV { dy }) => todo!(),
+        if with.lets_through(self.center, self.velocity_linear) {
+            return None;
+        }
+
+        match (&self.shape, with.shape) {
+            (&Disc { radius }, SegmentH { dx }) => {
+                trace!(
+                    "Colliding Disc {{@{}, r={radius}}} with SegmentH {{@{}, dx={dx}}}",
+                    self.center, with.center
+                );
+
+                // The closest point of the segment is right above or below the disc, or one of the ends
+                let half = dx.abs() / 2.0;
+                let x = self
+                    .center
+                    .x
+                    .clamp(with.center.x - half, with.center.x + half);
+                let location = Point::new(x, with.center.y);
+
+                (self.center.dir(location).length_squared() <= radius * radius)
+                    .then(|| self.disc_contact_at(location, radius))
+            }
+
+            (&Disc { radius }, SegmentV { dy }) => {
+                trace!(
+                    "Colliding Disc {{@{}, r={radius}}} with SegmentV {{@{}, dy={dy}}}",
+                    self.center, with.center
+                );
+
+                // The closest point of the segment is to the left or to the right of the disc, or one of the ends
+                let half = dy.abs() / 2.0;
+                let y = self
+                    .center
+                    .y
+                    .clamp(with.center.y - half, with.center.y + half);
+                let location = Point::new(with.center.x, y);
 
-            (Disc { radius }, Segment { dx, dy }) => {
+                (self.center.dir(location).length_squared() <= radius * radius)
+                    .then(|| self.disc_contact_at(location, radius))
+            }
+
+            (&Disc { radius }, Segment { dx, dy }) => {
                 let (sa, sb) = (
                     with.center - vec2(dx, dy) / 2.0,
                     with.center + vec2(dx, dy) / 2.0,
@@ -56,29 +297,13 @@ impl<M> CollideWith<&SceneObject> for PhysObject<M> {
 
                 if discr < 0.0 {
                     trace!(" -> The line defined by the segment does not intersect the disc");
-                    return;
+                    None
                 } else {
                     trace!(
                         " -> The line defined by the segment is a secant or a tangent to the disc"
                     );
 
-                    let mut collision_for = |t: f32| {
-                        let location = sa.lerp(sb, t);
-
-                        // Moving towards the segment
-                        if self.velocity_linear.dot(self.center.dir(location)) > 0.0 {
-                            let momentum = self.velocity_linear * self.mass;
-                            let impulse = momentum.project_onto(self.center.dir(location));
-
-                            // Dampen the impulse a bit
-                            let impulse = impulse * 0.95;
-
-                            self.velocity_linear -= 2.0 * impulse / self.mass;
-                        }
-
-                        let overlap = radius - self.center.dir(location).length();
-                        self.center += location.dir(self.center).clamp_length(overlap, overlap);
-                    };
+                    let contact_for = |t: f32| Some(self.disc_contact_at(sa.lerp(sb, t), radius));
 
                     // This represents the midpoint of the chord
                     let t_halfway = -mb / (2.0 * ma);
@@ -86,8 +311,7 @@ impl<M> CollideWith<&SceneObject> for PhysObject<M> {
 
                     if t_start <= t_halfway && t_halfway <= t_end {
                         trace!(" -> The disc hit the \"inner\" part of the segment");
-                        collision_for(t_halfway);
-                        return;
+                        return contact_for(t_halfway);
                     }
 
                     let discr_rt = discr.sqrt();
@@ -96,55 +320,158 @@ impl<M> CollideWith<&SceneObject> for PhysObject<M> {
 
                     if t_halfway <= t_start && t_start <= t_out {
                         trace!(" -> The disc hit the start of the segment");
-                        collision_for(t_start);
-                        return;
+                        return contact_for(t_start);
                     }
 
                     if t_in <= t_end && t_end <= t_halfway {
                         trace!(" -> The disc hit the end of the segment");
-                        collision_for(t_end);
-                        return;
+                        return contact_for(t_end);
                     }
+
+                    None
                 }
             }
+
+            (_, _) => {
+                let hull_self = RoundedHull::of_object(self);
+                let hull_with = RoundedHull::of_scene(with);
+
+                contact(&hull_with, &hull_self)
+            }
         }
     }
 }
 
+/// Time of impact of a disc moving by `motion` with the segment `sa`-`sb`,
+/// as a share of the motion, together with the contact point and the normal towards the disc.
+fn sweep_disc_segment(
+    center: Point,
+    radius: f32,
+    motion: Vec2,
+    sa: Point,
+    sb: Point,
+) -> Option<(f32, Point, Vec2)> {
+    let mut first: Option<(f32, Point, Vec2)> = None;
+    let mut consider = |t: f32, point: Point, normal: Vec2| {
+        if (0.0..=1.0).contains(&t) && first.is_none_or(|(first_t, _, _)| t < first_t) {
+            first = Some((t, point, normal));
+        }
+    };
+
+    // The side of the disc hits the inner part of the segment
+    let a_b = sa.dir(sb);
+    let mut normal = a_b.perp().normalize_or_zero();
+    let mut distance = sa.dir(center).dot(normal);
+    if distance < 0.0 {
+        normal = -normal;
+        distance = -distance;
+    }
+
+    let approach = -motion.dot(normal);
+    if distance >= radius && approach > 0.0 {
+        let t = (distance - radius) / approach;
+        let point = center + motion * t - normal * radius;
+        let along = sa.dir(point).dot(a_b) / a_b.length_squared();
+        if (0.0..=1.0).contains(&along) {
+            consider(t, point, normal);
+        }
+    }
+
+    // The disc hits one of the ends of the segment
+    for end in [sa, sb] {
+        let end_c = end.dir(center);
+
+        let ma = motion.dot(motion);
+        let mb = 2.0 * end_c.dot(motion);
+        let mc = end_c.dot(end_c) - radius * radius;
+
+        let discr = mb * mb - 4.0 * ma * mc;
+        if mc < 0.0 || ma == 0.0 || discr < 0.0 {
+            continue;
+        }
+
+        let t = (-mb - discr.sqrt()) / (2.0 * ma);
+        let normal = end.dir(center + motion * t).normalize_or_zero();
+        consider(t, end, normal);
+    }
+
+    first
+}
+
+pub trait CollideWith<Obj> {
+    /// Resolve the contact with the object, if there is one.
+    fn collide(&mut self, with: Obj) -> Option<Collision>;
+}
+
+impl<M> CollideWith<&SceneObject> for PhysObject<M> {
+    fn collide(&mut self, with: &SceneObject) -> Option<Collision> {
+        let contact = self.touch_scene(with)?;
+        let collision = self.bounce_off(with, contact.point, contact.normal);
+        self.center += contact.normal * contact.depth;
+
+        Some(collision)
+    }
+}
+
 impl<M1, M2> CollideWith<&mut PhysObject<M2>> for PhysObject<M1> {
-    fn collide(&mut self, with: &mut PhysObject<M2>) {
+    fn collide(&mut self, with: &mut PhysObject<M2>) -> Option<Collision> {
         use PhysObjectShape::*;
 
-        match (self.shape, with.shape) {
-            (Disc { radius: r1 }, Disc { radius: r2 }) => {
+        match (&self.shape, &with.shape) {
+            (&Disc { radius: r1 }, &Disc { radius: r2 }) => {
                 let r_sq = (r1 + r2) * (r1 + r2);
                 let d_sq = self.center.dist_sq(with.center);
-                if d_sq <= r_sq {
-                    // There is a collision.
+                if d_sq > r_sq {
+                    return None;
+                }
 
-                    // TODO: impulse should take angular velocity into account
+                // There is a collision.
 
-                    let location = self.center.lerp(with.center, r1 / (r1 + r2));
+                let location = self.center.lerp(with.center, r1 / (r1 + r2));
+                let normal = self.center.dir(with.center).normalize_or_zero();
 
-                    // Pretend that `with` is not moving
-                    let vel_lin = self.velocity_linear - with.velocity_linear;
+                let side_self = ContactSide::new(self, location);
+                let side_with = ContactSide::new(with, location);
+                let material = self.material.combine(with.material);
 
-                    if vel_lin.dot(self.center.dir(location)) > 0.0 {
-                        // The objects are moving towards each other
+                let impulse = contact_impulse(&side_self, &side_with, normal, material);
 
-                        let momentum_self = self.velocity_linear * self.mass;
-                        let momentum_with = with.velocity_linear * with.mass;
+                self.accumulate_impulse(-impulse, side_self.arm);
+                with.accumulate_impulse(impulse, side_with.arm);
 
-                        let impulse_self = momentum_self.project_onto(self.center.dir(location));
-                        let impulse_with = momentum_with.project_onto(with.center.dir(location));
+                Some(Collision {
+                    point: location,
+                    normal,
+                    impulse: impulse.length(),
+                })
+            }
 
-                        self.velocity_acc.velocity_linear -= impulse_self / self.mass;
-                        self.velocity_acc.velocity_linear += impulse_with / self.mass;
+            (_, _) => {
+                let hull_self = RoundedHull::of_object(self);
+                let hull_with = RoundedHull::of_object(with);
 
-                        with.velocity_acc.velocity_linear -= impulse_with / with.mass;
-                        with.velocity_acc.velocity_linear += impulse_self / with.mass;
-                    }
-                }
+                let contact = contact(&hull_self, &hull_with)?;
+
+                let side_self = ContactSide::new(self, contact.point);
+                let side_with = ContactSide::new(with, contact.point);
+                let material = self.material.combine(with.material);
+
+                let impulse = contact_impulse(&side_self, &side_with, contact.normal, material);
+
+                self.accumulate_impulse(-impulse, side_self.arm);
+                with.accumulate_impulse(impulse, side_with.arm);
+
+                // Separate the objects, the lighter one moves more
+                let share =
+                    side_self.inverse_mass / (side_self.inverse_mass + side_with.inverse_mass);
+                self.center -= contact.normal * contact.depth * share;
+                with.center += contact.normal * contact.depth * (1.0 - share);
+
+                Some(Collision {
+                    point: contact.point,
+                    normal: contact.normal,
+                    impulse: impulse.length(),
+                })
             }
         }
     }
@@ -245,12 +572,291 @@ mod tests {
             let mut d =
                 PhysObject::new_disc((0.0, 1.0).into(), 1.0, 1.0).with_velocity(vec2(0.0, -1.0));
 
-            let mut s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into());
+            let mut s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into())
+                .with_material(Material {
+                    restitution: 0.9,
+                    ..Default::default()
+                });
 
             interact(&mut d, &mut s);
 
             assert_eq!(d.center, (0.0, 1.9).into());
             assert_eq!(d.velocity_linear, vec2(0.0, 0.9));
+            assert_eq!(d.velocity_angular, 0.0);
+        }
+
+        {
+            // Sliding along the floor while hitting it makes the disc spin
+            let mut d = PhysObject::new_disc((0.0, 1.0).into(), 1.0, 1.0)
+                .with_velocity(vec2(1.0, -1.0))
+                .with_material(ROUGH);
+
+            let s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into())
+                .with_material(ROUGH);
+
+            d.collide(&s);
+
+            // Friction only takes away horizontal velocity, restitution is 0.5 * 0.5
+            assert_eq!(d.velocity_linear.y, 0.25);
+            assert!(0.0 < d.velocity_linear.x && d.velocity_linear.x < 1.0);
+            // Rolling to the right is clockwise
+            assert!(d.velocity_angular < 0.0);
+        }
+    }
+
+    const ROUGH: Material = Material {
+        restitution: 0.5,
+        static_friction: 0.3,
+        dynamic_friction: 0.2,
+        stickiness: 0.0,
+    };
+
+    fn kinetic_energy(obj: &PhysObject<()>) -> f32 {
+        let linear = obj.mass * obj.velocity_linear.length_squared() / 2.0;
+        let angular = obj.inertia() * obj.velocity_angular.powi(2) / 2.0;
+        linear + angular
+    }
+
+    #[test]
+    fn interaction_momentum_conserved() {
+        init_logging();
+
+        let mut d1 = PhysObject::new_disc((0.0, 0.0).into(), 1.0, 2.0)
+            .with_velocity(vec2(1.5, 0.5))
+            .with_material(ROUGH);
+        d1.velocity_angular = 2.0;
+
+        let mut d2 = PhysObject::new_disc((1.2, 1.2).into(), 0.7, 0.5)
+            .with_velocity(vec2(-1.0, 0.2))
+            .with_material(ROUGH);
+
+        let momentum = |d1: &PhysObject<()>, d2: &PhysObject<()>| {
+            d1.velocity_linear * d1.mass + d2.velocity_linear * d2.mass
+        };
+
+        let momentum_before = momentum(&d1, &d2);
+        let energy_before = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+        d1.collide(&mut d2);
+        d1.flush_acc();
+        d2.flush_acc();
+
+        let momentum_after = momentum(&d1, &d2);
+        let energy_after = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+        assert!(
+            momentum_before.abs_diff_eq(momentum_after, 1e-5),
+            "{momentum_before} != {momentum_after}"
+        );
+        assert!(energy_after < energy_before);
+        // Friction made both of them spin
+        assert_ne!(d2.velocity_angular, 0.0);
+    }
+
+    #[test]
+    fn interaction_energy_loss() {
+        init_logging();
+
+        // Head-on, so friction does not matter
+        let (m1, m2) = (3.0, 1.0);
+        let (v1, v2) = (2.0, -1.0);
+
+        for restitution in [0.0, 0.5, 1.0] {
+            let material = Material {
+                restitution,
+                ..ROUGH
+            };
+
+            let mut d1 = PhysObject::new_disc((0.0, 0.0).into(), 1.0, m1)
+                .with_velocity(vec2(v1, 0.0))
+                .with_material(Material {
+                    restitution: 1.0,
+                    ..material
+                });
+            let mut d2 = PhysObject::new_disc((1.9, 0.0).into(), 1.0, m2)
+                .with_velocity(vec2(v2, 0.0))
+                .with_material(material);
+
+            let energy_before = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+            d1.collide(&mut d2);
+            d1.flush_acc();
+            d2.flush_acc();
+
+            let energy_after = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+            // Energy lost in a collision with the given coefficient of restitution
+            let reduced_mass = m1 * m2 / (m1 + m2);
+            let expected_loss =
+                reduced_mass * (v1 - v2).powi(2) * (1.0 - restitution.powi(2)) / 2.0;
+
+            assert!(
+                (energy_before - energy_after - expected_loss).abs() < 1e-5,
+                "restitution {restitution}: lost {}, expected {expected_loss}",
+                energy_before - energy_after
+            );
+
+            // Objects separate with the given share of the approach speed
+            let separation = d2.velocity_linear.x - d1.velocity_linear.x;
+            assert!((separation - restitution * (v1 - v2)).abs() < 1e-5);
+            assert_eq!(d1.velocity_angular, 0.0);
+        }
+    }
+
+    #[test]
+    fn interaction_disc_segment_impact() {
+        init_logging();
+
+        let s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into());
+
+        // Falling onto the inner part of the segment
+        let d = PhysObject::new_disc((0.0, 3.0).into(), 1.0, 1.0).with_velocity(vec2(0.0, -4.0));
+        let impact = d.impact_with(&s, 1.0).unwrap();
+        assert_eq!(impact.seconds, 0.5);
+        assert_eq!(impact.point, (0.0, 0.0).into());
+        assert_eq!(impact.normal, vec2(0.0, 1.0));
+
+        // Too slow to get there within the time
+        assert!(d.impact_with(&s, 0.25).is_none());
+
+        // Coming from the side onto the end of the segment
+        let d = PhysObject::new_disc((4.0, 0.0).into(), 1.0, 1.0).with_velocity(vec2(-2.0, 0.0));
+        let impact = d.impact_with(&s, 1.0).unwrap();
+        assert_eq!(impact.seconds, 1.0);
+        assert_eq!(impact.point, (1.0, 0.0).into());
+        assert_eq!(impact.normal, vec2(1.0, 0.0));
+
+        // Moving away
+        let d = PhysObject::new_disc((0.0, 3.0).into(), 1.0, 1.0).with_velocity(vec2(0.0, 4.0));
+        assert!(d.impact_with(&s, 1.0).is_none());
+    }
+
+    #[test]
+    fn interaction_box_segment() {
+        init_logging();
+
+        let s = SceneObject::new_segment((-3.0, 0.0).into(), (3.0, 0.0).into());
+
+        // Landing flat bounces straight back up
+        let mut b =
+            PhysObject::new_box((0.0, 0.95).into(), 1.0, 1.0, 1.0).with_velocity(vec2(0.0, -2.0));
+        b.collide(&s);
+        assert!(b.velocity_linear.abs_diff_eq(vec2(0.0, 2.0), 1e-5), "{b:?}");
+        assert!(b.velocity_angular.abs() < 1e-5, "{b:?}");
+        assert!((b.center.y - 1.0).abs() < 1e-5, "{b:?}");
+
+        // Landing on a corner makes it spin
+        let mut b = PhysObject::new_box((0.5, 1.2).into(), 1.0, 1.0, 1.0)
+            .with_orientation(0.3)
+            .with_velocity(vec2(0.0, -2.0));
+        b.collide(&s);
+        assert!(b.velocity_linear.y > 0.0, "{b:?}");
+        assert!(b.velocity_angular.abs() > 0.1, "{b:?}");
+    }
+
+    #[test]
+    fn interaction_shapes_momentum_conserved() {
+        init_logging();
+
+        let triangle = vec![vec2(0.0, 1.0), vec2(-1.0, -0.5), vec2(1.0, -0.5)];
+        let mut shapes = [
+            PhysObject::new_capsule((0.0, 0.0).into(), 1.0, 0.5, 2.0).with_orientation(0.3),
+            PhysObject::new_polygon((0.0, 0.0).into(), triangle, 1.5),
+            PhysObject::new_box((0.0, 0.0).into(), 0.5, 0.8, 1.0).with_orientation(-0.2),
+        ];
+
+        for shape in &mut shapes {
+            let mut d = PhysObject::new_disc((1.0, 0.6).into(), 0.7, 0.5)
+                .with_velocity(vec2(-1.0, -0.5))
+                .with_material(ROUGH);
+            shape.material = ROUGH;
+            shape.velocity_linear = vec2(0.5, 0.2);
+
+            let momentum = |s: &PhysObject<()>, d: &PhysObject<()>| {
+                s.velocity_linear * s.mass + d.velocity_linear * d.mass
+            };
+
+            let momentum_before = momentum(shape, &d);
+            let energy_before = kinetic_energy(shape) + kinetic_energy(&d);
+
+            shape.collide(&mut d);
+            shape.flush_acc();
+            d.flush_acc();
+
+            let momentum_after = momentum(shape, &d);
+            let energy_after = kinetic_energy(shape) + kinetic_energy(&d);
+
+            assert!(
+                momentum_before.abs_diff_eq(momentum_after, 1e-5),
+                "{momentum_before} != {momentum_after}"
+            );
+            assert!(energy_after < energy_before, "{shape:?}");
+            assert_ne!(shape.velocity_angular, 0.0, "{shape:?}");
+        }
+    }
+
+    #[test]
+    fn interaction_disc_segment_axis_aligned() {
+        init_logging();
+
+        let discs = || {
+            [
+                // Hitting the inner part from either side
+                PhysObject::new_disc((0.3, 0.8).into(), 1.0, 1.0).with_velocity(vec2(0.5, -1.0)),
+                PhysObject::new_disc((-0.4, -0.6).into(), 1.0, 1.0).with_velocity(vec2(0.2, 1.5)),
+                // Hitting the ends
+                PhysObject::new_disc((1.5, 0.3).into(), 1.0, 1.0).with_velocity(vec2(-1.0, -0.2)),
+                PhysObject::new_disc((-1.6, -0.5).into(), 1.0, 1.0).with_velocity(vec2(1.0, 0.4)),
+                // Too far away
+                PhysObject::new_disc((0.0, 1.5).into(), 1.0, 1.0).with_velocity(vec2(0.0, -1.0)),
+                PhysObject::new_disc((2.5, 0.0).into(), 1.0, 1.0).with_velocity(vec2(-1.0, 0.0)),
+            ]
+            .map(|d| d.with_material(ROUGH))
+        };
+
+        // For vertical segments, the discs are turned by a quarter
+        let check = |fast: SceneObject, general: SceneObject, turn: bool| {
+            for (mut d_fast, mut d_general) in discs().into_iter().zip(discs()) {
+                if turn {
+                    d_fast.center = Point::new(-d_fast.center.y, d_fast.center.x);
+                    d_fast.velocity_linear = d_fast.velocity_linear.perp();
+                }
+                d_general.center = d_fast.center;
+                d_general.velocity_linear = d_fast.velocity_linear;
+
+                d_fast.collide(&fast);
+                d_general.collide(&general);
+
+                assert!(
+                    d_fast.center.dist(d_general.center) < 1e-5,
+                    "{d_fast:?} != {d_general:?}"
+                );
+                assert!(
+                    d_fast
+                        .velocity_linear
+                        .abs_diff_eq(d_general.velocity_linear, 1e-5),
+                    "{d_fast:?} != {d_general:?}"
+                );
+                assert!(
+                    (d_fast.velocity_angular - d_general.velocity_angular).abs() < 1e-5,
+                    "{d_fast:?} != {d_general:?}"
+                );
+            }
+        };
+
+        // Both directions of the segment behave the same
+        for (start, end) in [(-1.0, 1.0), (1.0, -1.0)] {
+            let h =
+                SceneObject::new_segment_h((start, 0.0).into(), end - start).with_material(ROUGH);
+            let general = SceneObject::new_segment((start, 0.0).into(), (end, 0.0).into())
+                .with_material(ROUGH);
+            check(h, general, false);
+
+            let v =
+                SceneObject::new_segment_v((0.0, start).into(), end - start).with_material(ROUGH);
+            let general = SceneObject::new_segment((0.0, start).into(), (0.0, end).into())
+                .with_material(ROUGH);
+            check(v, general, true);
         }
     }
 }
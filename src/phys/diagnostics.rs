@@ -0,0 +1,36 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::phys::object::PhysObject;
+
+/// Totals of the simulation after a step, to see how well the integrator keeps the energy and the momentum.
+/// Kinematic objects move no matter what, so they are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub kinetic_energy: f32,
+    /// In the gravity, relative to the origin of the world, and in the stretched springs.
+    /// The fields other than the gravity are not counted
+    pub potential_energy: f32,
+    pub momentum: Vec2,
+}
+
+impl Diagnostics {
+    pub fn total_energy(&self) -> f32 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    pub fn add_object<M>(&mut self, obj: &PhysObject<M>, gravity: Vec2) {
+        if obj.kinematic {
+            return;
+        }
+
+        self.kinetic_energy += obj.mass * obj.velocity_linear.length_squared() / 2.0
+            + obj.inertia() * obj.velocity_angular.powi(2) / 2.0;
+        self.potential_energy -= obj.mass * (gravity * obj.gravity_scale).dot(obj.center.vec());
+        self.momentum += obj.mass * obj.velocity_linear;
+    }
+
+    pub fn add_spring(&mut self, stretch: f32, stiffness: f32) {
+        self.potential_energy += stiffness * stretch * stretch / 2.0;
+    }
+}
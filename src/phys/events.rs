@@ -0,0 +1,158 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{geo::Point, phys::collision::Collision, phys::handle::Handle};
+
+/// What touched what.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ContactPair {
+    /// Two objects, ordered by their handles
+    Objects(Handle, Handle),
+    /// An object and the scene object at this index
+    Scene(Handle, usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContactPhase {
+    /// The pair started touching during the step
+    Begin,
+    /// The pair was touching during the previous step too
+    Persist,
+    /// The pair stopped touching, the point and the normal are the last known ones
+    End,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContactEvent {
+    pub pair: ContactPair,
+    pub point: Point,
+    /// Unit vector that points from the first object of the pair to the second one,
+    /// or from the scene object to the object
+    pub normal: Vec2,
+    /// Magnitude of the impulse that resolved the contact during the step
+    pub impulse: f32,
+    pub phase: ContactPhase,
+}
+
+/// Remembers which pairs were touching, to tell apart the phases of contacts.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ContactTracker {
+    /// Contacts of the last finished step, sorted by pair
+    touching: Vec<ContactEvent>,
+    /// Contacts of the current step
+    current: Vec<ContactEvent>,
+}
+
+impl ContactTracker {
+    pub fn record_objects(&mut self, a: Handle, b: Handle, collision: Collision) {
+        let (pair, normal) = if a < b {
+            (ContactPair::Objects(a, b), collision.normal)
+        } else {
+            (ContactPair::Objects(b, a), -collision.normal)
+        };
+
+        self.record(pair, collision.point, normal, collision.impulse);
+    }
+
+    pub fn record_scene(&mut self, obj: Handle, scene_index: usize, collision: Collision) {
+        let pair = ContactPair::Scene(obj, scene_index);
+        self.record(pair, collision.point, collision.normal, collision.impulse);
+    }
+
+    fn record(&mut self, pair: ContactPair, point: Point, normal: Vec2, impulse: f32) {
+        self.current.push(ContactEvent {
+            pair,
+            point,
+            normal,
+            impulse,
+            phase: ContactPhase::Begin,
+        });
+    }
+
+    /// The pair of objects was not checked during the step, it is assumed to be still touching
+    /// if it was touching during the previous step.
+    pub fn keep_objects(&mut self, a: Handle, b: Handle) {
+        let pair = ContactPair::Objects(a.min(b), a.max(b));
+        if let Ok(i) = self.touching.binary_search_by_key(&pair, |e| e.pair) {
+            self.keep(i);
+        }
+    }
+
+    /// Like [`ContactTracker::keep_objects`], but for all contacts of the object with the scene.
+    pub fn keep_scene(&mut self, obj: Handle) {
+        let start = self
+            .touching
+            .partition_point(|e| e.pair < ContactPair::Scene(obj, 0));
+        let end = self
+            .touching
+            .partition_point(|e| e.pair <= ContactPair::Scene(obj, usize::MAX));
+
+        for i in start..end {
+            self.keep(i);
+        }
+    }
+
+    fn keep(&mut self, touching_index: usize) {
+        self.current.push(ContactEvent {
+            impulse: 0.0,
+            ..self.touching[touching_index]
+        });
+    }
+
+    /// Pairs that are touching during the current step so far, some of them can repeat.
+    pub fn current_pairs(&self) -> impl Iterator<Item = ContactPair> + '_ {
+        self.current.iter().map(|e| e.pair)
+    }
+
+    /// Objects that were touching the object during the last finished step.
+    pub fn touching_objects(&self, obj: Handle) -> impl Iterator<Item = Handle> + '_ {
+        self.touching.iter().filter_map(move |e| match e.pair {
+            ContactPair::Objects(a, b) if a == obj => Some(b),
+            ContactPair::Objects(a, b) if b == obj => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Compare the contacts of the step that just finished with the ones of the previous step.
+    pub fn finish_step(&mut self, events: &mut Vec<ContactEvent>) {
+        // The same pair can touch several times during a step, the impulses add up
+        self.current.sort_by_key(|e| e.pair);
+        self.current.dedup_by(|later, earlier| {
+            if later.pair != earlier.pair {
+                return false;
+            }
+
+            earlier.point = later.point;
+            earlier.normal = later.normal;
+            earlier.impulse += later.impulse;
+            true
+        });
+
+        for event in &mut self.current {
+            if self
+                .touching
+                .binary_search_by_key(&event.pair, |e| e.pair)
+                .is_ok()
+            {
+                event.phase = ContactPhase::Persist;
+            }
+        }
+        events.extend_from_slice(&self.current);
+
+        for event in &self.touching {
+            if self
+                .current
+                .binary_search_by_key(&event.pair, |e| e.pair)
+                .is_err()
+            {
+                events.push(ContactEvent {
+                    impulse: 0.0,
+                    phase: ContactPhase::End,
+                    ..*event
+                });
+            }
+        }
+
+        self.touching = std::mem::take(&mut self.current);
+    }
+}
@@ -0,0 +1,255 @@
+//! Regions of the world that change how objects inside of them move, see [`super::Physics::add_field`].
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geo::{Point, ToricGeometry},
+    phys::{
+        narrowphase::RoundedHull,
+        object::{PhysObject, PhysObjectShape},
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FieldEffect {
+    /// Constant acceleration, tiles per second squared
+    Wind(Vec2),
+    /// Acceleration towards the center of the region, away from it when negative
+    Radial(f32),
+    /// No gravity inside of the region, the other fields still work
+    ZeroGravity,
+    /// Part of the velocity that is lost per second
+    Drag(f32),
+    /// Pushes objects against the gravity, by the weight of the liquid they displace.
+    /// Objects that are denser than the liquid sink.
+    /// The drag works like [`FieldEffect::Drag`], but only on the part of the object that is submerged
+    Buoyancy { density: f32, drag: f32 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForceField {
+    pub shape: PhysObjectShape,
+    pub center: Point,
+    /// Radians, counterclockwise
+    pub orientation: f32,
+    pub effect: FieldEffect,
+}
+
+impl ForceField {
+    pub fn new(shape: PhysObjectShape, center: Point, effect: FieldEffect) -> Self {
+        Self {
+            shape,
+            center,
+            orientation: 0.0,
+            effect,
+        }
+    }
+
+    pub fn with_orientation(mut self, orientation: f32) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn hull(&self) -> RoundedHull {
+        RoundedHull::of_shape(&self.shape, self.center, self.orientation)
+    }
+}
+
+/// Points along the object that are checked to find out how much of it is submerged.
+const BUOYANCY_SAMPLES: usize = 8;
+
+/// Acceleration of the object by the gravity and the fields that its center is in,
+/// when the center is at `center`, and the part of its velocity that is lost per second.
+/// `hulls` are the regions of the fields.
+pub fn field_acceleration<M>(
+    obj: &PhysObject<M>,
+    center: Point,
+    gravity: Vec2,
+    fields: &[ForceField],
+    hulls: &[RoundedHull],
+    geometry: &ToricGeometry,
+) -> (Vec2, f32) {
+    // Position of the object that is the closest to the field, through the edges of the torus
+    let near = |field: &ForceField| field.center + geometry.dir(field.center, center);
+    let inside = |field: &ForceField, hull: &RoundedHull| hull.contains(near(field).vec());
+
+    let zero_gravity = fields
+        .iter()
+        .zip(hulls)
+        .any(|(field, hull)| field.effect == FieldEffect::ZeroGravity && inside(field, hull));
+    let gravity = if zero_gravity {
+        Vec2::ZERO
+    } else {
+        gravity * obj.gravity_scale
+    };
+
+    let mut acceleration = gravity;
+    let mut drag = 0.0;
+
+    for (field, hull) in fields.iter().zip(hulls) {
+        match field.effect {
+            FieldEffect::Wind(wind) if inside(field, hull) => acceleration += wind,
+            FieldEffect::Radial(strength) if inside(field, hull) => {
+                let towards = near(field).dir(field.center).normalize_or_zero();
+                acceleration += towards * strength;
+            }
+            FieldEffect::Drag(amount) if inside(field, hull) => drag += amount,
+            FieldEffect::Buoyancy {
+                density,
+                drag: amount,
+            } => {
+                let submerged = submerged(hull, near(field), obj.shape.bounding_radius(), gravity);
+                if submerged > 0.0 {
+                    let displaced = density * obj.shape.area() * submerged;
+                    acceleration -= gravity * displaced / obj.mass;
+                    drag += amount * submerged;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (acceleration, drag)
+}
+
+/// Part of the object under the surface of the liquid, measured along the gravity.
+fn submerged(liquid: &RoundedHull, center: Point, radius: f32, gravity: Vec2) -> f32 {
+    let Some(down) = gravity.try_normalize() else {
+        return 0.0;
+    };
+
+    let inside = (0..BUOYANCY_SAMPLES)
+        .filter(|i| {
+            let along = (*i as f32 + 0.5) / BUOYANCY_SAMPLES as f32 * 2.0 - 1.0;
+            liquid.contains(center.vec() + down * along * radius)
+        })
+        .count();
+
+    inside as f32 / BUOYANCY_SAMPLES as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glam::vec2;
+
+    use crate::{init_logging, phys::Physics};
+
+    use super::*;
+
+    fn world() -> Physics<()> {
+        Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 })
+    }
+
+    fn region(half_size: f32) -> PhysObjectShape {
+        PhysObjectShape::Box {
+            half_width: half_size,
+            half_height: half_size,
+        }
+    }
+
+    fn advance(physics: &mut Physics<()>, steps: usize) {
+        for _ in 0..steps {
+            physics.advance_by(&[], Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn field_gravity() {
+        init_logging();
+
+        let mut physics = world();
+        physics.set_gravity(vec2(1.0, -2.0));
+        physics.add_field(ForceField::new(
+            region(1.0),
+            (10.0, 0.0).into(),
+            FieldEffect::ZeroGravity,
+        ));
+
+        let normal = physics.add(PhysObject::new_disc((0.0, 0.0).into(), 0.25, 1.0));
+        let half = physics
+            .add(PhysObject::new_disc((-10.0, 0.0).into(), 0.25, 1.0).with_gravity_scale(0.5));
+        let floating = physics.add(PhysObject::new_disc((10.0, 0.0).into(), 0.25, 1.0));
+
+        advance(&mut physics, 10);
+
+        let velocity = |handle| physics.get(handle).unwrap().velocity_linear;
+        assert!(velocity(normal).abs_diff_eq(vec2(0.1, -0.2), 1e-5));
+        assert!(velocity(half).abs_diff_eq(vec2(0.05, -0.1), 1e-5));
+        assert_eq!(velocity(floating), Vec2::ZERO);
+    }
+
+    #[test]
+    fn field_wind_radial_drag() {
+        init_logging();
+
+        let mut physics = world();
+        physics.set_gravity(Vec2::ZERO);
+
+        // On the edge of the torus
+        let wind = FieldEffect::Wind(vec2(0.0, 4.0));
+        physics.add_field(ForceField::new(region(2.0), (49.0, 0.0).into(), wind));
+        let attractor = FieldEffect::Radial(2.0);
+        physics.add_field(ForceField::new(region(2.0), (0.0, 0.0).into(), attractor));
+        let repeller = FieldEffect::Radial(-2.0);
+        physics.add_field(ForceField::new(region(2.0), (10.0, 0.0).into(), repeller));
+        let drag = FieldEffect::Drag(1.0);
+        physics.add_field(ForceField::new(region(2.0), (20.0, 0.0).into(), drag));
+
+        let blown = physics.add(PhysObject::new_disc((-49.5, 0.0).into(), 0.25, 1.0));
+        let attracted = physics.add(PhysObject::new_disc((1.0, 0.0).into(), 0.25, 1.0));
+        let repelled = physics.add(PhysObject::new_disc((11.0, 0.0).into(), 0.25, 1.0));
+        let slowed = physics
+            .add(PhysObject::new_disc((19.0, 0.0).into(), 0.25, 1.0).with_velocity(vec2(1.0, 0.0)));
+
+        advance(&mut physics, 10);
+
+        let velocity = |handle| physics.get(handle).unwrap().velocity_linear;
+        assert!(velocity(blown).abs_diff_eq(vec2(0.0, 0.4), 1e-5));
+        assert!(velocity(attracted).abs_diff_eq(vec2(-0.2, 0.0), 1e-5));
+        assert!(velocity(repelled).abs_diff_eq(vec2(0.2, 0.0), 1e-5));
+        assert!((velocity(slowed).x - 0.99f32.powi(10)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn field_buoyancy() {
+        init_logging();
+
+        let mut physics = world();
+
+        // A pool with the surface at 0
+        let liquid = FieldEffect::Buoyancy {
+            density: 1.0,
+            drag: 2.0,
+        };
+        physics.add_field(ForceField::new(region(5.0), (0.0, -5.0).into(), liquid));
+
+        // Half as dense as the liquid, and twice as dense
+        let side = 0.5;
+        let area = 4.0 * side * side;
+        let light = physics.add(PhysObject::new_box(
+            (-2.0, 1.0).into(),
+            side,
+            side,
+            area / 2.0,
+        ));
+        let heavy = physics.add(PhysObject::new_box(
+            (2.0, 1.0).into(),
+            side,
+            side,
+            area * 2.0,
+        ));
+
+        advance(&mut physics, 1000);
+
+        // Half submerged
+        let light = physics.get(light).unwrap();
+        assert!(light.center.y.abs() < 0.1, "{light:?}");
+        assert!(light.velocity_linear.length() < 0.01, "{light:?}");
+
+        let heavy = physics.get(heavy).unwrap();
+        assert!(heavy.center.y < -5.0, "{heavy:?}");
+    }
+}
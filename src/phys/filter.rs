@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// Decides which objects collide with each other.
+///
+/// Two objects collide when the group of each of them is in the mask of the other one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionFilter {
+    /// Layers that the object is on
+    pub group: u32,
+    /// Layers that the object collides with
+    pub mask: u32,
+}
+
+impl Default for CollisionFilter {
+    /// On the first layer, collides with everything.
+    fn default() -> Self {
+        Self {
+            group: 1,
+            mask: u32::MAX,
+        }
+    }
+}
+
+impl CollisionFilter {
+    pub fn new(group: u32, mask: u32) -> Self {
+        Self { group, mask }
+    }
+
+    pub fn allows(self, other: Self) -> bool {
+        self.group & other.mask != 0 && other.group & self.mask != 0
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Refers to an object in [`super::Physics`].
+///
+/// A handle stays valid until its object is removed, and it never refers to any other object after that,
+/// even if the slot is reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Handle {
+    slot: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    /// Index of the object in the densely packed storage, `None` if the slot is free
+    index: Option<usize>,
+}
+
+/// Maps handles to indices of objects that are stored densely, without gaps.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Handles {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    /// Handle of the object at every index
+    handles: Vec<Handle>,
+}
+
+impl Handles {
+    /// Handle for an object that is pushed to the end of the storage.
+    pub fn insert(&mut self) -> Handle {
+        let index = Some(self.handles.len());
+
+        let handle = match self.free.pop() {
+            Some(slot) => {
+                let s = &mut self.slots[slot as usize];
+                s.generation += 1;
+                s.index = index;
+                Handle {
+                    slot,
+                    generation: s.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    index,
+                });
+                Handle {
+                    slot: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        self.handles.push(handle);
+        handle
+    }
+
+    pub fn index(&self, handle: Handle) -> Option<usize> {
+        let slot = self.slots.get(handle.slot as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.index
+    }
+
+    pub fn handle(&self, index: usize) -> Handle {
+        self.handles[index]
+    }
+
+    /// Forget the handle of the object at `index`.
+    /// The storage is expected to `swap_remove` the object, so the last object takes its index.
+    pub fn swap_remove(&mut self, index: usize) {
+        let handle = self.handles.swap_remove(index);
+        self.slots[handle.slot as usize].index = None;
+        self.free.push(handle.slot);
+
+        if let Some(moved) = self.handles.get(index) {
+            self.slots[moved.slot as usize].index = Some(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_reuse_slots() {
+        let mut handles = Handles::default();
+        let a = handles.insert();
+        let b = handles.insert();
+        let c = handles.insert();
+
+        // `c` takes the place of `a`
+        handles.swap_remove(handles.index(a).unwrap());
+        assert_eq!(handles.index(a), None);
+        assert_eq!(handles.index(b), Some(1));
+        assert_eq!(handles.index(c), Some(0));
+
+        // The slot of `a` is reused, but the old handle stays invalid
+        let d = handles.insert();
+        assert_ne!(a, d);
+        assert_eq!(handles.index(a), None);
+        assert_eq!(handles.index(d), Some(2));
+        assert_eq!(handles.handle(2), d);
+    }
+}
@@ -0,0 +1,296 @@
+//! How the objects move under the gravity and the fields during a step, see [`super::Physics::set_integrator`].
+//!
+//! The contacts and the joints change the velocities in the middle of the step,
+//! so every integrator is split in two: [`Integrator::kick`] before them, and [`Integrator::finish`] after the objects have moved.
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::geo::Point;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Integrator {
+    /// The velocity changes first, then the object moves with the new velocity.
+    /// The cheapest one, its error grows with the timestep
+    #[default]
+    SemiImplicitEuler,
+    /// Half of the velocity change before moving, and the other half with the acceleration at the new position.
+    /// Exact for constant acceleration
+    VelocityVerlet,
+    /// Runge-Kutta of the fourth order, for accelerations that change a lot along the way.
+    /// The object moves with the average velocity over the step, the rest of the velocity change comes after that
+    Rk4,
+}
+
+impl Integrator {
+    /// Change the velocity before the constraints are solved, the object moves with it during the step.
+    /// Returns the velocity, and the change of the velocity that is left for after the object has moved.
+    ///
+    /// `acceleration` is the acceleration at a position, and the part of the velocity that is lost per second there.
+    pub fn kick(
+        self,
+        center: Point,
+        velocity: Vec2,
+        seconds: f32,
+        acceleration: impl Fn(Point) -> (Vec2, f32),
+    ) -> (Vec2, Vec2) {
+        match self {
+            Self::SemiImplicitEuler => {
+                let (acceleration, drag) = acceleration(center);
+                let velocity =
+                    (velocity + acceleration * seconds) * (1.0 - drag * seconds).max(0.0);
+                (velocity, Vec2::ZERO)
+            }
+            Self::VelocityVerlet => {
+                let change = derivative(acceleration(center), velocity);
+                (velocity + change * seconds / 2.0, Vec2::ZERO)
+            }
+            Self::Rk4 => {
+                let f = |center: Point, velocity: Vec2| derivative(acceleration(center), velocity);
+                let half = seconds / 2.0;
+
+                let (x1, v1) = (velocity, f(center, velocity));
+                let (x2, v2) = (
+                    velocity + v1 * half,
+                    f(center + x1 * half, velocity + v1 * half),
+                );
+                let (x3, v3) = (
+                    velocity + v2 * half,
+                    f(center + x2 * half, velocity + v2 * half),
+                );
+                let (x4, v4) = (
+                    velocity + v3 * seconds,
+                    f(center + x3 * seconds, velocity + v3 * seconds),
+                );
+
+                // Moves with the average velocity, so that the contacts and the sweep see the actual motion
+                let end = velocity + (v1 + 2.0 * v2 + 2.0 * v3 + v4) * seconds / 6.0;
+                let average = (x1 + 2.0 * x2 + 2.0 * x3 + x4) / 6.0;
+                (average, end - average)
+            }
+        }
+    }
+
+    /// Change the velocity after the object has moved to `center`.
+    pub fn finish(
+        self,
+        center: Point,
+        velocity: Vec2,
+        seconds: f32,
+        acceleration: impl Fn(Point) -> (Vec2, f32),
+    ) -> Vec2 {
+        match self {
+            Self::SemiImplicitEuler | Self::Rk4 => velocity,
+            Self::VelocityVerlet => {
+                let change = derivative(acceleration(center), velocity);
+                velocity + change * seconds / 2.0
+            }
+        }
+    }
+}
+
+/// Change of the velocity per second.
+fn derivative((acceleration, drag): (Vec2, f32), velocity: Vec2) -> Vec2 {
+    acceleration - velocity * drag
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glam::vec2;
+
+    use crate::{
+        geo::{Point, ToricGeometry},
+        init_logging,
+        phys::{
+            Physics,
+            joint::{Joint, JointEnd},
+            material::Material,
+            object::{PhysObject, SceneObject},
+        },
+    };
+
+    use super::*;
+
+    const ALL: [Integrator; 3] = [
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk4,
+    ];
+
+    fn world(integrator: Integrator) -> Physics<()> {
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        physics.set_integrator(integrator);
+        physics
+    }
+
+    fn step(physics: &mut Physics<()>, scene: &[SceneObject]) {
+        physics.advance_by(scene, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn integrator_free_fall() {
+        init_logging();
+
+        // Maximum errors of the position and of the total energy after a second.
+        // Euler is off by half of the gravity times the timestep times the time
+        let bounds = [(0.02, 0.06), (1e-4, 1e-3), (1e-4, 1e-3)];
+
+        for (integrator, (position_bound, energy_bound)) in ALL.into_iter().zip(bounds) {
+            let mut physics = world(integrator);
+            let handle = physics.add(PhysObject::new_disc((0.0, 10.0).into(), 0.25, 1.0));
+
+            // The diagnostics are measured after every step
+            step(&mut physics, &[]);
+            let energy = physics.diagnostics().total_energy();
+
+            let mut energy_error: f32 = 0.0;
+            for _ in 1..100 {
+                step(&mut physics, &[]);
+                let diagnostics = physics.diagnostics();
+                energy_error = energy_error.max((diagnostics.total_energy() - energy).abs());
+            }
+
+            let disc = physics.get(handle).unwrap();
+            let position_error = (disc.center.y - 8.5).abs();
+            assert!(position_error < position_bound, "{integrator:?}: {disc:?}");
+            assert!(
+                (disc.velocity_linear.y + 3.0).abs() < 1e-4,
+                "{integrator:?}: {disc:?}"
+            );
+            assert!(
+                energy_error < energy_bound,
+                "{integrator:?}: {energy_error}"
+            );
+
+            let momentum = physics.diagnostics().momentum;
+            assert!(momentum.abs_diff_eq(vec2(0.0, -3.0), 1e-4), "{momentum}");
+        }
+    }
+
+    #[test]
+    fn integrator_pendulum() {
+        init_logging();
+
+        // Maximum error of the total energy over a few swings, starting with 6 units of potential energy
+        let bounds = [0.05, 0.015, 0.01];
+
+        for (integrator, bound) in ALL.into_iter().zip(bounds) {
+            let mut physics = world(integrator);
+            let bob = physics.add(PhysObject::new_disc((2.0, 0.0).into(), 0.25, 1.0));
+            let end = JointEnd::Object {
+                handle: bob,
+                anchor: Vec2::ZERO,
+            };
+            physics.add_joint(Joint::distance(JointEnd::World(Point::ZERO), end, 2.0));
+
+            step(&mut physics, &[]);
+            let energy = physics.diagnostics().total_energy();
+
+            let mut energy_error: f32 = 0.0;
+            let mut lowest: f32 = 0.0;
+            for _ in 0..500 {
+                step(&mut physics, &[]);
+                let diagnostics = physics.diagnostics();
+                energy_error = energy_error.max((diagnostics.total_energy() - energy).abs());
+                lowest = lowest.min(physics.get(bob).unwrap().center.y);
+            }
+
+            assert!(energy_error < bound, "{integrator:?}: {energy_error}");
+            // It has swung through the bottom
+            assert!((lowest + 2.0).abs() < 0.01, "{integrator:?}: {lowest}");
+        }
+    }
+
+    #[test]
+    fn integrator_elastic_collisions() {
+        init_logging();
+
+        let bouncy = Material {
+            restitution: 1.0,
+            ..Default::default()
+        };
+
+        for integrator in ALL {
+            // Discs hitting each other keep the momentum and the energy
+            let mut physics = world(integrator);
+            physics.set_gravity(Vec2::ZERO);
+            let small = physics.add(
+                PhysObject::new_disc((-1.0, 0.0).into(), 0.25, 1.0)
+                    .with_velocity(vec2(2.0, 0.1))
+                    .with_material(bouncy),
+            );
+            let large = physics.add(
+                PhysObject::new_disc((1.0, 0.0).into(), 0.5, 3.0)
+                    .with_velocity(vec2(-1.0, 0.0))
+                    .with_material(bouncy),
+            );
+
+            step(&mut physics, &[]);
+            let before = physics.diagnostics();
+            for _ in 0..200 {
+                step(&mut physics, &[]);
+            }
+            let after = physics.diagnostics();
+
+            assert!(
+                before.momentum.abs_diff_eq(after.momentum, 1e-4),
+                "{integrator:?}: {before:?} {after:?}"
+            );
+            assert!(
+                (before.kinetic_energy - after.kinetic_energy).abs() < 1e-4,
+                "{integrator:?}: {before:?} {after:?}"
+            );
+            // They did hit each other
+            let velocity = |handle| physics.get(handle).unwrap().velocity_linear;
+            assert!(velocity(small).x < 0.0 && velocity(large).x > 0.0);
+
+            // A ball bouncing on the floor keeps coming back to about the same height
+            let mut physics = world(integrator);
+            let floor = [
+                SceneObject::new_segment((-5.0, 0.0).into(), (5.0, 0.0).into())
+                    .with_material(bouncy),
+            ];
+            let ball = physics
+                .add(PhysObject::new_disc((0.0, 2.0).into(), 0.25, 1.0).with_material(bouncy));
+
+            let mut highest: f32 = 0.0;
+            for i in 0..600 {
+                step(&mut physics, &floor);
+                if i > 300 {
+                    highest = highest.max(physics.get(ball).unwrap().center.y);
+                }
+            }
+            assert!((highest - 2.0).abs() < 0.2, "{integrator:?}: {highest}");
+        }
+    }
+    #[test]
+    fn integrator_rk4_contact() {
+        init_logging();
+
+        // Strong enough for the motion of RK4 within a step to matter
+        let mut physics = world(Integrator::Rk4);
+        physics.set_gravity(vec2(0.0, -5000.0));
+        let floor = [
+            SceneObject::new_segment((-5.0, 0.0).into(), (5.0, 0.0).into()).with_material(
+                Material {
+                    restitution: 0.0,
+                    ..Default::default()
+                },
+            ),
+        ];
+        let disc = physics
+            .add(PhysObject::new_disc((0.0, 1.0).into(), 0.1, 1.0).with_velocity(vec2(0.0, -30.0)));
+
+        for _ in 0..50 {
+            step(&mut physics, &floor);
+            let disc = physics.get(disc).unwrap();
+            assert!(disc.center.y > 0.0, "{disc:?}");
+        }
+
+        // Rests on the floor instead of hovering above it
+        let disc = physics.get(disc).unwrap();
+        assert!((disc.center.y - 0.1).abs() < 0.01, "{disc:?}");
+    }
+}
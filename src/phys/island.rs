@@ -0,0 +1,48 @@
+/// Groups of objects that touch each other or are joined, directly or through other objects.
+///
+/// Objects are referred to by their indices, the groups are found with union-find.
+#[derive(Clone, Debug)]
+pub struct Islands {
+    parents: Vec<usize>,
+}
+
+impl Islands {
+    /// Every object starts on its own island.
+    pub fn new(count: usize) -> Self {
+        Self {
+            parents: (0..count).collect(),
+        }
+    }
+
+    /// Index of the object that represents the island of the object.
+    pub fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            // Halve the path on the way up, so that the next search is shorter
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    /// Put the islands of both objects together.
+    pub fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a.max(b)] = a.min(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn island_join() {
+        let mut islands = Islands::new(6);
+        islands.join(0, 1);
+        islands.join(3, 4);
+        islands.join(4, 1);
+
+        let roots: Vec<_> = (0..6).map(|i| islands.find(i)).collect();
+        assert_eq!(roots, [0, 0, 2, 0, 0, 5]);
+    }
+}
@@ -0,0 +1,374 @@
+//! Constraints between objects, solved with impulses like the contacts in [`super::collision`].
+//!
+//! Based on https://box2d.org/files/ErinCatto_ModelingAndSolvingConstraints_GDC2009.pdf
+
+use glam::{Mat2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geo::{Point, ToricGeometry},
+    phys::{collision::ContactSide, handle::Handle, object::PhysObject},
+};
+
+/// What a joint is attached to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JointEnd {
+    /// A point of an object, relative to its center and rotated with it
+    Object { handle: Handle, anchor: Vec2 },
+    /// A point that never moves
+    World(Point),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JointKind {
+    /// The ends are always exactly this far apart
+    Distance { length: f32 },
+    /// The ends are pulled towards the rest length, and the damping slows down the oscillation
+    Spring {
+        rest_length: f32,
+        /// Force per unit of stretch
+        stiffness: f32,
+        /// Force per unit of the speed of stretching
+        damping: f32,
+    },
+    /// The ends are at most this far apart, but can come closer
+    Rope { max_length: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Joint {
+    pub a: JointEnd,
+    pub b: JointEnd,
+    pub kind: JointKind,
+}
+
+impl Joint {
+    pub fn distance(a: JointEnd, b: JointEnd, length: f32) -> Self {
+        Self {
+            a,
+            b,
+            kind: JointKind::Distance { length },
+        }
+    }
+
+    pub fn spring(
+        a: JointEnd,
+        b: JointEnd,
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    ) -> Self {
+        Self {
+            a,
+            b,
+            kind: JointKind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+            },
+        }
+    }
+
+    pub fn rope(a: JointEnd, b: JointEnd, max_length: f32) -> Self {
+        Self {
+            a,
+            b,
+            kind: JointKind::Rope { max_length },
+        }
+    }
+
+    /// Keep the point of the object at the point of the world, the object can still turn around it.
+    pub fn pin(handle: Handle, anchor: Vec2, world: Point) -> Self {
+        Self::distance(
+            JointEnd::Object { handle, anchor },
+            JointEnd::World(world),
+            0.0,
+        )
+    }
+
+    /// Whether the joint is attached to the object.
+    pub fn attached_to(&self, handle: Handle) -> bool {
+        [self.a, self.b]
+            .iter()
+            .any(|end| matches!(end, JointEnd::Object { handle: h, .. } if *h == handle))
+    }
+}
+
+/// Where the end of a joint is in the world, given the center and the orientation of its object.
+pub fn anchor_at(anchor: Vec2, center: Point, orientation: f32) -> Point {
+    center + Mat2::from_angle(orientation) * anchor
+}
+
+/// One end of a joint during a step, it refers to an object by its index in the storage.
+#[derive(Clone, Copy, Debug)]
+pub struct Attachment {
+    /// `None` for the ends that are attached to the world
+    pub index: Option<usize>,
+    pub point: Point,
+}
+
+impl Attachment {
+    fn side<M>(&self, objects: &[PhysObject<M>]) -> ContactSide {
+        match self.index {
+            Some(index) => ContactSide::new(&objects[index], self.point),
+            None => ContactSide::IMMOVABLE,
+        }
+    }
+
+    fn apply_impulse<M>(&self, objects: &mut [PhysObject<M>], impulse: Vec2) {
+        if let Some(index) = self.index {
+            let obj = &mut objects[index];
+            let arm = obj.center.dir(self.point);
+            obj.apply_impulse(impulse, arm);
+        }
+    }
+}
+
+/// State of a joint during the iterations of a step.
+#[derive(Clone, Copy, Debug)]
+pub struct JointSolver {
+    pub kind: JointKind,
+    pub a: Attachment,
+    pub b: Attachment,
+    /// From `a` to `b`
+    separation: Vec2,
+    /// Impulse applied along the separation so far, ropes can only pull
+    accumulated: f32,
+}
+
+impl JointSolver {
+    /// Part of the error that is corrected during each step
+    const BAUMGARTE: f32 = 0.2;
+
+    pub fn new(kind: JointKind, a: Attachment, b: Attachment, geometry: &ToricGeometry) -> Self {
+        Self {
+            kind,
+            a,
+            b,
+            separation: geometry.dir(a.point, b.point),
+            accumulated: 0.0,
+        }
+    }
+
+    /// Springs are not solved iteratively, their force is applied once per step.
+    pub fn apply_spring<M>(&self, objects: &mut [PhysObject<M>], seconds: f32) {
+        let JointKind::Spring {
+            rest_length,
+            stiffness,
+            damping,
+        } = self.kind
+        else {
+            return;
+        };
+
+        let Some(direction) = self.separation.try_normalize() else {
+            return;
+        };
+
+        let (a, b) = (self.a.side(objects), self.b.side(objects));
+        let stretch = self.separation.length() - rest_length;
+        let stretching = (b.velocity - a.velocity).dot(direction);
+        let force = -stiffness * stretch - damping * stretching;
+
+        self.apply_impulse(objects, direction * force * seconds);
+    }
+
+    /// One iteration, applies the impulse that makes the ends move as the joint wants.
+    pub fn solve<M>(&mut self, objects: &mut [PhysObject<M>], seconds: f32) {
+        let distance = self.separation.length();
+        let direction = self.separation.try_normalize();
+
+        match (self.kind, direction) {
+            (JointKind::Spring { .. }, _) => {}
+            // The ends are together or have to be, which can't be done along a single direction
+            (JointKind::Distance { length: 0.0 }, _) => {
+                for axis in [Vec2::X, Vec2::Y] {
+                    let bias = Self::BAUMGARTE * self.separation.dot(axis) / seconds;
+                    let lambda = self.lambda(objects, axis, bias);
+                    self.apply_impulse(objects, axis * lambda);
+                }
+            }
+            (JointKind::Distance { length }, Some(direction)) => {
+                let bias = Self::BAUMGARTE * (distance - length) / seconds;
+                let lambda = self.lambda(objects, direction, bias);
+                self.apply_impulse(objects, direction * lambda);
+            }
+            (JointKind::Rope { max_length }, Some(direction)) => {
+                let error = distance - max_length;
+                let bias = if error < 0.0 {
+                    // A slack rope lets the ends move apart until it is taut
+                    error / seconds
+                } else {
+                    Self::BAUMGARTE * error / seconds
+                };
+
+                // Ropes can only pull
+                let lambda = self.lambda(objects, direction, bias);
+                let previous = self.accumulated;
+                self.accumulated = (self.accumulated + lambda).min(0.0);
+                self.apply_impulse(objects, direction * (self.accumulated - previous));
+            }
+            (_, None) => {}
+        }
+    }
+
+    /// Impulse along the direction that makes the ends move apart with the speed of `-bias`.
+    fn lambda<M>(&self, objects: &[PhysObject<M>], direction: Vec2, bias: f32) -> f32 {
+        let (a, b) = (self.a.side(objects), self.b.side(objects));
+        let k = a.inverse_effective_mass(direction) + b.inverse_effective_mass(direction);
+        if k == 0.0 {
+            return 0.0;
+        }
+
+        let velocity = (b.velocity - a.velocity).dot(direction);
+        -(velocity + bias) / k
+    }
+
+    /// Apply the impulse to `b` and the opposite one to `a`.
+    fn apply_impulse<M>(&self, objects: &mut [PhysObject<M>], impulse: Vec2) {
+        self.a.apply_impulse(objects, -impulse);
+        self.b.apply_impulse(objects, impulse);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::FRAC_PI_2, time::Duration};
+
+    use glam::vec2;
+
+    use crate::{init_logging, phys::Physics};
+
+    use super::*;
+
+    fn world() -> Physics<()> {
+        Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 })
+    }
+
+    fn at(handle: Handle) -> JointEnd {
+        JointEnd::Object {
+            handle,
+            anchor: Vec2::ZERO,
+        }
+    }
+
+    #[test]
+    fn joint_anchor_at() {
+        let point = anchor_at(vec2(1.0, 0.0), (2.0, 3.0).into(), FRAC_PI_2);
+        assert!(point.dist((2.0, 4.0).into()) < 1e-6, "{point}");
+    }
+
+    #[test]
+    fn joint_pendulum() {
+        init_logging();
+
+        let mut physics = world();
+        let bob = physics.add(PhysObject::new_disc((2.0, 0.0).into(), 0.25, 1.0));
+        let pivot = JointEnd::World(Point::ZERO);
+        physics.add_joint(Joint::distance(pivot, at(bob), 2.0));
+
+        for _ in 0..300 {
+            physics.advance_by(&[], Duration::from_millis(10));
+
+            let center = physics.get(bob).unwrap().center;
+            let length = center.dist(Point::ZERO);
+            assert!((length - 2.0).abs() < 0.05, "{center}");
+        }
+
+        // Swinging, not falling
+        let center = physics.get(bob).unwrap().center;
+        assert!(center.x < 2.0 && center.y < 0.0, "{center}");
+    }
+
+    #[test]
+    fn joint_pin() {
+        init_logging();
+
+        // A lantern hanging from its corner turns until the center is below it
+        let mut physics = world();
+        let lantern = physics.add(PhysObject::new_box((0.5, -0.5).into(), 0.5, 0.5, 1.0));
+        let corner = vec2(-0.5, 0.5);
+        physics.add_joint(Joint::pin(lantern, corner, Point::ZERO));
+
+        for _ in 0..1000 {
+            physics.advance_by(&[], Duration::from_millis(10));
+
+            let obj = physics.get(lantern).unwrap();
+            let pinned = anchor_at(corner, obj.center, obj.orientation);
+            assert!(pinned.dist(Point::ZERO) < 0.05, "{pinned}");
+        }
+
+        let (start, end) = physics.joint_endpoints().next().unwrap();
+        assert!(start.dist(end) < 0.05, "{start} {end}");
+    }
+
+    #[test]
+    fn joint_rope() {
+        init_logging();
+
+        let mut physics = world();
+        let weight = physics.add(PhysObject::new_disc((0.0, -1.0).into(), 0.25, 1.0));
+        physics.add_joint(Joint::rope(JointEnd::World(Point::ZERO), at(weight), 2.0));
+
+        // Slack, so falling freely
+        for _ in 0..30 {
+            physics.advance_by(&[], Duration::from_millis(10));
+        }
+        let center = physics.get(weight).unwrap().center;
+        assert!(
+            (center.y - (-1.0 - 1.5 * 0.3 * 0.3)).abs() < 0.01,
+            "{center}"
+        );
+
+        // Taut
+        for _ in 0..300 {
+            physics.advance_by(&[], Duration::from_millis(10));
+        }
+        let center = physics.get(weight).unwrap().center;
+        assert!((center.y + 2.0).abs() < 0.05, "{center}");
+    }
+
+    #[test]
+    fn joint_spring() {
+        init_logging();
+
+        // It stretches until the spring holds the weight
+        let mut physics = world();
+        let weight = physics.add(PhysObject::new_disc((0.0, -1.0).into(), 0.25, 1.0));
+        let hook = JointEnd::World(Point::ZERO);
+        physics.add_joint(Joint::spring(hook, at(weight), 1.0, 30.0, 3.0));
+
+        for _ in 0..1000 {
+            physics.advance_by(&[], Duration::from_millis(10));
+        }
+
+        let center = physics.get(weight).unwrap().center;
+        assert!((center.y + 1.1).abs() < 0.01, "{center}");
+    }
+
+    #[test]
+    fn joint_chain_removed() {
+        init_logging();
+
+        let mut physics = world();
+        let links: Vec<_> = (1..=3)
+            .map(|i| physics.add(PhysObject::new_disc((0.0, -(i as f32)).into(), 0.2, 1.0)))
+            .collect();
+
+        physics.add_joint(Joint::distance(
+            JointEnd::World(Point::ZERO),
+            at(links[0]),
+            1.0,
+        ));
+        for pair in links.windows(2) {
+            physics.add_joint(Joint::distance(at(pair[0]), at(pair[1]), 1.0));
+        }
+        assert_eq!(physics.joint_endpoints().count(), 3);
+
+        physics.remove(links[1]);
+        assert_eq!(physics.joint_endpoints().count(), 1);
+        physics.advance_by(&[], Duration::from_millis(10));
+        assert_eq!(physics.joints().len(), 1);
+        assert!(!physics.joints()[0].attached_to(links[1]));
+    }
+}
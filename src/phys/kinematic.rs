@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geo::Point;
+
+/// Moves a kinematic object along a path with a constant speed, see [`super::Physics::add_kinematic`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PathFollower {
+    points: Vec<Point>,
+    /// Whether there is a segment from the last point back to the first one.
+    /// Without ping-pong there always is one, so that the object goes around without jumping
+    closed: bool,
+    /// Units per second
+    speed: f32,
+    /// Go back and forth between the ends instead of going around
+    ping_pong: bool,
+    /// Distance since the start of the current round, with ping-pong the round includes the way back
+    travelled: f32,
+}
+
+impl PathFollower {
+    /// Starts at the first point.
+    pub fn new(points: Vec<Point>, closed: bool, speed: f32, ping_pong: bool) -> Self {
+        assert!(!points.is_empty(), "Path has to have at least one point");
+
+        Self {
+            points,
+            closed,
+            speed,
+            ping_pong,
+            travelled: 0.0,
+        }
+    }
+
+    fn segments(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let closing = (self.closed || !self.ping_pong)
+            .then(|| (self.points[self.points.len() - 1], self.points[0]));
+        self.points.windows(2).map(|w| (w[0], w[1])).chain(closing)
+    }
+
+    fn length(&self) -> f32 {
+        self.segments().map(|(a, b)| a.dist(b)).sum()
+    }
+
+    /// Distance along the path after which the motion repeats.
+    fn period(&self) -> f32 {
+        let length = self.length();
+        if self.ping_pong { length * 2.0 } else { length }
+    }
+
+    /// Where the object is now.
+    pub fn position(&self) -> Point {
+        let length = self.length();
+        if length == 0.0 {
+            return self.points[0];
+        }
+
+        let mut along = self.travelled;
+        if self.ping_pong && along > length {
+            along = 2.0 * length - along;
+        }
+
+        for (a, b) in self.segments() {
+            let segment = a.dist(b);
+            if along <= segment {
+                return a.lerp(b, along / segment);
+            }
+            along -= segment;
+        }
+
+        *self.points.last().unwrap()
+    }
+
+    /// Move along the path, returns the new position.
+    pub fn advance(&mut self, seconds: f32) -> Point {
+        let period = self.period();
+        if period > 0.0 {
+            self.travelled = (self.travelled + self.speed * seconds).rem_euclid(period);
+        }
+
+        self.position()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_at(follower: &PathFollower, expected: (f32, f32)) {
+        let position = follower.position();
+        assert!(
+            position.dist(expected.into()) < 1e-5,
+            "{position} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn kinematic_path_follower() {
+        let points = vec![
+            Point::new(0.0, 0.0),
+            Point::new(2.0, 0.0),
+            Point::new(2.0, 2.0),
+        ];
+
+        // Back and forth
+        let mut follower = PathFollower::new(points.clone(), false, 1.0, true);
+        follower.advance(3.0);
+        assert_at(&follower, (2.0, 1.0));
+        follower.advance(1.0);
+        assert_at(&follower, (2.0, 2.0));
+        follower.advance(2.5);
+        assert_at(&follower, (1.5, 0.0));
+
+        // Around, through the closing segment
+        let mut follower = PathFollower::new(points.clone(), true, 2.0, false);
+        follower.advance(2.5);
+        let d = 1.0 / 2.0f32.sqrt();
+        assert_at(&follower, (2.0 - d, 2.0 - d));
+
+        // Open paths are closed when going around
+        let mut follower = PathFollower::new(points, false, 2.0, false);
+        follower.advance(2.5);
+        assert_at(&follower, (2.0 - d, 2.0 - d));
+    }
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Surface properties of an object.
+/// When two objects touch, their materials are combined, see [`Material::combine`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    /// Share of the approach speed that is kept after a collision, 1.0 is perfectly elastic
+    pub restitution: f32,
+    /// Tangential impulse up to this share of the normal impulse stops sliding completely
+    pub static_friction: f32,
+    /// Share of the normal impulse that is applied against sliding
+    pub dynamic_friction: f32,
+    /// Acceleration that it takes to pull a touching object off, tiles per second squared
+    pub stickiness: f32,
+}
+
+impl Default for Material {
+    /// Perfectly elastic, frictionless and not sticky.
+    fn default() -> Self {
+        Self {
+            restitution: 1.0,
+            static_friction: 0.0,
+            dynamic_friction: 0.0,
+            stickiness: 0.0,
+        }
+    }
+}
+
+impl Material {
+    /// Material of the contact between two objects.
+    /// A sticky object sticks to anything, so the stickier one wins.
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            restitution: self.restitution * other.restitution,
+            static_friction: (self.static_friction * other.static_friction).sqrt(),
+            dynamic_friction: (self.dynamic_friction * other.dynamic_friction).sqrt(),
+            stickiness: self.stickiness.max(other.stickiness),
+        }
+    }
+}
@@ -1,105 +1,1279 @@
-use std::time::Duration;
+use std::{
+    f32::consts::{PI, TAU},
+    time::Duration,
+};
 
 use glam::{Vec2, vec2};
+use log::debug;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    geo::ToricGeometry,
+    geo::{Point, ToricGeometry},
     phys::{
-        collision::CollideWith,
+        broadphase::SpatialHash,
+        collision::Collision,
+        diagnostics::Diagnostics,
+        events::{ContactEvent, ContactPair, ContactTracker},
+        field::{ForceField, field_acceleration},
+        handle::{Handle, Handles},
+        integrator::Integrator,
+        island::Islands,
+        joint::{Attachment, Joint, JointEnd, JointKind, JointSolver, anchor_at},
+        kinematic::PathFollower,
         object::{PhysObject, SceneObject},
+        solver::{ContactSolver, Manifold},
     },
 };
 
+pub mod broadphase;
 pub mod collision;
+pub mod diagnostics;
+pub mod events;
+pub mod field;
+pub mod filter;
+pub mod handle;
+pub mod integrator;
+pub mod island;
+pub mod joint;
+pub mod kinematic;
+pub mod material;
+pub mod narrowphase;
 pub mod object;
+pub mod particle;
+pub mod query;
+pub mod solver;
 
 pub struct Physics<M> {
     objects: Vec<PhysObject<M>>,
-    max_timestep: f32,
+    handles: Handles,
+    /// Every step of the simulation takes exactly this long
+    timestep: Duration,
+    /// Time that has passed but was not simulated yet, less than a timestep
+    accumulator: Duration,
     geometry: ToricGeometry,
+    /// Acceleration of every object, scaled by its gravity scale
+    gravity: Vec2,
+    broadphase: SpatialHash,
+    contacts: ContactTracker,
+    /// Kinematic objects that follow paths
+    paths: Vec<(Handle, PathFollower)>,
+    joints: Vec<Joint>,
+    fields: Vec<ForceField>,
+    /// Contacts of the last step with the impulses that resolved them, sorted by pair
+    manifolds: Vec<Manifold>,
+    integrator: Integrator,
+    /// Measured after the last step
+    diagnostics: Diagnostics,
+}
+
+/// Complete state of [`Physics`], it can be serialized when `M` can.
+///
+/// Restoring a snapshot and feeding the same sequence of [`Physics::advance_by`] calls
+/// reproduces the simulation exactly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhysicsSnapshot<M> {
+    pub objects: Vec<PhysObject<M>>,
+    pub handles: Handles,
+    pub timestep: Duration,
+    pub accumulator: Duration,
+    pub geometry: ToricGeometry,
+    pub gravity: Vec2,
+    pub contacts: ContactTracker,
+    pub paths: Vec<(Handle, PathFollower)>,
+    pub joints: Vec<Joint>,
+    pub fields: Vec<ForceField>,
+    pub manifolds: Vec<Manifold>,
+    pub integrator: Integrator,
 }
 
 impl<M> Physics<M> {
-    const G: Vec2 = vec2(0.0, -3.0);
+    pub const DEFAULT_GRAVITY: Vec2 = vec2(0.0, -3.0);
+    /// Fast objects in tight spaces bounce a lot, they are allowed to tunnel after that many bounces
+    const MAX_IMPACTS_PER_STEP: usize = 4;
+    /// When simulating takes longer than the time it simulates, catching up only makes things worse
+    const MAX_STEPS_PER_ADVANCE: u32 = 8;
+    /// More iterations make piles of objects settle faster
+    const CONTACT_ITERATIONS: usize = 8;
+    /// More iterations make chains of joints stiffer
+    const JOINT_ITERATIONS: usize = 8;
+    /// Objects that move slower than that for long enough fall asleep, tiles per second
+    const SLEEP_VELOCITY_LINEAR: f32 = 0.05;
+    /// Radians per second
+    const SLEEP_VELOCITY_ANGULAR: f32 = 0.1;
+    /// Seconds
+    const SLEEP_TIME: f32 = 0.5;
 
-    pub fn new(max_timestep: f32, geometry: ToricGeometry) -> Self {
+    pub fn new(timestep: f32, geometry: ToricGeometry) -> Self {
         Self {
             objects: Vec::new(),
-            max_timestep,
+            handles: Handles::default(),
+            timestep: Duration::from_secs_f32(timestep),
+            accumulator: Duration::ZERO,
+            broadphase: SpatialHash::new(geometry.clone()),
             geometry,
+            gravity: Self::DEFAULT_GRAVITY,
+            contacts: ContactTracker::default(),
+            paths: Vec::new(),
+            joints: Vec::new(),
+            fields: Vec::new(),
+            manifolds: Vec::new(),
+            integrator: Integrator::default(),
+            diagnostics: Diagnostics::default(),
         }
     }
 
-    pub fn advance_by(&mut self, scene: &[SceneObject], time: Duration) {
-        let mut seconds = time.as_millis() as f32 / 1000.0;
+    /// Simulate as many fixed timesteps as fit into the time that has passed,
+    /// the rest of the time is carried over to the next call.
+    ///
+    /// Returns what touched what during the simulated steps, in order.
+    pub fn advance_by(&mut self, scene: &[SceneObject], time: Duration) -> Vec<ContactEvent> {
+        self.accumulator += time;
 
-        while seconds > 0.0 {
-            let timestep = self.max_timestep.min(seconds);
+        let mut events = Vec::new();
 
-            self.for_each_distinct_pair_mut(|obj1, obj2| {
-                obj1.collide(obj2);
-            });
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            if steps == Self::MAX_STEPS_PER_ADVANCE {
+                let remainder = Duration::from_nanos(
+                    (self.accumulator.as_nanos() % self.timestep.as_nanos()) as u64,
+                );
+                debug!(
+                    "Physics can't keep up, dropping {:?} of simulation",
+                    self.accumulator - remainder
+                );
+                self.accumulator = remainder;
+                break;
+            }
+
+            self.step(scene, self.timestep.as_secs_f32());
+            self.contacts.finish_step(&mut events);
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+
+        events
+    }
 
-            self.for_each_mut(|obj| {
-                obj.flush_acc();
-                for i in 0..scene.len() {
-                    obj.collide(&scene[i]);
+    fn step(&mut self, scene: &[SceneObject], timestep: f32) {
+        for obj in &mut self.objects {
+            obj.previous_center = obj.center;
+            obj.previous_orientation = obj.orientation;
+        }
+
+        self.drive_paths(timestep);
+
+        let hulls: Vec<_> = self.fields.iter().map(ForceField::hull).collect();
+        let integrator = self.integrator;
+
+        // Velocity changes that are left for after the objects have moved, see `Integrator::kick`
+        let mut remainders = vec![Vec2::ZERO; self.objects.len()];
+        for (obj, remainder) in self.objects.iter_mut().zip(&mut remainders) {
+            if !obj.kinematic && !obj.sleeping {
+                let (velocity, rest) =
+                    integrator.kick(obj.center, obj.velocity_linear, timestep, |center| {
+                        field_acceleration(
+                            obj,
+                            center,
+                            self.gravity,
+                            &self.fields,
+                            &hulls,
+                            &self.geometry,
+                        )
+                    });
+                obj.velocity_linear = velocity;
+                *remainder = rest;
+            }
+        }
+
+        let mut manifolds = Vec::new();
+
+        // Pairs of objects that might touch according to the broadphase
+        for &(i, j) in self.broadphase.pairs(&self.objects) {
+            let (obj1, obj2) = (&self.objects[i], &self.objects[j]);
+            if !obj1.filter.allows(obj2.filter) || (obj1.kinematic && obj2.kinematic) {
+                continue;
+            }
+
+            // Nothing changes between objects that don't move
+            let (a, b) = (self.handles.handle(i), self.handles.handle(j));
+            if obj1.is_still() && obj2.is_still() {
+                self.contacts.keep_objects(a, b);
+                continue;
+            }
+
+            // The first object of the pair has the smaller handle, like in the contact events
+            let ((a, i, obj1), (b, j, obj2)) = if a < b {
+                ((a, i, obj1), (b, j, obj2))
+            } else {
+                ((b, j, obj2), (a, i, obj1))
+            };
+
+            // Across the edges of the torus, the second object touches the first one where it is the closest
+            let image = obj1.center + self.geometry.dir(obj1.center, obj2.center);
+
+            if obj1.sensor || obj2.sensor {
+                if let Some(collision) = obj1.overlap(obj2, image) {
+                    self.contacts.record_objects(a, b, collision);
                 }
-            });
+                continue;
+            }
 
-            for obj in self.objects.iter_mut() {
-                obj.accelerate(Self::G, timestep);
+            if let Some(contact) = obj1.contact_points(obj2, image) {
+                let material = obj1.material.combine(obj2.material);
+                let pair = ContactPair::Objects(a, b);
+                let offset = image.dir(obj2.center);
+                manifolds
+                    .push(Manifold::new(pair, Some(i), j, contact, material).with_offset(offset));
+            }
+        }
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            if obj.sleeping && !obj.kinematic {
+                self.contacts.keep_scene(self.handles.handle(i));
+            }
+        }
+
+        // Pairs of objects and scene objects that might touch, from the same cells
+        for &(i, scene_index) in self.broadphase.scene_pairs(scene) {
+            let (obj, with) = (&self.objects[i], &scene[scene_index]);
+            if obj.kinematic || obj.sleeping || !obj.filter.allows(with.filter) {
+                continue;
+            }
+
+            let handle = self.handles.handle(i);
+            if obj.sensor {
+                if let Some(collision) = obj.overlap_scene(with) {
+                    self.contacts.record_scene(handle, scene_index, collision);
+                }
+            } else if let Some(contact) = obj.contact_points_scene(with) {
+                let material = obj.material.combine(with.material);
+                let pair = ContactPair::Scene(handle, scene_index);
+                manifolds.push(Manifold::new(pair, None, i, contact, material));
+            }
+        }
+
+        self.solve_contacts(manifolds, timestep);
+        self.solve_joints(timestep);
+
+        for (i, obj) in self.objects.iter_mut().enumerate() {
+            if obj.sleeping {
+                continue;
+            }
+
+            if obj.kinematic {
                 obj.advance_by(timestep);
                 self.geometry.wrap(&mut obj.center);
+                continue;
             }
 
-            seconds -= timestep;
+            Self::advance_swept(obj, scene, timestep, |scene_index, collision| {
+                let handle = self.handles.handle(i);
+                self.contacts.record_scene(handle, scene_index, collision);
+            });
+            self.geometry.wrap(&mut obj.center);
+
+            let velocity = obj.velocity_linear + remainders[i];
+            let velocity = integrator.finish(obj.center, velocity, timestep, |center| {
+                field_acceleration(
+                    obj,
+                    center,
+                    self.gravity,
+                    &self.fields,
+                    &hulls,
+                    &self.geometry,
+                )
+            });
+            obj.velocity_linear = velocity;
         }
+
+        self.update_sleep(timestep);
+        self.diagnostics = self.measure();
     }
 
-    pub fn add(&mut self, obj: PhysObject<M>) {
-        self.objects.push(obj);
+    /// Energy and momentum of the objects as they are now.
+    fn measure(&self) -> Diagnostics {
+        let mut diagnostics = Diagnostics::default();
+
+        for obj in &self.objects {
+            diagnostics.add_object(obj, self.gravity);
+        }
+
+        for joint in &self.joints {
+            if let JointKind::Spring {
+                rest_length,
+                stiffness,
+                ..
+            } = joint.kind
+                && let (Some(a), Some(b)) = (self.attachment(joint.a), self.attachment(joint.b))
+            {
+                let length = self.geometry.dir(a.point, b.point).length();
+                diagnostics.add_spring(length - rest_length, stiffness);
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Put to sleep the islands of objects that have been at rest for long enough, and wake up the other ones.
+    fn update_sleep(&mut self, timestep: f32) {
+        for obj in &mut self.objects {
+            // Objects that rest on each other keep some velocity that the collisions cancel out,
+            // so the velocity is measured by how far they have actually moved during the step
+            let moved = self.geometry.dir(obj.previous_center, obj.center).length();
+            let turned = (obj.orientation - obj.previous_orientation + PI).rem_euclid(TAU) - PI;
+            let resting = moved < Self::SLEEP_VELOCITY_LINEAR * timestep
+                && turned.abs() < Self::SLEEP_VELOCITY_ANGULAR * timestep;
+            obj.rest_time = if resting && !obj.kinematic {
+                obj.rest_time + timestep
+            } else {
+                0.0
+            };
+        }
+
+        let mut islands = Islands::new(self.objects.len());
+
+        for pair in self.contacts.current_pairs() {
+            let ContactPair::Objects(a, b) = pair else {
+                continue;
+            };
+            let (Some(i), Some(j)) = (self.handles.index(a), self.handles.index(b)) else {
+                continue;
+            };
+
+            let (obj1, obj2) = (&self.objects[i], &self.objects[j]);
+            if obj1.sensor || obj2.sensor {
+                continue;
+            }
+
+            // Kinematic objects don't belong to islands, but the objects that they push can't sleep
+            match (obj1.kinematic, obj2.kinematic) {
+                (false, false) => islands.join(i, j),
+                (true, _) if !obj1.is_still() => self.objects[j].rest_time = 0.0,
+                (_, true) if !obj2.is_still() => self.objects[i].rest_time = 0.0,
+                _ => {}
+            }
+        }
+
+        for joint in &self.joints {
+            if let (JointEnd::Object { handle: a, .. }, JointEnd::Object { handle: b, .. }) =
+                (joint.a, joint.b)
+                && let (Some(i), Some(j)) = (self.handles.index(a), self.handles.index(b))
+            {
+                islands.join(i, j);
+            }
+        }
+
+        // An island can only sleep when all of its objects can
+        let mut restless = vec![false; self.objects.len()];
+        for (i, obj) in self.objects.iter().enumerate() {
+            if obj.rest_time < Self::SLEEP_TIME {
+                restless[islands.find(i)] = true;
+            }
+        }
+
+        for (i, obj) in self.objects.iter_mut().enumerate() {
+            if obj.kinematic {
+                continue;
+            }
+
+            if restless[islands.find(i)] {
+                obj.sleeping = false;
+            } else if !obj.sleeping {
+                obj.sleeping = true;
+                obj.velocity_linear = Vec2::ZERO;
+                obj.velocity_angular = 0.0;
+            }
+        }
     }
 
-    pub fn iter(&self) -> impl ExactSizeIterator<Item = &PhysObject<M>> {
-        self.objects.iter()
+    /// Give the objects that follow paths the velocities that take them to their next positions.
+    fn drive_paths(&mut self, timestep: f32) {
+        // Paths of removed objects are not needed anymore
+        self.paths
+            .retain(|(handle, _)| self.handles.index(*handle).is_some());
+
+        for (handle, path) in &mut self.paths {
+            let Some(index) = self.handles.index(*handle) else {
+                continue;
+            };
+
+            let obj = &mut self.objects[index];
+            let target = path.advance(timestep);
+            obj.velocity_linear = self.geometry.dir(obj.center, target) / timestep;
+        }
     }
 
-    fn for_each_mut(&mut self, mut f: impl FnMut(&mut PhysObject<M>)) {
-        for i in 0..self.objects.len() {
-            f(&mut self.objects[i]);
+    /// Resolve all contacts of the step together, see [`solver`].
+    fn solve_contacts(&mut self, manifolds: Vec<Manifold>, timestep: f32) {
+        let mut solver =
+            ContactSolver::new(manifolds, &self.manifolds, &mut self.objects, timestep);
+
+        for _ in 0..Self::CONTACT_ITERATIONS {
+            solver.solve_velocities(&mut self.objects);
+        }
+        solver.correct_positions(&mut self.objects, timestep);
+
+        for manifold in solver.manifolds() {
+            let collision = manifold.collision();
+            match manifold.pair {
+                ContactPair::Objects(a, b) => self.contacts.record_objects(a, b, collision),
+                ContactPair::Scene(obj, scene_index) => {
+                    self.contacts.record_scene(obj, scene_index, collision)
+                }
+            }
         }
+
+        self.manifolds = solver.finish();
     }
 
-    fn for_each_pair_mut(&mut self, mut f: impl FnMut(&mut PhysObject<M>, &mut PhysObject<M>)) {
-        for i in 0..self.objects.len() {
-            for j in 0..self.objects.len() {
-                if i == j {
-                    continue;
+    /// Change the velocities so that the objects move as the joints want them to.
+    fn solve_joints(&mut self, timestep: f32) {
+        // Joints of removed objects are not needed anymore
+        self.joints.retain(|joint| {
+            [joint.a, joint.b].iter().all(|end| match end {
+                JointEnd::Object { handle, .. } => self.handles.index(*handle).is_some(),
+                JointEnd::World(_) => true,
+            })
+        });
+
+        let mut solvers: Vec<_> = self
+            .joints
+            .iter()
+            .filter_map(|joint| {
+                let a = self.attachment(joint.a)?;
+                let b = self.attachment(joint.b)?;
+
+                // Joints of sleeping objects hold them where they are already
+                let still = |end: &Attachment| end.index.is_none_or(|i| self.objects[i].sleeping);
+                if still(&a) && still(&b) {
+                    return None;
                 }
 
-                let (left_i, right_i) = if i < j {
-                    (i, j - i - 1)
-                } else {
-                    (j, i - j - 1)
-                };
+                Some(JointSolver::new(joint.kind, a, b, &self.geometry))
+            })
+            .collect();
 
-                let (left, right) = self.objects.split_at_mut(left_i + 1);
+        for solver in &solvers {
+            solver.apply_spring(&mut self.objects, timestep);
+        }
 
-                f(&mut left[left_i], &mut right[right_i]);
+        for _ in 0..Self::JOINT_ITERATIONS {
+            for solver in &mut solvers {
+                solver.solve(&mut self.objects, timestep);
             }
         }
     }
 
-    fn for_each_distinct_pair_mut(
-        &mut self,
-        mut f: impl FnMut(&mut PhysObject<M>, &mut PhysObject<M>),
+    fn attachment(&self, end: JointEnd) -> Option<Attachment> {
+        Some(match end {
+            JointEnd::Object { handle, anchor } => {
+                let index = self.handles.index(handle)?;
+                let obj = &self.objects[index];
+                Attachment {
+                    index: Some(index),
+                    point: anchor_at(anchor, obj.center, obj.orientation),
+                }
+            }
+            JointEnd::World(point) => Attachment { index: None, point },
+        })
+    }
+
+    pub fn snapshot(&self) -> PhysicsSnapshot<M>
+    where
+        M: Clone,
+    {
+        PhysicsSnapshot {
+            objects: self.objects.clone(),
+            handles: self.handles.clone(),
+            timestep: self.timestep,
+            accumulator: self.accumulator,
+            geometry: self.geometry.clone(),
+            gravity: self.gravity,
+            contacts: self.contacts.clone(),
+            paths: self.paths.clone(),
+            joints: self.joints.clone(),
+            fields: self.fields.clone(),
+            manifolds: self.manifolds.clone(),
+            integrator: self.integrator,
+        }
+    }
+
+    /// Replace the whole state with the one from the snapshot.
+    pub fn restore(&mut self, snapshot: PhysicsSnapshot<M>) {
+        let PhysicsSnapshot {
+            objects,
+            handles,
+            timestep,
+            accumulator,
+            geometry,
+            gravity,
+            contacts,
+            paths,
+            joints,
+            fields,
+            manifolds,
+            integrator,
+        } = snapshot;
+
+        self.objects = objects;
+        self.handles = handles;
+        self.timestep = timestep;
+        self.accumulator = accumulator;
+        self.broadphase = SpatialHash::new(geometry.clone());
+        self.geometry = geometry;
+        self.gravity = gravity;
+        self.contacts = contacts;
+        self.paths = paths;
+        self.joints = joints;
+        self.fields = fields;
+        self.manifolds = manifolds;
+        self.integrator = integrator;
+        self.diagnostics = self.measure();
+    }
+
+    /// How far the time has progressed from the last step towards the next one, in `[0, 1)`.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.timestep.as_secs_f32()
+    }
+
+    /// Center and orientation of the object between the last two steps, according to [`Physics::alpha`].
+    /// Rendering these instead of the simulated ones makes the motion smooth at any frame rate.
+    pub fn interpolated(&self, obj: &PhysObject<M>) -> (Point, f32) {
+        let alpha = self.alpha();
+
+        let mut center =
+            obj.previous_center + self.geometry.dir(obj.previous_center, obj.center) * alpha;
+        self.geometry.wrap(&mut center);
+
+        let turn = (obj.orientation - obj.previous_orientation + PI).rem_euclid(TAU) - PI;
+        let orientation = (obj.previous_orientation + turn * alpha).rem_euclid(TAU);
+
+        (center, orientation)
+    }
+
+    /// Advance a fast object, bouncing off the scene objects that it would pass through otherwise.
+    /// `on_impact` gets the index of every scene object that the object bounced off.
+    fn advance_swept(
+        obj: &mut PhysObject<M>,
+        scene: &[SceneObject],
+        seconds: f32,
+        mut on_impact: impl FnMut(usize, Collision),
     ) {
-        for i in 0..self.objects.len() {
-            let (left, right) = self.objects.split_at_mut(i + 1);
-            for j in 0..right.len() {
-                f(&mut left[i], &mut right[j]);
+        let mut seconds = seconds;
+
+        for _ in 0..Self::MAX_IMPACTS_PER_STEP {
+            // Slow objects can't skip over anything, overlaps will be resolved on the next step.
+            // Sensors pass through everything anyway
+            let motion = obj.velocity_linear * seconds;
+            if obj.sensor || motion.length() <= obj.shape.inner_radius() / 2.0 {
+                break;
             }
+
+            let impact = scene
+                .iter()
+                .enumerate()
+                .filter(|(_, with)| obj.filter.allows(with.filter))
+                .filter_map(|(i, with)| Some((obj.impact_with(with, seconds)?, i)))
+                .min_by(|(i1, _), (i2, _)| i1.seconds.total_cmp(&i2.seconds));
+
+            let Some((impact, scene_index)) = impact else {
+                break;
+            };
+
+            obj.advance_by(impact.seconds);
+            let collision = obj.bounce_off(&scene[scene_index], impact.point, impact.normal);
+            on_impact(scene_index, collision);
+            seconds -= impact.seconds;
+        }
+
+        obj.advance_by(seconds);
+    }
+
+    pub fn add(&mut self, obj: PhysObject<M>) -> Handle {
+        self.objects.push(obj);
+        self.handles.insert()
+    }
+
+    /// Add an object that moves along the path, it starts at the current position on the path.
+    pub fn add_kinematic(&mut self, obj: PhysObject<M>, path: PathFollower) -> Handle {
+        let mut obj = obj.with_kinematic(true);
+        obj.center = path.position();
+        obj.previous_center = obj.center;
+
+        let handle = self.add(obj);
+        self.paths.push((handle, path));
+        handle
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    /// Energy and momentum after the last step.
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        self.gravity
+    }
+
+    /// Wakes up all objects, so that they feel the new gravity.
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+        self.wake_all();
+    }
+
+    /// Wakes up all objects, so that they feel the new field.
+    pub fn add_field(&mut self, field: ForceField) {
+        self.fields.push(field);
+        self.wake_all();
+    }
+
+    /// Objects that are changed through [`Physics::get_mut`] should be woken up, otherwise they ignore the change.
+    pub fn wake(&mut self, handle: Handle) {
+        if let Some(obj) = self.get_mut(handle) {
+            obj.wake();
         }
     }
+
+    pub fn wake_all(&mut self) {
+        for obj in &mut self.objects {
+            obj.wake();
+        }
+    }
+
+    pub fn fields(&self) -> &[ForceField] {
+        &self.fields
+    }
+
+    /// The joint stops working when one of its objects is removed.
+    /// Wakes up the objects of the joint.
+    pub fn add_joint(&mut self, joint: Joint) {
+        for end in [joint.a, joint.b] {
+            if let JointEnd::Object { handle, .. } = end {
+                self.wake(handle);
+            }
+        }
+
+        self.joints.push(joint);
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    /// Keep only the joints for which `f` returns `true`.
+    pub fn retain_joints(&mut self, f: impl FnMut(&Joint) -> bool) {
+        self.joints.retain(f);
+    }
+
+    /// Both ends of every joint, interpolated like [`Physics::interpolated`], for drawing the links.
+    pub fn joint_endpoints(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let end = |end: JointEnd| match end {
+            JointEnd::Object { handle, anchor } => {
+                let (center, orientation) = self.interpolated(self.get(handle)?);
+                Some(anchor_at(anchor, center, orientation))
+            }
+            JointEnd::World(point) => Some(point),
+        };
+
+        self.joints
+            .iter()
+            .filter_map(move |joint| Some((end(joint.a)?, end(joint.b)?)))
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&PhysObject<M>> {
+        self.objects.get(self.handles.index(handle)?)
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut PhysObject<M>> {
+        self.objects.get_mut(self.handles.index(handle)?)
+    }
+
+    /// Remove the object, the handle becomes invalid.
+    /// Handles of the other objects stay valid, but their order changes.
+    /// The objects that were touching it wake up.
+    pub fn remove(&mut self, handle: Handle) -> Option<PhysObject<M>> {
+        let index = self.handles.index(handle)?;
+        self.wake_touching(handle);
+        self.handles.swap_remove(index);
+        Some(self.objects.swap_remove(index))
+    }
+
+    /// Keep only the objects for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(Handle, &mut PhysObject<M>) -> bool) {
+        let mut i = 0;
+        while i < self.objects.len() {
+            if f(self.handles.handle(i), &mut self.objects[i]) {
+                i += 1;
+            } else {
+                self.wake_touching(self.handles.handle(i));
+                self.handles.swap_remove(i);
+                self.objects.swap_remove(i);
+            }
+        }
+    }
+
+    /// Wake up the objects that were touching the object during the last step, they might have been resting on it.
+    fn wake_touching(&mut self, handle: Handle) {
+        let touching: Vec<_> = self.contacts.touching_objects(handle).collect();
+        for other in touching {
+            self.wake(other);
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Handle, &PhysObject<M>)> {
+        self.objects
+            .iter()
+            .enumerate()
+            .map(|(i, obj)| (self.handles.handle(i), obj))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use crate::{
+        init_logging,
+        phys::{
+            events::{ContactPair, ContactPhase},
+            filter::CollisionFilter,
+            kinematic::PathFollower,
+            material::Material,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn physics_rolling() {
+        init_logging();
+
+        let material = Material {
+            restitution: 0.0,
+            static_friction: 0.5,
+            dynamic_friction: 0.4,
+            stickiness: 0.0,
+        };
+
+        let radius = 0.5;
+        let floor = [
+            SceneObject::new_segment((-100.0, 0.0).into(), (100.0, 0.0).into())
+                .with_material(material),
+        ];
+
+        let mut physics = Physics::new(
+            0.01,
+            ToricGeometry {
+                x: 1000.0,
+                y: 1000.0,
+            },
+        );
+        let handle = physics.add(
+            PhysObject::new_disc((0.0, radius).into(), radius, 1.0)
+                .with_velocity(vec2(2.0, 0.0))
+                .with_material(material),
+        );
+
+        for _ in 0..100 {
+            physics.advance_by(&floor, Duration::from_millis(10));
+        }
+
+        let disc = physics.get(handle).unwrap();
+
+        // Sliding friction slows the disc down until it rolls without slipping,
+        // a uniform disc keeps 2/3 of its speed
+        assert!(
+            (disc.velocity_linear.x - 4.0 / 3.0).abs() < 0.05,
+            "{disc:?}"
+        );
+        // Resting contacts are allowed to overlap a bit, so it rolls on what is above the floor
+        let rolling_radius = disc.center.y;
+        assert!(rolling_radius <= radius && rolling_radius > radius - 0.01);
+        assert!(
+            (disc.velocity_angular * rolling_radius + disc.velocity_linear.x).abs() < 1e-3,
+            "{disc:?}"
+        );
+
+        // Rolling to the right is clockwise
+        assert!(disc.velocity_angular < 0.0);
+        assert!(disc.orientation > PI);
+    }
+
+    #[test]
+    fn physics_fast_disc_thin_wall() {
+        init_logging();
+
+        let wall = [SceneObject::new_segment(
+            (1.0, -1.0).into(),
+            (1.0, 1.0).into(),
+        )];
+
+        let mut physics = Physics::new(0.016, ToricGeometry { x: 100.0, y: 100.0 });
+        // Moves by 1.6 per step, further than the wall and the diameter of the disc
+        let handle = physics.add(
+            PhysObject::new_disc((0.0, 0.0).into(), 0.25, 1.0).with_velocity(vec2(100.0, 0.0)),
+        );
+
+        // One step and a bit
+        physics.advance_by(&wall, Duration::from_millis(20));
+
+        let disc = physics.get(handle).unwrap();
+        assert!(disc.center.x <= 0.75, "{disc:?}");
+        assert!(disc.velocity_linear.x < 0.0, "{disc:?}");
+    }
+
+    #[test]
+    fn physics_fixed_timestep() {
+        init_logging();
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let handle = physics.add(PhysObject::new_disc((0.0, 0.0).into(), 0.25, 1.0));
+
+        let steps_done = |physics: &Physics<()>| {
+            let disc = physics.get(handle).unwrap();
+            (disc.velocity_linear.y / (physics.gravity().y * 0.01)).round()
+        };
+
+        // Short frames add up
+        for _ in 0..25 {
+            physics.advance_by(&[], Duration::from_micros(1000));
+        }
+        assert_eq!(steps_done(&physics), 2.0);
+        assert!((physics.alpha() - 0.5).abs() < 1e-3);
+
+        // Rendered halfway between the last two steps
+        let disc = physics.get(handle).unwrap();
+        let (center, _) = physics.interpolated(disc);
+        let expected = disc.previous_center.lerp(disc.center, physics.alpha());
+        assert!(center.dist(expected) < 1e-6);
+
+        // A long frame doesn't make the simulation catch up all the way
+        physics.advance_by(&[], Duration::from_secs(1));
+        assert_eq!(
+            steps_done(&physics),
+            2.0 + Physics::<()>::MAX_STEPS_PER_ADVANCE as f32
+        );
+        assert!(physics.alpha() < 1.0);
+    }
+
+    #[test]
+    fn physics_snapshot_deterministic() {
+        init_logging();
+
+        let material = Material {
+            restitution: 0.8,
+            static_friction: 0.4,
+            dynamic_friction: 0.3,
+            stickiness: 0.0,
+        };
+
+        // A box that the discs keep bouncing around in
+        let corners = [(-4.0, -3.0), (4.0, -3.0), (4.0, 3.0), (-4.0, 3.0)];
+        let scene: Vec<_> = (0..corners.len())
+            .map(|i| {
+                let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+                SceneObject::new_segment(a.into(), b.into()).with_material(material)
+            })
+            .collect();
+
+        let mut physics = Physics::new(0.016, ToricGeometry { x: 10.0, y: 8.0 });
+        for i in 0..20 {
+            let t = i as f32;
+            physics.add(
+                PhysObject::new_disc((t * 0.35 - 3.5, (t * 1.3).sin() * 2.0).into(), 0.25, 1.0)
+                    .with_velocity(vec2((t * 2.1).cos() * 6.0, (t * 0.7).sin() * 6.0))
+                    .with_material(material)
+                    .with_meta(i),
+            );
+        }
+
+        // Uneven frame times, like in the game
+        let frames: Vec<_> = (0..300)
+            .map(|i| Duration::from_micros(7_000 + (i * 7919) % 20_000))
+            .collect();
+
+        physics.advance_by(&scene, Duration::from_millis(500));
+        let snapshot = toml::to_string(&physics.snapshot()).unwrap();
+
+        let run = |snapshot: &str| {
+            let mut physics = Physics::<i32>::new(1.0, ToricGeometry { x: 1.0, y: 1.0 });
+            physics.restore(toml::from_str(snapshot).unwrap());
+
+            for &frame in &frames {
+                physics.advance_by(&scene, frame);
+            }
+
+            toml::to_string(&physics.snapshot()).unwrap()
+        };
+
+        let first = run(&snapshot);
+        let second = run(&snapshot);
+
+        assert_ne!(first, snapshot);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn physics_handles() {
+        init_logging();
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                physics.add(PhysObject::new_disc((i as f32, 0.0).into(), 0.25, 1.0).with_meta(i))
+            })
+            .collect();
+
+        // Other handles survive removals
+        let removed = physics.remove(handles[1]).unwrap();
+        assert_eq!(removed.meta, 1);
+        assert!(physics.remove(handles[1]).is_none());
+        assert!(physics.get(handles[1]).is_none());
+
+        physics.retain(|_, obj| obj.meta != 3);
+        assert!(physics.get(handles[3]).is_none());
+
+        for i in [0, 2, 4] {
+            assert_eq!(physics.get(handles[i]).unwrap().meta, i);
+        }
+
+        // Iteration yields the same handles
+        for (handle, obj) in physics.iter() {
+            assert_eq!(handle, handles[obj.meta]);
+        }
+
+        // Reused slots don't bring old handles back to life
+        let added = physics.add(PhysObject::new_disc((0.0, 0.0).into(), 0.25, 1.0).with_meta(5));
+        assert!(!handles.contains(&added));
+        assert!(physics.get(handles[1]).is_none() && physics.get(handles[3]).is_none());
+
+        physics.get_mut(handles[2]).unwrap().meta = 6;
+        assert_eq!(physics.iter().len(), 4);
+
+        // Handles stay valid after restoring a snapshot
+        let snapshot = physics.snapshot();
+        let mut restored = Physics::new(0.01, ToricGeometry { x: 1.0, y: 1.0 });
+        restored.restore(snapshot);
+        assert_eq!(restored.get(handles[2]).unwrap().meta, 6);
+        assert_eq!(restored.get(added).unwrap().meta, 5);
+    }
+
+    #[test]
+    fn physics_contact_events() {
+        init_logging();
+
+        let material = Material {
+            restitution: 0.0,
+            ..Default::default()
+        };
+        let floor = [SceneObject::new_segment_h((-10.0, 0.0).into(), 20.0).with_material(material)];
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let mut add = |center: (f32, f32), velocity: Vec2, material: Material| {
+            physics.add(
+                PhysObject::new_disc(center.into(), 0.5, 1.0)
+                    .with_velocity(velocity)
+                    .with_material(material)
+                    .with_meta(0),
+            )
+        };
+        let resting = add((-5.0, 0.5), Vec2::ZERO, material);
+        let left = add((0.0, 5.0), vec2(10.0, 0.0), Material::default());
+        let right = add((0.9, 5.0), vec2(-10.0, 0.0), Material::default());
+
+        let events = physics.advance_by(&floor, Duration::from_millis(10));
+        let phases = |events: &[ContactEvent], pair| {
+            events
+                .iter()
+                .filter(|e| e.pair == pair)
+                .map(|e| e.phase)
+                .collect::<Vec<_>>()
+        };
+
+        // The discs fly into each other, the normal goes from the first handle to the second one
+        let discs = ContactPair::Objects(left, right);
+        assert_eq!(phases(&events, discs), vec![ContactPhase::Begin]);
+        let hit = events.iter().find(|e| e.pair == discs).unwrap();
+        assert!(hit.normal.abs_diff_eq(vec2(1.0, 0.0), 1e-5), "{hit:?}");
+        assert!((hit.impulse - 20.0).abs() < 1e-3, "{hit:?}");
+        assert!((hit.point.x - 0.45).abs() < 1e-5, "{hit:?}");
+
+        // The resting disc touches the floor all the time
+        let ground = ContactPair::Scene(resting, 0);
+        assert_eq!(phases(&events, ground), vec![ContactPhase::Begin]);
+
+        // Snapshots remember what is touching
+        let snapshot = toml::to_string(&physics.snapshot()).unwrap();
+        physics.restore(toml::from_str(&snapshot).unwrap());
+
+        let events = physics.advance_by(&floor, Duration::from_millis(10));
+        assert_eq!(phases(&events, discs), vec![ContactPhase::End]);
+        assert_eq!(phases(&events, ground), vec![ContactPhase::Persist]);
+
+        // Still touching during the first step, as collisions are resolved before moving
+        physics.get_mut(resting).unwrap().velocity_linear = vec2(0.0, 5.0);
+        let events = physics.advance_by(&floor, Duration::from_millis(20));
+        assert_eq!(
+            phases(&events, ground),
+            vec![ContactPhase::Persist, ContactPhase::End]
+        );
+        assert_eq!(phases(&events, discs), vec![]);
+    }
+
+    #[test]
+    fn physics_collision_across_seam() {
+        init_logging();
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 10.0, y: 10.0 });
+        physics.set_gravity(Vec2::ZERO);
+
+        // Close to the opposite edges, so they touch through them
+        let left = physics.add(
+            PhysObject::new_disc((-4.8, 0.0).into(), 0.25, 1.0).with_velocity(vec2(-2.0, 0.0)),
+        );
+        let right = physics
+            .add(PhysObject::new_disc((4.8, 0.1).into(), 0.25, 1.0).with_velocity(vec2(2.0, 0.0)));
+
+        let events = physics.advance_by(&[], Duration::from_millis(50));
+        let hit = events
+            .iter()
+            .find(|e| e.pair == ContactPair::Objects(left, right))
+            .unwrap();
+        // From the left disc across the edge to the right one, and next to the left one
+        assert!(hit.normal.x < 0.0, "{hit:?}");
+        assert!(hit.point.x < -4.5, "{hit:?}");
+
+        // They bounce back instead of passing through each other
+        let (left, right) = (physics.get(left).unwrap(), physics.get(right).unwrap());
+        assert!(left.velocity_linear.x > 0.0, "{left:?}");
+        assert!(right.velocity_linear.x < 0.0, "{right:?}");
+        assert!(physics.geometry.dir(left.center, right.center).x < 0.0);
+        assert!(left.center.x < 0.0 && right.center.x > 0.0);
+    }
+
+    #[test]
+    fn physics_filters_and_sensors() {
+        init_logging();
+
+        const LIGHTS: u32 = 0b01;
+        const WALLS: u32 = 0b10;
+
+        let wall = [SceneObject::new_segment_v((0.0, -5.0).into(), 10.0)
+            .with_filter(CollisionFilter::new(WALLS, u32::MAX))];
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let mut add = |x: f32, vx: f32, filter: CollisionFilter, sensor: bool| {
+            physics.add(
+                PhysObject::new_disc((x, 20.0).into(), 0.5, 1.0)
+                    .with_velocity(vec2(vx, 0.0))
+                    .with_filter(filter)
+                    .with_sensor(sensor),
+            )
+        };
+
+        // Lights pass through each other, but not through walls
+        let light = CollisionFilter::new(LIGHTS, WALLS);
+        let light1 = add(-0.4, 1.0, light, false);
+        let light2 = add(0.4, -1.0, light, false);
+
+        // A pickup only notices what touches it
+        let pickup = add(10.0, 0.0, CollisionFilter::default(), true);
+        let visitor = add(10.8, -1.0, CollisionFilter::default(), false);
+
+        let events = physics.advance_by(&wall, Duration::from_millis(10));
+
+        assert!(physics.get(light1).unwrap().velocity_linear.x > 0.0);
+        assert!(physics.get(light2).unwrap().velocity_linear.x < 0.0);
+        assert!(
+            events
+                .iter()
+                .all(|e| e.pair != ContactPair::Objects(light1, light2))
+        );
+
+        let touch = events
+            .iter()
+            .find(|e| e.pair == ContactPair::Objects(pickup, visitor))
+            .unwrap();
+        assert_eq!(touch.phase, ContactPhase::Begin);
+        assert_eq!(touch.impulse, 0.0);
+        assert_eq!(physics.get(visitor).unwrap().velocity_linear.x, -1.0);
+        assert_eq!(physics.get(pickup).unwrap().velocity_linear.x, 0.0);
+
+        // Only the lights hit the wall
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let light = physics.add(
+            PhysObject::new_disc((-0.4, 0.0).into(), 0.5, 1.0)
+                .with_velocity(vec2(1.0, 0.0))
+                .with_filter(light),
+        );
+        let ghost = physics.add(
+            PhysObject::new_disc((0.4, 0.0).into(), 0.5, 1.0)
+                .with_velocity(vec2(-1.0, 0.0))
+                .with_filter(CollisionFilter::new(LIGHTS, 0)),
+        );
+
+        physics.advance_by(&wall, Duration::from_millis(10));
+        assert!(physics.get(light).unwrap().velocity_linear.x < 0.0);
+        assert!(physics.get(ghost).unwrap().velocity_linear.x < 0.0);
+        assert!(physics.get(ghost).unwrap().center.x < 0.4);
+    }
+
+    #[test]
+    fn physics_one_way_platform() {
+        init_logging();
+
+        // Going to the right, so it can be jumped onto from below
+        let platform = [SceneObject::new_segment_h((-2.0, 0.0).into(), 4.0).with_one_way(true)];
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let disc = physics
+            .add(PhysObject::new_disc((0.0, -1.0).into(), 0.25, 1.0).with_velocity(vec2(0.0, 5.0)));
+
+        // Flies up through the platform, falls back onto it and bounces on top
+        let mut lowest_after_apex = f32::INFINITY;
+        for _ in 0..200 {
+            physics.advance_by(&platform, Duration::from_millis(10));
+
+            let disc = physics.get(disc).unwrap();
+            if disc.velocity_linear.y < 0.0 {
+                lowest_after_apex = lowest_after_apex.min(disc.center.y);
+            }
+        }
+
+        assert!(lowest_after_apex > 0.2, "{lowest_after_apex}");
+        assert!(physics.get(disc).unwrap().center.y > 0.0);
+
+        // Coming from above at high speed doesn't tunnel through either
+        let fast = physics.add(
+            PhysObject::new_disc((1.0, 1.0).into(), 0.25, 1.0).with_velocity(vec2(0.0, -200.0)),
+        );
+        physics.advance_by(&platform, Duration::from_millis(10));
+        assert!(physics.get(fast).unwrap().center.y > 0.0);
+    }
+
+    #[test]
+    fn physics_kinematic_platform() {
+        init_logging();
+
+        let sticky = Material {
+            restitution: 0.0,
+            static_friction: 1.0,
+            dynamic_friction: 1.0,
+            stickiness: 0.0,
+        };
+
+        let ride = |path: Vec<Point>, speed: f32, seconds: u32| {
+            let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+
+            let start = path[0];
+            let platform = physics.add_kinematic(
+                PhysObject::new_box(Point::ZERO, 1.0, 0.25, 1.0).with_material(sticky),
+                PathFollower::new(path, false, speed, true),
+            );
+            assert_eq!(physics.get(platform).unwrap().center, start);
+
+            let rider = physics.add(
+                PhysObject::new_box(start + vec2(0.0, 0.45), 0.5, 0.2, 1.0).with_material(sticky),
+            );
+
+            for _ in 0..seconds * 100 {
+                physics.advance_by(&[], Duration::from_millis(10));
+
+                let platform = physics.get(platform).unwrap();
+                let rider = physics.get(rider).unwrap();
+                assert!(
+                    rider.center.y > platform.center.y + 0.4,
+                    "{platform:?} {rider:?}"
+                );
+            }
+
+            let platform = physics.get(platform).unwrap().clone();
+            let rider = physics.get(rider).unwrap().clone();
+            (platform, rider)
+        };
+
+        // Pushed up, while the platform doesn't slow down
+        let (platform, _) = ride(vec![Point::new(0.0, -3.0), Point::new(0.0, 3.0)], 2.0, 2);
+        assert!(
+            platform.center.dist((0.0, 1.0).into()) < 1e-3,
+            "{platform:?}"
+        );
+
+        // Carried along to the right by friction
+        let (platform, rider) = ride(vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)], 1.0, 2);
+        assert!(rider.center.x > 1.0, "{rider:?}");
+        assert!(
+            (rider.center.x - platform.center.x).abs() < 1.0,
+            "{rider:?}"
+        );
+    }
+
+    #[test]
+    fn physics_sleep() {
+        init_logging();
+
+        let material = Material {
+            restitution: 0.0,
+            static_friction: 0.6,
+            dynamic_friction: 0.5,
+            stickiness: 0.0,
+        };
+        let floor = [SceneObject::new_segment_h((-10.0, 0.0).into(), 20.0).with_material(material)];
+
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let mut add = |center: (f32, f32)| {
+            physics.add(PhysObject::new_box(center.into(), 0.5, 0.5, 1.0).with_material(material))
+        };
+        let bottom = add((0.0, 0.5));
+        let top = add((0.0, 1.5));
+        let alone = add((5.0, 0.5));
+
+        let advance = |physics: &mut Physics<()>, steps| {
+            let mut events = Vec::new();
+            for _ in 0..steps {
+                events.extend(physics.advance_by(&floor, Duration::from_millis(10)));
+            }
+            events
+        };
+        let sleeping = |physics: &Physics<()>, handles: &[Handle]| {
+            handles
+                .iter()
+                .map(|h| physics.get(*h).unwrap().sleeping)
+                .collect::<Vec<_>>()
+        };
+
+        // Resting piles fall asleep, and keep touching what they touched
+        advance(&mut physics, 100);
+        assert_eq!(sleeping(&physics, &[bottom, top, alone]), [true; 3]);
+        let events = advance(&mut physics, 10);
+        assert!(events.iter().all(|e| e.phase == ContactPhase::Persist));
+        assert_eq!(events.len(), 10 * 3);
+
+        // Pushing one object of the pile wakes up the whole pile
+        let obj = physics.get_mut(top).unwrap();
+        obj.apply_impulse(vec2(0.5, 0.0), Vec2::ZERO);
+        advance(&mut physics, 1);
+        assert_eq!(
+            sleeping(&physics, &[bottom, top, alone]),
+            [false, false, true]
+        );
+
+        advance(&mut physics, 200);
+        assert_eq!(sleeping(&physics, &[bottom, top, alone]), [true; 3]);
+        let resting_at = physics.get(top).unwrap().center;
+        assert!(resting_at.x > 0.0, "{resting_at}");
+
+        // Without the bottom one, the top one falls
+        physics.remove(bottom);
+        assert!(!physics.get(top).unwrap().sleeping);
+        advance(&mut physics, 100);
+        assert!(physics.get(top).unwrap().center.y < resting_at.y - 0.5);
+    }
 }
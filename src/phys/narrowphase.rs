@@ -0,0 +1,636 @@
+//! Contacts between arbitrary convex shapes.
+//!
+//! Every shape is handled as a convex hull of a few points grown by a radius:
+//! discs are single points, capsules and segments are two points, boxes and polygons have no radius.
+//! Overlapping hulls are separated along the axis of the least penetration (SAT),
+//! otherwise the contact is found from the closest points of the hulls.
+
+use glam::{Mat2, Vec2};
+
+use crate::{
+    geo::Point,
+    phys::object::{PhysObject, PhysObjectShape, SceneObject},
+};
+
+/// Points that are this close to being the deepest are treated as a single side.
+const SIDE_TOLERANCE: f32 = 1e-3;
+/// Shape casts stop when the shapes get this close
+const CAST_TOLERANCE: f32 = 1e-4;
+/// Shape casts that take longer than that are assumed to slide past the target
+const CAST_ITERATIONS: usize = 32;
+
+/// Shape in world coordinates: the convex hull of the points grown by the radius.
+#[derive(Clone, Debug)]
+pub struct RoundedHull {
+    /// Counterclockwise if there are more than two
+    pub points: Vec<Vec2>,
+    pub radius: f32,
+}
+
+/// Where two shapes touch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub point: Point,
+    /// Unit vector that points from the first shape to the second one
+    pub normal: Vec2,
+    /// How far the shapes have to move apart along the normal to stop overlapping
+    pub depth: f32,
+}
+
+/// All points where two shapes touch, with the same normal.
+/// Flat sides that rest on each other touch at both ends of the part they share.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactManifold {
+    /// Unit vector that points from the first shape to the second one
+    pub normal: Vec2,
+    /// One or two
+    pub points: Vec<ContactPoint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactPoint {
+    pub point: Point,
+    /// How far the shapes have to move apart along the normal to stop overlapping at this point
+    pub depth: f32,
+}
+
+impl RoundedHull {
+    pub fn of_object<M>(obj: &PhysObject<M>) -> Self {
+        Self::of_shape(&obj.shape, obj.center, obj.orientation)
+    }
+
+    pub fn of_shape(shape: &PhysObjectShape, center: Point, orientation: f32) -> Self {
+        let (points, radius) = shape.core();
+        let rotation = Mat2::from_angle(orientation);
+        let center = center.vec();
+
+        Self {
+            points: points.into_iter().map(|p| center + rotation * p).collect(),
+            radius,
+        }
+    }
+
+    pub fn of_scene(obj: &SceneObject) -> Self {
+        let (a, b) = obj.endpoints();
+
+        Self {
+            points: vec![a.vec(), b.vec()],
+            radius: 0.0,
+        }
+    }
+
+    /// Edges of the hull, a single point is an edge that starts and ends at it.
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let n = self.points.len();
+        let count = match n {
+            1 => 1,
+            2 => 1,
+            _ => n,
+        };
+        (0..count).map(move |i| (self.points[i], self.points[(i + 1) % n]))
+    }
+
+    /// Directions that can separate this hull from another one.
+    fn axes(&self) -> Vec<Vec2> {
+        match self.points.len() {
+            1 => Vec::new(),
+            2 => {
+                let d = (self.points[1] - self.points[0]).normalize_or_zero();
+                vec![d, d.perp()]
+            }
+            _ => self
+                .edges()
+                .map(|(a, b)| (b - a).perp().normalize_or_zero())
+                .collect(),
+        }
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        self.points
+            .iter()
+            .map(|p| p.dot(axis))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+                (min.min(d), max.max(d))
+            })
+    }
+
+    /// The point furthest along the direction.
+    /// A whole side can be the furthest, then its middle is used.
+    fn deepest(&self, direction: Vec2) -> Vec2 {
+        let side = self.side(direction);
+        side.iter().sum::<Vec2>() / side.len() as f32
+    }
+
+    /// The points that are the furthest along the direction, a single one or a whole side.
+    fn side(&self, direction: Vec2) -> Vec<Vec2> {
+        let (_, max) = self.project(direction);
+        self.points
+            .iter()
+            .copied()
+            .filter(|p| max - p.dot(direction) < SIDE_TOLERANCE)
+            .collect()
+    }
+
+    /// The edge that faces the direction the most: its ends and its outward normal.
+    /// Hulls of a single point have no edges, both sides of a segment are its edges.
+    fn face(&self, direction: Vec2) -> Option<(Vec2, Vec2, Vec2)> {
+        let centroid = self.centroid();
+        self.edges()
+            .filter_map(|(s, e)| {
+                let normal = (e - s).perp().try_normalize()?;
+                let outwards = match self.points.len() {
+                    2 => normal.dot(direction) >= 0.0,
+                    _ => normal.dot(s - centroid) >= 0.0,
+                };
+                Some((s, e, if outwards { normal } else { -normal }))
+            })
+            .max_by(|(_, _, n1), (_, _, n2)| n1.dot(direction).total_cmp(&n2.dot(direction)))
+    }
+
+    pub fn translated(&self, by: Vec2) -> Self {
+        Self {
+            points: self.points.iter().map(|p| *p + by).collect(),
+            radius: self.radius,
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let point = Self {
+            points: vec![point],
+            radius: 0.0,
+        };
+        distance(self, &point) < 0.0
+    }
+
+    fn centroid(&self) -> Vec2 {
+        self.points.iter().sum::<Vec2>() / self.points.len() as f32
+    }
+}
+
+/// Contact between two shapes, `None` if they don't touch.
+pub fn contact(a: &RoundedHull, b: &RoundedHull) -> Option<Contact> {
+    match penetration(a, b) {
+        Some(contact) => Some(contact),
+        None => closest(a, b),
+    }
+}
+
+/// Like [`contact`], but flat sides that rest on each other touch at two points.
+///
+/// The side of the hull that the separating axis belongs to is the reference,
+/// the side of the other hull that went through it is clipped to its extent.
+pub fn manifold(a: &RoundedHull, b: &RoundedHull) -> Option<ContactManifold> {
+    let single = |contact: Contact| ContactManifold {
+        normal: contact.normal,
+        points: vec![ContactPoint {
+            point: contact.point,
+            depth: contact.depth,
+        }],
+    };
+
+    let Some((overlap, normal, on_a)) = separating_axis(a, b) else {
+        return closest(a, b).map(single);
+    };
+
+    // Pointing from the reference hull to the incident one
+    let (reference, incident, outwards) = if on_a {
+        (a, b, normal)
+    } else {
+        (b, a, -normal)
+    };
+
+    let contact = penetration_contact(a, b, overlap, normal, on_a);
+    let (Some(face), Some(side)) = (reference.face(outwards), incident.face(-outwards)) else {
+        return Some(single(contact));
+    };
+
+    // The axis belongs to a corner of a capsule or a segment, not to its side
+    if face.2.dot(outwards) < 1.0 - SIDE_TOLERANCE {
+        return Some(single(contact));
+    }
+
+    let tangent = outwards.perp();
+    let (face_start, face_end) = (face.0.dot(tangent), face.1.dot(tangent));
+    let (start, end) = (side.0, side.1);
+    let (t_start, t_end) = (start.dot(tangent), end.dot(tangent));
+    if (t_end - t_start).abs() < SIDE_TOLERANCE {
+        return Some(single(contact));
+    }
+
+    // Cut off the parts of the incident side that are beyond the ends of the reference side
+    let surface = face.0.dot(outwards);
+    let mut points: Vec<_> = [t_start, t_end]
+        .into_iter()
+        .map(|t| {
+            let t = t.clamp(face_start.min(face_end), face_start.max(face_end));
+            let p = start.lerp(end, (t - t_start) / (t_end - t_start));
+            let point = p - outwards * incident.radius;
+            ContactPoint {
+                point: Point::new(point.x, point.y),
+                depth: surface - p.dot(outwards) + a.radius + b.radius,
+            }
+        })
+        .filter(|p| p.depth > 0.0)
+        .collect();
+
+    // The sides only share a corner
+    if points.len() == 2 && points[0].point.dist(points[1].point) < SIDE_TOLERANCE {
+        points.pop();
+    }
+
+    if points.is_empty() {
+        return Some(single(contact));
+    }
+
+    Some(ContactManifold { normal, points })
+}
+
+/// Contact between hulls whose points overlap, found with the separating axis test.
+fn penetration(a: &RoundedHull, b: &RoundedHull) -> Option<Contact> {
+    let (overlap, normal, on_a) = separating_axis(a, b)?;
+    Some(penetration_contact(a, b, overlap, normal, on_a))
+}
+
+/// The axis of the least overlap of the points of the hulls: the overlap, the normal from `a` to `b`,
+/// and whether the axis is a side of `a`. `None` if the points don't overlap.
+fn separating_axis(a: &RoundedHull, b: &RoundedHull) -> Option<(f32, Vec2, bool)> {
+    let axes_a = a.axes();
+    let from_a = axes_a.len();
+    let axes: Vec<_> = axes_a.into_iter().chain(b.axes()).collect();
+    if axes.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(f32, Vec2, bool)> = None;
+
+    for (i, axis) in axes.into_iter().enumerate() {
+        if axis == Vec2::ZERO {
+            continue;
+        }
+
+        let (min_a, max_a) = a.project(axis);
+        let (min_b, max_b) = b.project(axis);
+
+        // Moving `b` along the axis, or against it
+        let (overlap, normal) = if max_a - min_b < max_b - min_a {
+            (max_a - min_b, axis)
+        } else {
+            (max_b - min_a, -axis)
+        };
+
+        if overlap <= 0.0 {
+            return None;
+        }
+
+        if best.is_none_or(|(best_overlap, _, _)| overlap < best_overlap) {
+            best = Some((overlap, normal, i < from_a));
+        }
+    }
+
+    best
+}
+
+fn penetration_contact(
+    a: &RoundedHull,
+    b: &RoundedHull,
+    overlap: f32,
+    normal: Vec2,
+    on_a: bool,
+) -> Contact {
+    // When the axis is a side of `a`, the points of `b` went through it, and the other way around
+    let point = if on_a {
+        b.deepest(-normal) - normal * b.radius
+    } else {
+        a.deepest(normal) + normal * a.radius
+    };
+
+    Contact {
+        point: Point::new(point.x, point.y),
+        normal,
+        depth: overlap + a.radius + b.radius,
+    }
+}
+
+/// Contact between hulls whose points don't overlap, but might be close enough for the radii to.
+fn closest(a: &RoundedHull, b: &RoundedHull) -> Option<Contact> {
+    let (dist_sq, on_a, on_b) = closest_points(a, b)?;
+    let reach = a.radius + b.radius;
+    if dist_sq > reach * reach {
+        return None;
+    }
+
+    let dist = dist_sq.sqrt();
+    let normal = if dist > 0.0 {
+        (on_b - on_a) / dist
+    } else {
+        // The hulls only touch, any sensible direction will do
+        (b.centroid() - a.centroid()).normalize_or(Vec2::X)
+    };
+
+    let surface_a = on_a + normal * a.radius;
+    let surface_b = on_b - normal * b.radius;
+    let point = (surface_a + surface_b) / 2.0;
+
+    Some(Contact {
+        point: Point::new(point.x, point.y),
+        normal,
+        depth: reach - dist,
+    })
+}
+
+/// Squared distance between the closest points of the hulls (without the radii), and the points.
+fn closest_points(a: &RoundedHull, b: &RoundedHull) -> Option<(f32, Vec2, Vec2)> {
+    let mut best: Option<(f32, Vec2, Vec2)> = None;
+    let mut consider = |on_a: Vec2, on_b: Vec2| {
+        let dist_sq = on_a.distance_squared(on_b);
+        if best.is_none_or(|(best_dist_sq, _, _)| dist_sq < best_dist_sq) {
+            best = Some((dist_sq, on_a, on_b));
+        }
+    };
+
+    for &p in &b.points {
+        for (s, e) in a.edges() {
+            consider(closest_on_segment(p, s, e), p);
+        }
+    }
+
+    for &p in &a.points {
+        for (s, e) in b.edges() {
+            consider(p, closest_on_segment(p, s, e));
+        }
+    }
+
+    best
+}
+
+/// Distance between the surfaces of the shapes, negative when they overlap.
+pub fn distance(a: &RoundedHull, b: &RoundedHull) -> f32 {
+    if let Some(contact) = penetration(a, b) {
+        return -contact.depth;
+    }
+
+    match closest_points(a, b) {
+        Some((dist_sq, _, _)) => dist_sq.sqrt() - a.radius - b.radius,
+        None => f32::INFINITY,
+    }
+}
+
+/// Where a ray first enters the shape: the distance along the unit `direction`, and the normal of the surface.
+/// Rays that start inside the shape don't hit it.
+pub fn ray_cast(
+    hull: &RoundedHull,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+) -> Option<(f32, Vec2)> {
+    let mut best: Option<(f32, Vec2)> = None;
+    let mut consider = |t: f32, normal: Vec2| {
+        if (0.0..=max_distance).contains(&t) && best.is_none_or(|(best_t, _)| t < best_t) {
+            best = Some((t, normal));
+        }
+    };
+
+    // Rounded corners
+    if hull.radius > 0.0 {
+        for &p in &hull.points {
+            if let Some(t) = ray_circle(origin, direction, p, hull.radius) {
+                consider(t, (origin + direction * t - p) / hull.radius);
+            }
+        }
+    }
+
+    // Sides, moved out by the radius
+    for (s, e) in hull.edges() {
+        let Some(n) = (e - s).perp().try_normalize() else {
+            continue;
+        };
+
+        for side in [n, -n] {
+            let offset = side * hull.radius;
+            if let Some(t) = ray_segment(origin, direction, s + offset, e + offset) {
+                let normal = if side.dot(direction) < 0.0 {
+                    side
+                } else {
+                    -side
+                };
+                consider(t, normal);
+            }
+        }
+    }
+
+    best
+}
+
+/// Distance along the unit `direction` to where the ray enters the circle.
+fn ray_circle(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let to_origin = origin - center;
+    let b = direction.dot(to_origin);
+    let c = to_origin.length_squared() - radius * radius;
+
+    let discr = b * b - c;
+    if discr < 0.0 {
+        return None;
+    }
+
+    let t = -b - discr.sqrt();
+    (t >= 0.0).then_some(t)
+}
+
+/// Distance along the unit `direction` to where the ray crosses the segment `s`-`e`.
+fn ray_segment(origin: Vec2, direction: Vec2, s: Vec2, e: Vec2) -> Option<f32> {
+    let d = e - s;
+    let denom = direction.perp_dot(d);
+    if denom.abs() < f32::EPSILON {
+        // Parallel
+        return None;
+    }
+
+    let t = (s - origin).perp_dot(d) / denom;
+    let u = (s - origin).perp_dot(direction) / denom;
+
+    (t >= 0.0 && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Where the shape moving along the unit `direction` first touches the target:
+/// the distance it moves, and the contact with the normal from the moving shape to the target.
+/// Targets that the shape overlaps at the start are not hit.
+pub fn shape_cast(
+    moving: &RoundedHull,
+    direction: Vec2,
+    max_distance: f32,
+    target: &RoundedHull,
+) -> Option<(f32, Contact)> {
+    let mut t = 0.0;
+
+    if distance(moving, target) < 0.0 {
+        return None;
+    }
+
+    // Conservative advancement: the shapes can't touch before moving by the distance between them
+    for _ in 0..CAST_ITERATIONS {
+        let moved = moving.translated(direction * t);
+        let gap = distance(&moved, target);
+
+        if gap <= CAST_TOLERANCE {
+            let touching = RoundedHull {
+                radius: moved.radius + CAST_TOLERANCE,
+                ..moved
+            };
+            return Some((t, contact(&touching, target)?));
+        }
+
+        t += gap;
+        if t > max_distance {
+            return None;
+        }
+    }
+
+    None
+}
+
+fn closest_on_segment(p: Vec2, s: Vec2, e: Vec2) -> Vec2 {
+    let d = e - s;
+    let len_sq = d.length_squared();
+    if len_sq == 0.0 {
+        return s;
+    }
+
+    let t = ((p - s).dot(d) / len_sq).clamp(0.0, 1.0);
+    s + d * t
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use glam::vec2;
+
+    use super::*;
+
+    fn hull<M>(obj: &PhysObject<M>) -> RoundedHull {
+        RoundedHull::of_object(obj)
+    }
+
+    fn assert_contact(contact: Option<Contact>, point: (f32, f32), normal: Vec2, depth: f32) {
+        let contact = contact.expect("Shapes should touch");
+        assert!(
+            contact.point.dist(point.into()) < 1e-5
+                && contact.normal.abs_diff_eq(normal, 1e-5)
+                && (contact.depth - depth).abs() < 1e-5,
+            "{contact:?}"
+        );
+    }
+
+    #[test]
+    fn narrowphase_discs() {
+        let a = PhysObject::new_disc((0.0, 0.0).into(), 1.0, 1.0);
+        let b = PhysObject::new_disc((1.5, 0.0).into(), 1.0, 1.0);
+        let c = PhysObject::new_disc((3.0, 0.0).into(), 0.5, 1.0);
+
+        assert_contact(contact(&hull(&a), &hull(&b)), (0.75, 0.0), Vec2::X, 0.5);
+        assert_eq!(contact(&hull(&a), &hull(&c)), None);
+    }
+
+    #[test]
+    fn narrowphase_boxes() {
+        let a = PhysObject::new_box((0.0, 0.0).into(), 1.0, 1.0, 1.0);
+        let b = PhysObject::new_box((1.8, 0.5).into(), 1.0, 1.0, 1.0);
+
+        // Pushed apart along the X axis, where the overlap is the smallest
+        let contact_ab = contact(&hull(&a), &hull(&b)).unwrap();
+        assert_eq!(contact_ab.normal, Vec2::X);
+        assert!((contact_ab.depth - 0.2).abs() < 1e-5);
+
+        // Standing on a corner
+        let c = PhysObject::new_box((0.0, 1.0 + 2.0f32.sqrt() - 0.1).into(), 1.0, 1.0, 1.0)
+            .with_orientation(FRAC_PI_4);
+        assert_contact(contact(&hull(&a), &hull(&c)), (0.0, 0.9), Vec2::Y, 0.1);
+    }
+
+    #[test]
+    fn narrowphase_manifold() {
+        let s = SceneObject::new_segment((-3.0, 0.0).into(), (3.0, 0.0).into());
+        let floor = RoundedHull::of_scene(&s);
+
+        // Resting flat on the floor, at both corners
+        let a = PhysObject::new_box((0.0, 0.9).into(), 1.0, 1.0, 1.0);
+        let m = manifold(&floor, &hull(&a)).unwrap();
+        assert!(m.normal.abs_diff_eq(Vec2::Y, 1e-5), "{m:?}");
+        assert_eq!(m.points.len(), 2);
+        for x in [-1.0, 1.0] {
+            assert!(
+                m.points
+                    .iter()
+                    .any(|p| p.point.dist((x, -0.1).into()) < 1e-5 && (p.depth - 0.1).abs() < 1e-5),
+                "{m:?}"
+            );
+        }
+
+        // On top of a narrower box, only where they share the side
+        let b = PhysObject::new_box((0.5, 2.35).into(), 0.5, 0.5, 1.0);
+        let m = manifold(&hull(&a), &hull(&b)).unwrap();
+        assert!(m.normal.abs_diff_eq(Vec2::Y, 1e-5), "{m:?}");
+        assert_eq!(m.points.len(), 2);
+        for x in [0.0, 1.0] {
+            assert!(
+                m.points.iter().any(|p| p.point.dist((x, 1.85).into()) < 1e-5
+                    && (p.depth - 0.05).abs() < 1e-5),
+                "{m:?}"
+            );
+        }
+
+        // Standing on a corner, and round shapes
+        let c = PhysObject::new_box((0.0, 2.0f32.sqrt() - 0.1).into(), 1.0, 1.0, 1.0)
+            .with_orientation(FRAC_PI_4);
+        assert_eq!(manifold(&floor, &hull(&c)).unwrap().points.len(), 1);
+        let d = PhysObject::new_disc((0.0, 0.4).into(), 0.5, 1.0);
+        assert_eq!(manifold(&floor, &hull(&d)).unwrap().points.len(), 1);
+    }
+
+    #[test]
+    fn narrowphase_capsules() {
+        // Crossing each other
+        let a = PhysObject::new_capsule((0.0, 0.0).into(), 2.0, 0.25, 1.0);
+        let b = PhysObject::new_capsule((0.0, 0.5).into(), 2.0, 0.25, 1.0)
+            .with_orientation(FRAC_PI_4 * 2.0);
+        let contact_ab = contact(&hull(&a), &hull(&b)).unwrap();
+        assert!(contact_ab.depth > 0.5, "{contact_ab:?}");
+
+        // Side by side
+        let c = PhysObject::new_capsule((1.0, 0.4).into(), 2.0, 0.25, 1.0);
+        let contact_ac = contact(&hull(&a), &hull(&c)).unwrap();
+        assert!(
+            contact_ac.normal.abs_diff_eq(Vec2::Y, 1e-5),
+            "{contact_ac:?}"
+        );
+        assert!((contact_ac.depth - 0.1).abs() < 1e-5, "{contact_ac:?}");
+        assert!((contact_ac.point.y - 0.2).abs() < 1e-5, "{contact_ac:?}");
+
+        // End to end
+        let d = PhysObject::new_capsule((4.4, 0.0).into(), 2.0, 0.25, 1.0);
+        assert_contact(contact(&hull(&a), &hull(&d)), (2.2, 0.0), Vec2::X, 0.1);
+    }
+
+    #[test]
+    fn narrowphase_polygon_segment() {
+        let triangle = vec![vec2(0.0, 1.0), vec2(-1.0, -0.5), vec2(1.0, -0.5)];
+        let p = PhysObject::new_polygon((0.0, 0.4).into(), triangle, 1.0);
+        let s = SceneObject::new_segment((-2.0, 0.0).into(), (2.0, 0.0).into());
+
+        // The bottom side went below the floor, the contact is in its middle
+        assert_contact(
+            contact(&RoundedHull::of_scene(&s), &hull(&p)),
+            (0.0, -0.1),
+            Vec2::Y,
+            0.1,
+        );
+
+        // Disc against the end of the segment
+        let d = PhysObject::new_disc((2.3, 0.3).into(), 0.5, 1.0);
+        let c = contact(&RoundedHull::of_scene(&s), &hull(&d)).unwrap();
+        assert!(
+            c.normal.abs_diff_eq(vec2(1.0, 1.0).normalize(), 1e-5),
+            "{c:?}"
+        );
+    }
+}
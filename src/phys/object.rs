@@ -1,6 +1,12 @@
+use std::f32::consts::{PI, TAU};
+
 use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
-use crate::{geo::Point, phys::collision::VelocityAccumulator};
+use crate::{
+    geo::Point,
+    phys::{collision::VelocityAccumulator, filter::CollisionFilter, material::Material},
+};
 
 #[derive(Clone, Copy, Debug)]
 pub enum SceneObjectShape {
@@ -13,15 +19,44 @@ pub enum SceneObjectShape {
 pub struct SceneObject {
     pub center: Point,
     pub shape: SceneObjectShape,
+    pub material: Material,
+    pub filter: CollisionFilter,
+    /// Objects only collide with the segment from the left side of its direction (start to end),
+    /// and only while they are not moving away to that side.
+    /// A segment going to the right is a platform that can be jumped onto from below.
+    pub one_way: bool,
 }
 
 impl SceneObject {
     pub fn new_segment_h(start: Point, dx: f32) -> Self {
-        todo!()
+        Self {
+            center: start + Vec2::new(dx / 2.0, 0.0),
+            shape: SceneObjectShape::SegmentH { dx },
+            material: Material::default(),
+            filter: CollisionFilter::default(),
+            one_way: false,
+        }
     }
 
     pub fn new_segment_v(start: Point, dy: f32) -> Self {
-        todo!()
+        Self {
+            center: start + Vec2::new(0.0, dy / 2.0),
+            shape: SceneObjectShape::SegmentV { dy },
+            material: Material::default(),
+            filter: CollisionFilter::default(),
+            one_way: false,
+        }
+    }
+
+    /// Horizontal and vertical segments get their own shapes, as they are cheaper to collide with.
+    pub fn new_segment_classified(start: Point, end: Point) -> Self {
+        if start.y == end.y {
+            Self::new_segment_h(start, end.x - start.x)
+        } else if start.x == end.x {
+            Self::new_segment_v(start, end.y - start.y)
+        } else {
+            Self::new_segment(start, end)
+        }
     }
 
     pub fn new_segment(start: Point, end: Point) -> Self {
@@ -31,27 +66,238 @@ impl SceneObject {
                 dx: end.x - start.x,
                 dy: end.y - start.y,
             },
+            material: Material::default(),
+            filter: CollisionFilter::default(),
+            one_way: false,
+        }
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: CollisionFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_one_way(mut self, one_way: bool) -> Self {
+        self.one_way = one_way;
+        self
+    }
+
+    /// Unit vector to the left of the direction of the segment, the side that one-way segments block.
+    pub fn normal(&self) -> Vec2 {
+        let (a, b) = self.endpoints();
+        a.dir(b).perp().normalize_or_zero()
+    }
+
+    /// Whether a one-way segment lets the object at `center` moving with `velocity` pass.
+    pub fn lets_through(&self, center: Point, velocity: Vec2) -> bool {
+        if !self.one_way {
+            return false;
         }
+
+        let normal = self.normal();
+        self.center.dir(center).dot(normal) < 0.0 || velocity.dot(normal) > 0.0
+    }
+
+    /// Start and end of the segment.
+    pub fn endpoints(&self) -> (Point, Point) {
+        let d = match self.shape {
+            SceneObjectShape::SegmentH { dx } => Vec2::new(dx, 0.0),
+            SceneObjectShape::SegmentV { dy } => Vec2::new(0.0, dy),
+            SceneObjectShape::Segment { dx, dy } => Vec2::new(dx, dy),
+        };
+
+        (self.center - d / 2.0, self.center + d / 2.0)
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Shapes are defined around the center of mass, and they turn with the orientation of the object.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PhysObjectShape {
-    Disc { radius: f32 },
+    Disc {
+        radius: f32,
+    },
+    /// Oriented box
+    Box {
+        half_width: f32,
+        half_height: f32,
+    },
+    /// Segment along the X axis grown by the radius
+    Capsule {
+        half_length: f32,
+        radius: f32,
+    },
+    /// Convex polygon, vertices go counterclockwise around the center of mass, see [`PhysObject::new_polygon`]
+    Polygon {
+        vertices: Vec<Vec2>,
+    },
 }
 
-#[derive(Clone, Debug)]
+impl PhysObjectShape {
+    /// Moment of inertia around the center of mass for the given mass,
+    /// assuming the mass is distributed evenly.
+    pub fn inertia(&self, mass: f32) -> f32 {
+        match self {
+            PhysObjectShape::Disc { radius } => mass * radius * radius / 2.0,
+            PhysObjectShape::Box {
+                half_width,
+                half_height,
+            } => mass * (half_width * half_width + half_height * half_height) / 3.0,
+            PhysObjectShape::Capsule {
+                half_length,
+                radius,
+            } => {
+                // Split the mass between the rectangle in the middle and the two half-discs at the ends
+                let area_rect = 4.0 * half_length * radius;
+                let area_disc = PI * radius * radius;
+                let mass_rect = mass * area_rect / (area_rect + area_disc);
+                let mass_disc = mass - mass_rect;
+
+                let inertia_rect = mass_rect * (half_length * half_length + radius * radius) / 3.0;
+
+                // Distance from the flat side of a half-disc to its center of mass
+                let d = 4.0 * radius / (3.0 * PI);
+                let inertia_disc = mass_disc
+                    * (radius * radius / 2.0 + half_length * half_length + 2.0 * half_length * d);
+
+                inertia_rect + inertia_disc
+            }
+            PhysObjectShape::Polygon { vertices } => {
+                let mut numerator = 0.0;
+                let mut denominator = 0.0;
+
+                for (i, a) in vertices.iter().enumerate() {
+                    let b = vertices[(i + 1) % vertices.len()];
+                    let cross = a.perp_dot(b);
+                    numerator += cross * (a.dot(*a) + a.dot(b) + b.dot(b));
+                    denominator += cross;
+                }
+
+                mass * numerator / (6.0 * denominator)
+            }
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        match self {
+            PhysObjectShape::Disc { radius } => PI * radius * radius,
+            PhysObjectShape::Box {
+                half_width,
+                half_height,
+            } => 4.0 * half_width * half_height,
+            PhysObjectShape::Capsule {
+                half_length,
+                radius,
+            } => 4.0 * half_length * radius + PI * radius * radius,
+            PhysObjectShape::Polygon { vertices } => {
+                (0..vertices.len())
+                    .map(|i| vertices[i].perp_dot(vertices[(i + 1) % vertices.len()]))
+                    .sum::<f32>()
+                    / 2.0
+            }
+        }
+    }
+
+    /// Radius of the smallest circle around the center of mass that contains the shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            PhysObjectShape::Disc { radius } => *radius,
+            PhysObjectShape::Box {
+                half_width,
+                half_height,
+            } => half_width.hypot(*half_height),
+            PhysObjectShape::Capsule {
+                half_length,
+                radius,
+            } => half_length + radius,
+            PhysObjectShape::Polygon { vertices } => {
+                vertices.iter().map(|v| v.length()).fold(0.0, f32::max)
+            }
+        }
+    }
+
+    /// Radius of the largest circle around the center of mass that fits into the shape.
+    pub fn inner_radius(&self) -> f32 {
+        match self {
+            PhysObjectShape::Disc { radius } => *radius,
+            PhysObjectShape::Box {
+                half_width,
+                half_height,
+            } => half_width.min(*half_height),
+            PhysObjectShape::Capsule { radius, .. } => *radius,
+            PhysObjectShape::Polygon { vertices } => (0..vertices.len())
+                .map(|i| {
+                    let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+                    // Distance from the center of mass to the line of the edge
+                    a.perp_dot(b - a).abs() / a.distance(b)
+                })
+                .fold(f32::INFINITY, f32::min),
+        }
+    }
+
+    /// The shape is the convex hull of these points (in local coordinates) grown by the radius.
+    pub fn core(&self) -> (Vec<Vec2>, f32) {
+        match self {
+            PhysObjectShape::Disc { radius } => (vec![Vec2::ZERO], *radius),
+            PhysObjectShape::Box {
+                half_width: w,
+                half_height: h,
+            } => (
+                vec![
+                    Vec2::new(-w, -h),
+                    Vec2::new(*w, -h),
+                    Vec2::new(*w, *h),
+                    Vec2::new(-w, *h),
+                ],
+                0.0,
+            ),
+            PhysObjectShape::Capsule {
+                half_length,
+                radius,
+            } => (
+                vec![Vec2::new(-half_length, 0.0), Vec2::new(*half_length, 0.0)],
+                *radius,
+            ),
+            PhysObjectShape::Polygon { vertices } => (vertices.clone(), 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PhysObject<M> {
     /// Center of mass
     pub center: Point,
+    /// Radians, counterclockwise, in `[0, 2π)`
+    pub orientation: f32,
+    /// Center before the last step of the simulation
+    pub previous_center: Point,
+    /// Orientation before the last step of the simulation
+    pub previous_orientation: f32,
     pub shape: PhysObjectShape,
 
     /// Kilograms
     pub mass: f32,
     /// Meters per second
     pub velocity_linear: Vec2,
-    /// Radians per second
+    /// Radians per second, counterclockwise
     pub velocity_angular: f32,
+    pub material: Material,
+    pub filter: CollisionFilter,
+    /// Sensors report overlaps as contacts, but nothing bounces off them and they don't bounce off anything
+    pub sensor: bool,
+    /// Kinematic objects move only with the velocity they are given, as if their mass was infinite.
+    /// They push the other objects around, but ignore gravity and the scene
+    pub kinematic: bool,
+    /// How much the gravity of the world affects the object
+    pub gravity_scale: f32,
+    /// Sleeping objects are not simulated until something touches or pushes them
+    pub sleeping: bool,
+    /// Seconds since the object started moving slow enough to fall asleep
+    pub rest_time: f32,
 
     pub meta: M,
 
@@ -60,12 +306,95 @@ pub struct PhysObject<M> {
 
 impl PhysObject<()> {
     pub fn new_disc(center: Point, radius: f32, mass: f32) -> Self {
+        Self::new(center, PhysObjectShape::Disc { radius }, mass)
+    }
+
+    pub fn new_box(center: Point, half_width: f32, half_height: f32, mass: f32) -> Self {
+        Self::new(
+            center,
+            PhysObjectShape::Box {
+                half_width,
+                half_height,
+            },
+            mass,
+        )
+    }
+
+    pub fn new_capsule(center: Point, half_length: f32, radius: f32, mass: f32) -> Self {
+        Self::new(
+            center,
+            PhysObjectShape::Capsule {
+                half_length,
+                radius,
+            },
+            mass,
+        )
+    }
+
+    /// Convex polygon with the vertices relative to `center`, in either order.
+    /// The object is centered at the center of mass of the polygon, so it can be away from `center`.
+    /// Repeated vertices are dropped, as their edges would have no direction.
+    ///
+    /// Panics when there are fewer than 3 different vertices, or when the polygon is not convex.
+    pub fn new_polygon(center: Point, mut vertices: Vec<Vec2>, mass: f32) -> Self {
+        vertices.dedup_by(|a, b| a.distance(*b) < 1e-6);
+        if vertices.len() > 1 && vertices[0].distance(vertices[vertices.len() - 1]) < 1e-6 {
+            vertices.pop();
+        }
+        assert!(vertices.len() >= 3, "Polygons need at least 3 vertices");
+
+        let shape = PhysObjectShape::Polygon {
+            vertices: vertices.clone(),
+        };
+        let area = shape.area();
+        assert!(area != 0.0, "Polygons can't be flat");
+        if area < 0.0 {
+            vertices.reverse();
+        }
+
+        let n = vertices.len();
+        let convex = (0..n).all(|i| {
+            let (a, b) = (vertices[i], vertices[(i + 1) % n]);
+            vertices.iter().all(|&v| (b - a).perp_dot(v - a) >= -1e-6)
+        });
+        assert!(convex, "Polygons have to be convex");
+
+        // https://en.wikipedia.org/wiki/Centroid#Of_a_polygon
+        let centroid = (0..n)
+            .map(|i| {
+                let (a, b) = (vertices[i], vertices[(i + 1) % n]);
+                (a + b) * a.perp_dot(b)
+            })
+            .sum::<Vec2>()
+            / (6.0 * area.abs());
+        for v in &mut vertices {
+            *v -= centroid;
+        }
+
+        Self::new(
+            center + centroid,
+            PhysObjectShape::Polygon { vertices },
+            mass,
+        )
+    }
+
+    fn new(center: Point, shape: PhysObjectShape, mass: f32) -> Self {
         Self {
             center,
-            shape: PhysObjectShape::Disc { radius },
+            orientation: 0.0,
+            previous_center: center,
+            previous_orientation: 0.0,
+            shape,
             mass,
             velocity_linear: Vec2::ZERO,
             velocity_angular: 0.0,
+            material: Material::default(),
+            filter: CollisionFilter::default(),
+            sensor: false,
+            kinematic: false,
+            gravity_scale: 1.0,
+            sleeping: false,
+            rest_time: 0.0,
 
             meta: (),
 
@@ -81,6 +410,58 @@ impl<M> PhysObject<M> {
         self.velocity_acc = Default::default();
     }
 
+    /// Moment of inertia around the center of mass, kilograms times meters squared.
+    pub fn inertia(&self) -> f32 {
+        self.shape.inertia(self.mass)
+    }
+
+    /// Velocity of a point of the object, taking the spin into account.
+    /// `arm` is the vector from the center of mass to the point.
+    pub fn velocity_at(&self, arm: Vec2) -> Vec2 {
+        self.velocity_linear + self.velocity_angular * arm.perp()
+    }
+
+    /// Apply an impulse at the point `arm` away from the center of mass, it wakes the object up.
+    /// Kinematic objects are not affected.
+    pub fn apply_impulse(&mut self, impulse: Vec2, arm: Vec2) {
+        if self.kinematic {
+            return;
+        }
+
+        self.wake();
+
+        self.velocity_linear += impulse / self.mass;
+        self.velocity_angular += arm.perp_dot(impulse) / self.inertia();
+    }
+
+    /// Like [`PhysObject::apply_impulse`], but the velocity only changes on [`PhysObject::flush_acc`].
+    pub fn accumulate_impulse(&mut self, impulse: Vec2, arm: Vec2) {
+        if self.kinematic {
+            return;
+        }
+
+        self.wake();
+
+        self.velocity_acc.velocity_linear += impulse / self.mass;
+        self.velocity_acc.velocity_angular += arm.perp_dot(impulse) / self.inertia();
+    }
+
+    /// The object is simulated again, and it has to stay at rest for a while to fall asleep again.
+    pub fn wake(&mut self) {
+        if self.sleeping {
+            self.sleeping = false;
+            self.rest_time = 0.0;
+        }
+    }
+
+    /// Whether the object doesn't move by itself during the step: it sleeps or it is kinematic and stopped.
+    pub fn is_still(&self) -> bool {
+        self.sleeping
+            || (self.kinematic
+                && self.velocity_linear == Vec2::ZERO
+                && self.velocity_angular == 0.0)
+    }
+
     pub fn accelerate(&mut self, direction: Vec2, seconds: f32) {
         self.velocity_linear += direction * seconds;
     }
@@ -89,6 +470,13 @@ impl<M> PhysObject<M> {
         assert_eq!(self.velocity_acc.velocity_linear, Vec2::ZERO);
         assert_eq!(self.velocity_acc.velocity_angular, 0.0);
         self.center += self.velocity_linear * seconds;
+        self.orientation = (self.orientation + self.velocity_angular * seconds).rem_euclid(TAU);
+    }
+
+    pub fn with_orientation(mut self, orientation: f32) -> Self {
+        self.orientation = orientation.rem_euclid(TAU);
+        self.previous_orientation = self.orientation;
+        self
     }
 
     pub fn with_velocity(mut self, velocity_linear: Vec2) -> Self {
@@ -96,23 +484,68 @@ impl<M> PhysObject<M> {
         self
     }
 
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: CollisionFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_sensor(mut self, sensor: bool) -> Self {
+        self.sensor = sensor;
+        self
+    }
+
+    pub fn with_kinematic(mut self, kinematic: bool) -> Self {
+        self.kinematic = kinematic;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
     pub fn with_meta<N>(self, meta: N) -> PhysObject<N> {
         let Self {
             center,
+            orientation,
+            previous_center,
+            previous_orientation,
             shape,
             mass,
             velocity_linear,
             velocity_angular,
+            material,
+            filter,
+            sensor,
+            kinematic,
+            gravity_scale,
+            sleeping,
+            rest_time,
             velocity_acc,
             ..
         } = self;
 
         PhysObject {
             center,
+            orientation,
+            previous_center,
+            previous_orientation,
             shape,
             mass,
             velocity_linear,
             velocity_angular,
+            material,
+            filter,
+            sensor,
+            kinematic,
+            gravity_scale,
+            sleeping,
+            rest_time,
             meta,
             velocity_acc,
         }
@@ -123,22 +556,117 @@ impl<M> PhysObject<M> {
 
         let Self {
             center,
+            orientation,
+            previous_center,
+            previous_orientation,
             shape,
             mass,
             velocity_linear,
             velocity_angular,
+            material,
+            filter,
+            sensor,
+            kinematic,
+            gravity_scale,
+            sleeping,
+            rest_time,
             velocity_acc,
             ..
         } = self;
 
         PhysObject {
             center,
+            orientation,
+            previous_center,
+            previous_orientation,
             shape,
             mass,
             velocity_linear,
             velocity_angular,
+            material,
+            filter,
+            sensor,
+            kinematic,
+            gravity_scale,
+            sleeping,
+            rest_time,
             meta,
             velocity_acc,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn object_polygon_normalized() {
+        // Clockwise, with the corner at the origin
+        let square = vec![
+            vec2(0.0, 0.0),
+            vec2(0.0, 2.0),
+            vec2(2.0, 2.0),
+            vec2(2.0, 0.0),
+        ];
+        let p = PhysObject::new_polygon((1.0, 1.0).into(), square, 4.0);
+
+        // Same as the box around the same center of mass
+        let b = PhysObject::new_box((2.0, 2.0).into(), 1.0, 1.0, 4.0);
+        assert_eq!(p.center, b.center);
+        assert!((p.shape.area() - b.shape.area()).abs() < 1e-5);
+        assert!((p.inertia() - b.inertia()).abs() < 1e-5);
+
+        let PhysObjectShape::Polygon { vertices } = &p.shape else {
+            unreachable!();
+        };
+        assert_eq!(vertices[0], vec2(1.0, -1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 3 vertices")]
+    fn object_polygon_too_few_vertices() {
+        PhysObject::new_polygon(Point::ZERO, vec![vec2(0.0, 0.0), vec2(1.0, 0.0)], 1.0);
+    }
+
+    #[test]
+    fn object_polygon_repeated_vertices() {
+        // Closed like the polygons in Tiled, with a doubled corner
+        let triangle = vec![
+            vec2(0.0, 0.0),
+            vec2(3.0, 0.0),
+            vec2(3.0, 0.0),
+            vec2(0.0, 3.0),
+            vec2(0.0, 0.0),
+        ];
+        let p = PhysObject::new_polygon(Point::ZERO, triangle, 1.0);
+
+        let PhysObjectShape::Polygon { vertices } = &p.shape else {
+            unreachable!();
+        };
+        assert_eq!(vertices.len(), 3);
+        assert!(p.shape.inner_radius().is_finite());
+        assert!((p.shape.inner_radius() - 2.0_f32.sqrt() / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 3 vertices")]
+    fn object_polygon_one_repeated_vertex() {
+        let v = vec2(1.0, 1.0);
+        PhysObject::new_polygon(Point::ZERO, vec![v, v, v], 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "convex")]
+    fn object_polygon_not_convex() {
+        let arrow = vec![
+            vec2(0.0, 0.0),
+            vec2(2.0, -1.0),
+            vec2(1.0, 0.0),
+            vec2(2.0, 1.0),
+        ];
+        PhysObject::new_polygon(Point::ZERO, arrow, 1.0);
+    }
+}
@@ -0,0 +1,510 @@
+//! Cheap particles, like sparks and embers, that are simulated apart from the rigid objects of [`super::Physics`].
+//!
+//! Particles are points that move with Verlet integration: the velocity is never stored,
+//! it is the difference between the current and the previous positions.
+//! They bounce off walls, but don't touch the objects or each other.
+
+use std::{collections::HashMap, time::Duration};
+
+use glam::{Vec2, Vec4};
+use log::debug;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::geo::{Point, Segment, ToricGeometry};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: Point,
+    /// Position one step ago
+    pub previous: Point,
+    /// Seconds
+    pub age: f32,
+    /// Seconds
+    pub lifetime: f32,
+    /// Index of the style in [`Particles`]
+    pub style: usize,
+}
+
+impl Particle {
+    /// Part of the lifetime that has passed, in `[0, 1)`.
+    pub fn life(&self) -> f32 {
+        self.age / self.lifetime
+    }
+}
+
+/// How the particles of a kind look and move.
+#[derive(Clone, Debug)]
+pub struct ParticleStyle {
+    /// Seconds, every particle lives a random time in between
+    pub lifetime: (f32, f32),
+    /// Tiles per second, every particle gets a random speed in between
+    pub speed: (f32, f32),
+    /// Radians, particles fly off within this angle to either side of the direction of the emission
+    pub spread: f32,
+    /// Tiles
+    pub size: f32,
+    /// Colors at points of the life, from 0 at the birth to 1 at the death, sorted by the point.
+    /// Interpolated in between
+    pub colors: Vec<(f32, Vec4)>,
+    pub gravity_scale: f32,
+    /// Part of the velocity that is lost per second
+    pub drag: f32,
+    /// Share of the velocity towards a wall that the particle bounces back with
+    pub restitution: f32,
+    /// Share of the velocity along a wall that the particle loses on a bounce
+    pub friction: f32,
+}
+
+impl ParticleStyle {
+    /// Color at a point of the life.
+    pub fn color(&self, life: f32) -> Vec4 {
+        let Some(i) = self.colors.iter().position(|(at, _)| *at > life) else {
+            return self.colors.last().map_or(Vec4::ONE, |(_, color)| *color);
+        };
+        if i == 0 {
+            return self.colors[0].1;
+        }
+
+        let (from, color_from) = self.colors[i - 1];
+        let (to, color_to) = self.colors[i];
+        color_from.lerp(color_to, (life - from) / (to - from))
+    }
+}
+
+/// Emits particles of a style at a steady rate, see [`Particles::emit`].
+#[derive(Clone, Debug)]
+pub struct ParticleEmitter {
+    pub style: usize,
+    /// Particles per second
+    pub rate: f32,
+    /// Part of a particle that is due but not emitted yet
+    pending: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(style: usize, rate: f32) -> Self {
+        Self {
+            style,
+            rate,
+            pending: 0.0,
+        }
+    }
+}
+
+/// Walls in a uniform grid of cells about one tile wide,
+/// so that a particle only checks the walls that are near it.
+/// Cells wrap around the edges of the torus, like the ones of [`super::broadphase::SpatialHash`].
+struct WallGrid {
+    walls: Vec<Segment>,
+    geometry: ToricGeometry,
+    /// Number of cells along each axis, they cover the torus exactly
+    cells_x: i32,
+    cells_y: i32,
+    /// Indices of the walls whose bounding boxes overlap the cell
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl WallGrid {
+    fn new(geometry: ToricGeometry, walls: &[Segment]) -> Self {
+        let mut grid = Self {
+            walls: walls.to_vec(),
+            cells_x: (geometry.x.floor() as i32).max(1),
+            cells_y: (geometry.y.floor() as i32).max(1),
+            geometry,
+            cells: HashMap::new(),
+        };
+
+        for (i, wall) in walls.iter().enumerate() {
+            let (a, b) = wall.ab();
+            let cells: Vec<_> = grid.cells(a, b).collect();
+            for cell in cells {
+                grid.cells.entry(cell).or_default().push(i);
+            }
+        }
+
+        grid
+    }
+
+    /// Cells that the bounding box of the two points overlaps, each one once.
+    fn cells(&self, a: Point, b: Point) -> impl Iterator<Item = (i32, i32)> {
+        let cell = |v: f32, size: f32, count: i32| {
+            ((v + size / 2.0) / (size / count as f32)).floor() as i32
+        };
+        let (gx, gy, cx, cy) = (self.geometry.x, self.geometry.y, self.cells_x, self.cells_y);
+
+        let (x0, x1) = (cell(a.x.min(b.x), gx, cx), cell(a.x.max(b.x), gx, cx));
+        let (y0, y1) = (cell(a.y.min(b.y), gy, cy), cell(a.y.max(b.y), gy, cy));
+        (x0..=x1.min(x0 + cx - 1)).flat_map(move |x| {
+            (y0..=y1.min(y0 + cy - 1)).map(move |y| (x.rem_euclid(cx), y.rem_euclid(cy)))
+        })
+    }
+
+    /// The first wall that the motion from `from` to `to` crosses:
+    /// where it crosses it, and the normal of the wall that faces `from`.
+    fn first_hit(&self, from: Point, to: Point) -> Option<(Point, Vec2)> {
+        let motion = from.dir(to);
+        let mut first: Option<(f32, Vec2)> = None;
+
+        for cell in self.cells(from, to) {
+            let Some(walls) = self.cells.get(&cell) else {
+                continue;
+            };
+
+            for &i in walls {
+                // The image of the wall that is the closest to the particle, across the edges of the torus
+                let (a, b) = self.walls[i].ab();
+                let shift = self.geometry.dir(from, a) - from.dir(a);
+                let (a, b) = (a + shift, b + shift);
+                let along = a.dir(b);
+                let denominator = motion.perp_dot(along);
+                if denominator.abs() < f32::EPSILON {
+                    continue;
+                }
+
+                // Parameters of the crossing along the motion and along the wall
+                let t = from.dir(a).perp_dot(along) / denominator;
+                let u = from.dir(a).perp_dot(motion) / denominator;
+                if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) {
+                    continue;
+                }
+
+                if first.is_none_or(|(first, _)| t < first) {
+                    let normal = along.perp().normalize();
+                    let normal = if normal.dot(motion) > 0.0 {
+                        -normal
+                    } else {
+                        normal
+                    };
+                    first = Some((t, normal));
+                }
+            }
+        }
+
+        first.map(|(t, normal)| (from + motion * t, normal))
+    }
+}
+
+/// All particles of the world and the walls that they bounce off.
+pub struct Particles {
+    particles: Vec<Particle>,
+    styles: Vec<ParticleStyle>,
+    walls: WallGrid,
+    gravity: Vec2,
+    timestep: Duration,
+    /// Time that has passed but has not been simulated yet
+    accumulator: Duration,
+    rng: StdRng,
+}
+
+impl Particles {
+    /// New particles are not emitted while there are that many
+    const MAX_PARTICLES: usize = 4096;
+    /// Simulating more steps than that at once, the simulation falls behind instead
+    const MAX_STEPS_PER_ADVANCE: u32 = 8;
+    /// Distance from a wall where a particle that hit it ends up, tiles
+    const SKIN: f32 = 0.001;
+
+    pub fn new(timestep: f32, geometry: ToricGeometry, walls: &[Segment]) -> Self {
+        Self {
+            particles: Vec::new(),
+            styles: Vec::new(),
+            walls: WallGrid::new(geometry, walls),
+            gravity: Vec2::ZERO,
+            timestep: Duration::from_secs_f32(timestep),
+            accumulator: Duration::ZERO,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+    }
+
+    /// Returns the index of the style, to emit particles of it.
+    pub fn add_style(&mut self, style: ParticleStyle) -> usize {
+        self.styles.push(style);
+        self.styles.len() - 1
+    }
+
+    pub fn style(&self, style: usize) -> &ParticleStyle {
+        &self.styles[style]
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Particle> {
+        self.particles.iter()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Position of the particle between the last two steps, see [`super::Physics::interpolated`].
+    pub fn interpolated(&self, particle: &Particle) -> Point {
+        let alpha = self.accumulator.as_secs_f32() / self.timestep.as_secs_f32();
+        particle.previous.lerp(particle.position, alpha)
+    }
+
+    /// Velocity of the particle during the last step, tiles per second.
+    #[cfg(test)]
+    pub fn velocity(&self, particle: &Particle) -> Vec2 {
+        particle.previous.dir(particle.position) / self.timestep.as_secs_f32()
+    }
+
+    /// Emit `count` particles at once.
+    /// They fly off around `direction`, on top of the `velocity` of whatever emits them.
+    pub fn burst(
+        &mut self,
+        style: usize,
+        count: usize,
+        position: Point,
+        direction: Vec2,
+        velocity: Vec2,
+    ) {
+        for _ in 0..count {
+            if self.particles.len() >= Self::MAX_PARTICLES {
+                break;
+            }
+
+            let s = &self.styles[style];
+            let angle = direction.to_angle() + self.rng.random_range(-1.0..=1.0) * s.spread;
+            let speed = self.rng.random_range(s.speed.0..=s.speed.1);
+            let lifetime = self.rng.random_range(s.lifetime.0..=s.lifetime.1);
+
+            let step = (velocity + Vec2::from_angle(angle) * speed) * self.timestep.as_secs_f32();
+            self.particles.push(Particle {
+                position,
+                previous: position - step,
+                age: 0.0,
+                lifetime,
+                style,
+            });
+        }
+    }
+
+    /// Emit the particles that the emitter is due to emit over `seconds`.
+    pub fn emit(
+        &mut self,
+        emitter: &mut ParticleEmitter,
+        seconds: f32,
+        position: Point,
+        direction: Vec2,
+        velocity: Vec2,
+    ) {
+        emitter.pending += emitter.rate * seconds;
+        let count = emitter.pending.floor();
+        emitter.pending -= count;
+
+        self.burst(emitter.style, count as usize, position, direction, velocity);
+    }
+
+    /// Simulate as many fixed timesteps as fit into the time that has passed,
+    /// the rest of the time is carried over to the next call.
+    pub fn advance_by(&mut self, time: Duration) {
+        self.accumulator += time;
+
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            if steps == Self::MAX_STEPS_PER_ADVANCE {
+                let remainder = Duration::from_nanos(
+                    (self.accumulator.as_nanos() % self.timestep.as_nanos()) as u64,
+                );
+                debug!(
+                    "Particles can't keep up, dropping {:?} of simulation",
+                    self.accumulator - remainder
+                );
+                self.accumulator = remainder;
+                break;
+            }
+
+            self.step(self.timestep.as_secs_f32());
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+    }
+
+    fn step(&mut self, seconds: f32) {
+        for particle in &mut self.particles {
+            particle.age += seconds;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        for particle in &mut self.particles {
+            let style = &self.styles[particle.style];
+
+            let inertia = particle.previous.dir(particle.position);
+            let motion = inertia * (1.0 - style.drag * seconds).max(0.0)
+                + self.gravity * style.gravity_scale * seconds * seconds;
+
+            let from = particle.position;
+            let to = from + motion;
+
+            match self.walls.first_hit(from, to) {
+                Some((hit, normal)) => {
+                    // Bounces off with what is left of the velocity, but not any further during this step
+                    let towards = normal * motion.dot(normal);
+                    let along = motion - towards;
+                    let bounced = along * (1.0 - style.friction) - towards * style.restitution;
+
+                    particle.position = hit + normal * Self::SKIN;
+                    particle.previous = particle.position - bounced;
+                }
+                None => {
+                    particle.previous = from;
+                    particle.position = to;
+                }
+            }
+
+            // Both positions move together, so that the velocity stays the same
+            let mut wrapped = particle.position;
+            self.walls.geometry.wrap(&mut wrapped);
+            let shift = particle.position.dir(wrapped);
+            particle.position += shift;
+            particle.previous += shift;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use crate::init_logging;
+
+    use super::*;
+
+    fn style() -> ParticleStyle {
+        ParticleStyle {
+            lifetime: (10.0, 10.0),
+            speed: (0.0, 0.0),
+            spread: 0.0,
+            size: 0.1,
+            colors: vec![(0.0, Vec4::ONE), (1.0, Vec4::ZERO)],
+            gravity_scale: 1.0,
+            drag: 0.0,
+            restitution: 0.5,
+            friction: 0.0,
+        }
+    }
+
+    fn advance(particles: &mut Particles, steps: u32) {
+        for _ in 0..steps {
+            particles.advance_by(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn particle_free_fall() {
+        init_logging();
+
+        let mut particles = Particles::new(0.01, ToricGeometry { x: 100.0, y: 100.0 }, &[]);
+        particles.set_gravity(vec2(0.0, -3.0));
+        let style = particles.add_style(style());
+        particles.burst(style, 1, Point::ZERO, Vec2::X, vec2(1.0, 0.0));
+
+        advance(&mut particles, 100);
+
+        // Verlet is exact for constant acceleration, up to the half step that the first step is off by
+        let particle = *particles.iter().next().unwrap();
+        assert!((particle.position.x - 1.0).abs() < 1e-3, "{particle:?}");
+        assert!((particle.position.y + 1.5).abs() < 0.02, "{particle:?}");
+
+        let velocity = particles.velocity(&particle);
+        assert!(velocity.abs_diff_eq(vec2(1.0, -3.0), 1e-2), "{velocity}");
+    }
+
+    #[test]
+    fn particle_bounces_off_walls() {
+        init_logging();
+
+        let floor = [Segment::new((-5.0, 0.0), (5.0, 0.0)).unwrap()];
+        let mut particles = Particles::new(0.01, ToricGeometry { x: 100.0, y: 100.0 }, &floor);
+        particles.set_gravity(vec2(0.0, -10.0));
+        let style = particles.add_style(style());
+        particles.burst(style, 1, Point::new(0.0, 1.0), Vec2::X, Vec2::ZERO);
+
+        let mut lowest: f32 = 1.0;
+        let mut rising = false;
+        for _ in 0..100 {
+            advance(&mut particles, 1);
+            let particle = particles.iter().next().unwrap();
+            lowest = lowest.min(particle.position.y);
+            rising |= particles.velocity(particle).y > 1.0;
+        }
+
+        // Never passes through the floor, and comes back up from it
+        assert!(lowest > 0.0, "{lowest}");
+        assert!(rising);
+
+        // Fast ones don't pass through either
+        particles.burst(style, 1, Point::new(0.0, 1.0), -Vec2::Y, vec2(0.0, -500.0));
+        advance(&mut particles, 1);
+        assert!(particles.iter().all(|p| p.position.y > 0.0));
+    }
+
+    #[test]
+    fn particle_wraps_around() {
+        init_logging();
+
+        let bouncy = ParticleStyle {
+            gravity_scale: 0.0,
+            restitution: 1.0,
+            ..style()
+        };
+        // A wall just behind the left edge
+        let wall = [Segment::new((-4.9, -1.0), (-4.9, 1.0)).unwrap()];
+        let mut particles = Particles::new(0.01, ToricGeometry { x: 10.0, y: 10.0 }, &wall);
+        let style = particles.add_style(bouncy);
+
+        // Leaves through the top edge and comes back through the bottom one, as fast as before
+        particles.burst(style, 1, Point::new(0.0, 4.9), Vec2::Y, vec2(0.0, 2.0));
+        advance(&mut particles, 10);
+        let particle = *particles.iter().next().unwrap();
+        assert!((particle.position.y + 4.9).abs() < 1e-3, "{particle:?}");
+        let velocity = particles.velocity(&particle);
+        assert!(velocity.abs_diff_eq(vec2(0.0, 2.0), 1e-3), "{velocity}");
+
+        // Bounces off the wall on the other side of the right edge
+        particles.burst(style, 1, Point::new(4.8, 0.0), Vec2::X, vec2(5.0, 0.0));
+        for _ in 0..20 {
+            advance(&mut particles, 1);
+            // Between the wall and the right edge, going through the left one
+            let particle = particles.iter().last().unwrap();
+            let x = particle.position.x;
+            assert!(!(-4.9..=4.0).contains(&x), "{particle:?}");
+        }
+        let particle = particles.iter().last().unwrap();
+        assert!(particles.velocity(particle).x < 0.0, "{particle:?}");
+    }
+
+    #[test]
+    fn particle_emission_and_lifetime() {
+        init_logging();
+
+        let mut particles = Particles::new(0.01, ToricGeometry { x: 100.0, y: 100.0 }, &[]);
+        let style = particles.add_style(ParticleStyle {
+            lifetime: (0.5, 0.5),
+            ..style()
+        });
+
+        // Ten particles per second, emitted every few frames
+        let mut emitter = ParticleEmitter::new(style, 10.0);
+        for _ in 0..30 {
+            particles.emit(&mut emitter, 1.0 / 30.0, Point::ZERO, Vec2::Y, Vec2::ZERO);
+        }
+        assert!((9..=10).contains(&particles.len()), "{}", particles.len());
+
+        // Colors fade out over the life
+        let style = particles.style(style);
+        assert_eq!(style.color(0.0), Vec4::ONE);
+        assert!(style.color(0.25).abs_diff_eq(Vec4::splat(0.75), 1e-6));
+        assert_eq!(style.color(1.0), Vec4::ZERO);
+
+        advance(&mut particles, 30);
+        let life = particles.iter().next().unwrap().life();
+        assert!(life > 0.5 && life < 1.0, "{life}");
+
+        advance(&mut particles, 30);
+        assert_eq!(particles.len(), 0);
+    }
+}
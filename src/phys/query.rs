@@ -0,0 +1,254 @@
+//! Questions about the objects in [`Physics`], like what is under the mouse or what a light can see.
+//!
+//! Objects are found through the edges of the torus, their images further than one size of the torus away are not.
+
+use glam::Vec2;
+
+use crate::{
+    geo::Point,
+    phys::{
+        Physics,
+        handle::Handle,
+        narrowphase::{self, RoundedHull, contact},
+        object::{PhysObject, PhysObjectShape},
+    },
+};
+
+/// Where a ray or a moving shape first hit an object.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CastHit {
+    pub handle: Handle,
+    /// Along the direction of the cast
+    pub distance: f32,
+    pub point: Point,
+    /// Unit vector that points out of the object that was hit
+    pub normal: Vec2,
+}
+
+impl<M> Physics<M> {
+    /// Objects that overlap the disc.
+    pub fn overlapping_disc(&self, center: Point, radius: f32) -> Vec<Handle> {
+        let shape = PhysObjectShape::Disc { radius };
+        self.overlapping(&RoundedHull::of_shape(&shape, center, 0.0), center)
+    }
+
+    /// Objects that overlap the box, its sides are parallel to the axes.
+    pub fn overlapping_box(&self, center: Point, half_width: f32, half_height: f32) -> Vec<Handle> {
+        let shape = PhysObjectShape::Box {
+            half_width,
+            half_height,
+        };
+        self.overlapping(&RoundedHull::of_shape(&shape, center, 0.0), center)
+    }
+
+    fn overlapping(&self, query: &RoundedHull, center: Point) -> Vec<Handle> {
+        self.iter()
+            .filter(|(_, obj)| contact(query, &self.image_near(obj, center)).is_some())
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// The object whose surface is the closest to the point, and the distance to it.
+    /// The distance is zero when the point is inside of the object.
+    pub fn nearest(&self, point: Point) -> Option<(Handle, f32)> {
+        let query = RoundedHull::of_shape(&PhysObjectShape::Disc { radius: 0.0 }, point, 0.0);
+
+        self.iter()
+            .map(|(handle, obj)| {
+                let distance = narrowphase::distance(&query, &self.image_near(obj, point));
+                (handle, distance.max(0.0))
+            })
+            .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+    }
+
+    /// The first object that the ray hits within the distance.
+    /// Objects that contain the origin are ignored, so rays can be cast from inside of an object.
+    pub fn ray_cast(&self, origin: Point, direction: Vec2, max_distance: f32) -> Option<CastHit> {
+        let direction = direction.try_normalize()?;
+
+        self.iter()
+            .filter_map(|(handle, obj)| {
+                let image = self.image_near(obj, origin);
+                if image.contains(origin.vec()) {
+                    return None;
+                }
+
+                self.around(&image)
+                    .filter_map(|image| {
+                        narrowphase::ray_cast(&image, origin.vec(), direction, max_distance)
+                    })
+                    .min_by(|(t1, _), (t2, _)| t1.total_cmp(t2))
+                    .map(|(distance, normal)| CastHit {
+                        handle,
+                        distance,
+                        point: self.wrapped(origin + direction * distance),
+                        normal,
+                    })
+            })
+            .min_by(|h1, h2| h1.distance.total_cmp(&h2.distance))
+    }
+
+    /// The first object that the shape hits when moving from `center` along the direction within the distance.
+    /// Objects that the shape overlaps at the start are ignored.
+    pub fn shape_cast(
+        &self,
+        shape: &PhysObjectShape,
+        center: Point,
+        orientation: f32,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> Option<CastHit> {
+        let direction = direction.try_normalize()?;
+        let moving = RoundedHull::of_shape(shape, center, orientation);
+
+        self.iter()
+            .filter_map(|(handle, obj)| {
+                self.around(&self.image_near(obj, center))
+                    .filter_map(|image| {
+                        narrowphase::shape_cast(&moving, direction, max_distance, &image)
+                    })
+                    .min_by(|(t1, _), (t2, _)| t1.total_cmp(t2))
+                    .map(|(distance, contact)| CastHit {
+                        handle,
+                        distance,
+                        point: self.wrapped(contact.point),
+                        normal: -contact.normal,
+                    })
+            })
+            .min_by(|h1, h2| h1.distance.total_cmp(&h2.distance))
+    }
+
+    /// The object where it is the closest to the point, going through the edges of the torus.
+    fn image_near(&self, obj: &PhysObject<M>, point: Point) -> RoundedHull {
+        let center = point + self.geometry.dir(point, obj.center);
+        RoundedHull::of_shape(&obj.shape, center, obj.orientation)
+    }
+
+    /// The image and the ones next to it, for queries that can reach further than half of the torus.
+    fn around<'a>(&self, image: &'a RoundedHull) -> impl Iterator<Item = RoundedHull> + 'a {
+        let (x, y) = (self.geometry.x, self.geometry.y);
+        [-1.0, 0.0, 1.0]
+            .into_iter()
+            .flat_map(move |i| [-1.0, 0.0, 1.0].map(|j| Vec2::new(i * x, j * y)))
+            .map(|offset| image.translated(offset))
+    }
+
+    fn wrapped(&self, mut point: Point) -> Point {
+        self.geometry.wrap(&mut point);
+        point
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use glam::vec2;
+
+    use crate::{geo::ToricGeometry, init_logging};
+
+    use super::*;
+
+    fn world() -> (Physics<()>, [Handle; 3]) {
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 20.0, y: 10.0 });
+        let handles = [
+            physics.add(PhysObject::new_disc((9.5, 0.0).into(), 1.0, 1.0)),
+            physics.add(PhysObject::new_box((0.0, 3.0).into(), 1.0, 0.5, 1.0)),
+            physics.add(
+                PhysObject::new_capsule((0.0, -3.0).into(), 1.0, 0.5, 1.0)
+                    .with_orientation(FRAC_PI_4),
+            ),
+        ];
+        (physics, handles)
+    }
+
+    #[test]
+    fn query_overlapping() {
+        init_logging();
+        let (physics, [disc, rect, _]) = world();
+
+        // Across the edge of the torus
+        assert_eq!(
+            physics.overlapping_disc((-9.5, 0.0).into(), 0.5),
+            vec![disc]
+        );
+        assert_eq!(physics.overlapping_disc((0.0, 0.0).into(), 0.5), vec![]);
+
+        assert_eq!(
+            physics.overlapping_box((1.5, 2.0).into(), 0.6, 0.6),
+            vec![rect]
+        );
+        assert_eq!(physics.overlapping_box((1.5, 1.5).into(), 0.4, 0.4), vec![]);
+    }
+
+    #[test]
+    fn query_nearest() {
+        init_logging();
+        let (physics, [disc, rect, capsule]) = world();
+
+        let (handle, distance) = physics.nearest((-8.0, 0.0).into()).unwrap();
+        assert_eq!(handle, disc);
+        assert!((distance - 1.5).abs() < 1e-5, "{distance}");
+
+        assert_eq!(physics.nearest((0.2, 3.1).into()), Some((rect, 0.0)));
+        assert_eq!(physics.nearest((0.5, -2.0).into()).unwrap().0, capsule);
+    }
+
+    #[test]
+    fn query_ray_cast() {
+        init_logging();
+        let (physics, [disc, rect, _]) = world();
+
+        // Straight up into the bottom of the box
+        let hit = physics
+            .ray_cast((0.0, 1.0).into(), vec2(0.0, 1.0), 10.0)
+            .unwrap();
+        assert_eq!(hit.handle, rect);
+        assert!((hit.distance - 1.5).abs() < 1e-5, "{hit:?}");
+        assert!(hit.normal.abs_diff_eq(vec2(0.0, -1.0), 1e-5), "{hit:?}");
+
+        // To the left, through the edge of the torus
+        let hit = physics
+            .ray_cast((-5.0, 0.0).into(), vec2(-1.0, 0.0), 10.0)
+            .unwrap();
+        assert_eq!(hit.handle, disc);
+        assert!((hit.distance - 4.5).abs() < 1e-5, "{hit:?}");
+        assert!(hit.normal.abs_diff_eq(vec2(1.0, 0.0), 1e-5), "{hit:?}");
+        assert!(hit.point.dist((-9.5, 0.0).into()) < 1e-5, "{hit:?}");
+
+        // Too short, and from inside of the disc
+        assert!(
+            physics
+                .ray_cast((-5.0, 0.0).into(), vec2(-1.0, 0.0), 4.0)
+                .is_none()
+        );
+        let hit = physics.ray_cast((9.5, 0.0).into(), vec2(-1.0, 0.0), 8.0);
+        assert!(hit.is_none(), "{hit:?}");
+    }
+
+    #[test]
+    fn query_shape_cast() {
+        init_logging();
+        let (physics, [_, rect, capsule]) = world();
+
+        // A disc moving up stops under the box
+        let shape = PhysObjectShape::Disc { radius: 0.5 };
+        let hit = physics
+            .shape_cast(&shape, (0.5, 0.0).into(), 0.0, vec2(0.0, 1.0), 5.0)
+            .unwrap();
+        assert_eq!(hit.handle, rect);
+        assert!((hit.distance - 2.0).abs() < 1e-3, "{hit:?}");
+        assert!(hit.normal.abs_diff_eq(vec2(0.0, -1.0), 1e-3), "{hit:?}");
+
+        // A box moving down touches the capsule
+        let shape = PhysObjectShape::Box {
+            half_width: 0.25,
+            half_height: 0.25,
+        };
+        let hit = physics
+            .shape_cast(&shape, (0.0, 0.0).into(), 0.0, vec2(0.0, -1.0), 5.0)
+            .unwrap();
+        assert_eq!(hit.handle, capsule);
+        assert!(hit.distance < 3.0 - 0.25 - 0.5, "{hit:?}");
+    }
+}
@@ -0,0 +1,861 @@
+//! Contacts of a step, solved all together with sequential impulses.
+//!
+//! Every iteration goes over all contact points and corrects the impulses applied at them so far,
+//! so that a pile of objects settles as a whole instead of one pair at a time.
+//! The impulses of the previous step are applied first (warm starting), objects that rest on each other
+//! need about the same impulses every step. Overlaps are resolved with separate impulses that only move
+//! the objects apart (split impulses), so they don't add any energy.
+//!
+//! Based on https://box2d.org/files/ErinCatto_SequentialImpulses_GDC2006.pdf
+
+use std::f32::consts::TAU;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geo::Point,
+    phys::{
+        collision::{Collision, ContactSide},
+        events::ContactPair,
+        material::Material,
+        narrowphase::ContactManifold,
+        object::PhysObject,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ManifoldPoint {
+    pub point: Point,
+    pub depth: f32,
+    /// Accumulated over the iterations along the normal, negative only for sticky contacts
+    pub normal_impulse: f32,
+    /// Accumulated over the iterations along the tangent, the normal turned counterclockwise
+    pub tangent_impulse: f32,
+    /// Separating velocity that the restitution asks for
+    #[serde(skip)]
+    target_velocity: f32,
+    /// How far below zero the normal impulse can go to hold the objects together
+    #[serde(skip)]
+    adhesion: f32,
+    /// Accumulated over the iterations of the position correction
+    #[serde(skip)]
+    position_impulse: f32,
+}
+
+/// Contact points of a pair, see [`ContactManifold`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifold {
+    pub pair: ContactPair,
+    /// Index of the first object of the pair in the storage, `None` for scene objects
+    pub a: Option<usize>,
+    /// Index of the second object of the pair, or of the object that touches a scene object
+    pub b: usize,
+    /// Unit vector that points from `a` to `b`
+    pub normal: Vec2,
+    pub material: Material,
+    /// From the image of `b` that touches `a` to `b` itself, when they touch across the edges of the torus.
+    /// The contact points are next to `a`
+    pub offset: Vec2,
+    pub points: Vec<ManifoldPoint>,
+}
+
+impl Manifold {
+    /// The normal of the contact has to point from `a` to `b`.
+    pub fn new(
+        pair: ContactPair,
+        a: Option<usize>,
+        b: usize,
+        contact: ContactManifold,
+        material: Material,
+    ) -> Self {
+        let points = contact
+            .points
+            .into_iter()
+            .map(|p| ManifoldPoint {
+                point: p.point,
+                depth: p.depth,
+                normal_impulse: 0.0,
+                tangent_impulse: 0.0,
+                target_velocity: 0.0,
+                adhesion: 0.0,
+                position_impulse: 0.0,
+            })
+            .collect();
+
+        Self {
+            pair,
+            a,
+            b,
+            normal: contact.normal,
+            material,
+            offset: Vec2::ZERO,
+            points,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    fn tangent(&self) -> Vec2 {
+        self.normal.perp()
+    }
+
+    /// The whole contact as a single one, for the contact events.
+    pub fn collision(&self) -> Collision {
+        let count = self.points.len() as f32;
+        let point = self.points.iter().map(|p| p.point.vec()).sum::<Vec2>() / count;
+        let normal_impulse: f32 = self.points.iter().map(|p| p.normal_impulse).sum();
+        let tangent_impulse: f32 = self.points.iter().map(|p| p.tangent_impulse).sum();
+
+        Collision {
+            point: Point::new(point.x, point.y),
+            normal: self.normal,
+            impulse: (self.normal * normal_impulse + self.tangent() * tangent_impulse).length(),
+        }
+    }
+
+    fn sides<M>(&self, objects: &[PhysObject<M>], point: Point) -> (ContactSide, ContactSide) {
+        sides(self.a, self.b, self.offset, objects, point)
+    }
+
+    /// Apply the impulse to `b` and the opposite one to `a`.
+    fn apply_impulse<M>(&self, objects: &mut [PhysObject<M>], point: Point, impulse: Vec2) {
+        if let Some(a) = self.a {
+            let obj = &mut objects[a];
+            let arm = obj.center.dir(point);
+            obj.apply_impulse(-impulse, arm);
+        }
+
+        let obj = &mut objects[self.b];
+        let arm = obj.center.dir(point + self.offset);
+        obj.apply_impulse(impulse, arm);
+    }
+}
+
+fn sides<M>(
+    a: Option<usize>,
+    b: usize,
+    offset: Vec2,
+    objects: &[PhysObject<M>],
+    point: Point,
+) -> (ContactSide, ContactSide) {
+    let a = match a {
+        Some(a) => ContactSide::new(&objects[a], point),
+        None => ContactSide::IMMOVABLE,
+    };
+    (a, ContactSide::new(&objects[b], point + offset))
+}
+
+/// Velocities that only move the objects out of each other during the step, and are forgotten after that.
+#[derive(Clone, Copy, Debug, Default)]
+struct PseudoVelocity {
+    linear: Vec2,
+    angular: f32,
+}
+
+pub struct ContactSolver {
+    manifolds: Vec<Manifold>,
+    pseudo: Vec<PseudoVelocity>,
+}
+
+impl ContactSolver {
+    /// Approaching slower than that, tiles per second, objects don't bounce.
+    /// Otherwise resting objects would keep bouncing off each other because of the gravity
+    const RESTITUTION_VELOCITY: f32 = 0.2;
+    /// Overlap that is allowed to remain, so that the contacts of resting objects don't come and go
+    const SLOP: f32 = 0.005;
+    /// Part of the overlap that is resolved during each step
+    const POSITION_CORRECTION: f32 = 0.2;
+    const POSITION_ITERATIONS: usize = 4;
+    /// Contact points that moved less than that since the previous step, tiles, start from its impulses
+    const WARM_START_DISTANCE: f32 = 0.1;
+
+    /// Prepare the contacts of the step and apply the impulses that they had during the previous step.
+    /// `previous` are the manifolds of the previous step, sorted by pair.
+    pub fn new<M>(
+        mut manifolds: Vec<Manifold>,
+        previous: &[Manifold],
+        objects: &mut [PhysObject<M>],
+        seconds: f32,
+    ) -> Self {
+        for manifold in &mut manifolds {
+            let old = previous
+                .binary_search_by_key(&manifold.pair, |m| m.pair)
+                .ok()
+                .map(|i| &previous[i])
+                .filter(|old| old.normal.dot(manifold.normal) > 0.95);
+
+            let count = manifold.points.len() as f32;
+            for i in 0..manifold.points.len() {
+                let point = manifold.points[i].point;
+                let (a, b) = manifold.sides(objects, point);
+                let approaching = (b.velocity - a.velocity).dot(manifold.normal);
+                let k = a.inverse_effective_mass(manifold.normal)
+                    + b.inverse_effective_mass(manifold.normal);
+
+                let p = &mut manifold.points[i];
+                // The impulse that holds against the stickiness for a step, shared between the points
+                if k > 0.0 {
+                    p.adhesion = manifold.material.stickiness * seconds / k / count;
+                }
+                p.target_velocity = if approaching < -Self::RESTITUTION_VELOCITY {
+                    -manifold.material.restitution * approaching
+                } else {
+                    0.0
+                };
+
+                let warm = old.and_then(|old| {
+                    old.points
+                        .iter()
+                        .filter(|q| q.point.dist(point) < Self::WARM_START_DISTANCE)
+                        .min_by(|q1, q2| q1.point.dist(point).total_cmp(&q2.point.dist(point)))
+                });
+                if let Some(warm) = warm {
+                    p.normal_impulse = warm.normal_impulse;
+                    p.tangent_impulse = warm.tangent_impulse;
+                }
+            }
+        }
+
+        // Only after the restitution has seen the velocities from before the step
+        for manifold in &manifolds {
+            for p in &manifold.points {
+                let impulse =
+                    manifold.normal * p.normal_impulse + manifold.tangent() * p.tangent_impulse;
+                manifold.apply_impulse(objects, p.point, impulse);
+            }
+        }
+
+        Self {
+            manifolds,
+            pseudo: vec![PseudoVelocity::default(); objects.len()],
+        }
+    }
+
+    /// One iteration over all contact points: friction first, so that the normal impulse has the last word.
+    pub fn solve_velocities<M>(&mut self, objects: &mut [PhysObject<M>]) {
+        for manifold in &mut self.manifolds {
+            let (normal, tangent) = (manifold.normal, manifold.tangent());
+
+            for i in 0..manifold.points.len() {
+                let point = manifold.points[i].point;
+
+                let (a, b) = manifold.sides(objects, point);
+                let k = a.inverse_effective_mass(tangent) + b.inverse_effective_mass(tangent);
+                if k > 0.0 {
+                    let p = &mut manifold.points[i];
+                    let sliding = (b.velocity - a.velocity).dot(tangent);
+                    let candidate = p.tangent_impulse - sliding / k;
+
+                    // Sticks while the friction can hold it, slides otherwise.
+                    // Pulling on a sticky contact does not press it
+                    let material = manifold.material;
+                    let pressing = p.normal_impulse.max(0.0);
+                    let limit = if candidate.abs() <= material.static_friction * pressing {
+                        f32::INFINITY
+                    } else {
+                        material.dynamic_friction * pressing
+                    };
+                    let total = candidate.clamp(-limit, limit);
+
+                    let change = total - p.tangent_impulse;
+                    p.tangent_impulse = total;
+                    manifold.apply_impulse(objects, point, tangent * change);
+                }
+
+                let (a, b) = manifold.sides(objects, point);
+                let k = a.inverse_effective_mass(normal) + b.inverse_effective_mass(normal);
+                if k > 0.0 {
+                    let p = &mut manifold.points[i];
+                    let separating = (b.velocity - a.velocity).dot(normal);
+                    let total =
+                        (p.normal_impulse - (separating - p.target_velocity) / k).max(-p.adhesion);
+
+                    let change = total - p.normal_impulse;
+                    p.normal_impulse = total;
+                    manifold.apply_impulse(objects, point, normal * change);
+                }
+            }
+        }
+    }
+
+    /// Move the objects out of each other, without changing their velocities.
+    pub fn correct_positions<M>(&mut self, objects: &mut [PhysObject<M>], seconds: f32) {
+        for _ in 0..Self::POSITION_ITERATIONS {
+            for manifold in &mut self.manifolds {
+                let normal = manifold.normal;
+
+                for p in &mut manifold.points {
+                    let (a, b) = sides(manifold.a, manifold.b, manifold.offset, objects, p.point);
+                    let k = a.inverse_effective_mass(normal) + b.inverse_effective_mass(normal);
+                    if k == 0.0 {
+                        continue;
+                    }
+
+                    let pseudo_at = |index: Option<usize>, side: &ContactSide| {
+                        index.map_or(Vec2::ZERO, |i| {
+                            let v = self.pseudo[i];
+                            v.linear + v.angular * side.arm.perp()
+                        })
+                    };
+                    let separating =
+                        (pseudo_at(Some(manifold.b), &b) - pseudo_at(manifold.a, &a)).dot(normal);
+                    let target =
+                        Self::POSITION_CORRECTION * (p.depth - Self::SLOP).max(0.0) / seconds;
+
+                    let total = (p.position_impulse + (target - separating) / k).max(0.0);
+                    let impulse = normal * (total - p.position_impulse);
+                    p.position_impulse = total;
+
+                    for (index, side, impulse) in
+                        [(manifold.a, a, -impulse), (Some(manifold.b), b, impulse)]
+                    {
+                        if let Some(i) = index {
+                            let v = &mut self.pseudo[i];
+                            v.linear += impulse * side.inverse_mass;
+                            v.angular += side.arm.perp_dot(impulse) * side.inverse_inertia;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (obj, v) in objects.iter_mut().zip(&self.pseudo) {
+            obj.center += v.linear * seconds;
+            obj.orientation = (obj.orientation + v.angular * seconds).rem_euclid(TAU);
+        }
+    }
+
+    /// The manifolds with the impulses that were applied, sorted by pair to warm start the next step.
+    pub fn finish(mut self) -> Vec<Manifold> {
+        self.manifolds.sort_by_key(|m| m.pair);
+        self.manifolds
+    }
+
+    pub fn manifolds(&self) -> &[Manifold] {
+        &self.manifolds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glam::vec2;
+
+    use crate::{
+        geo::ToricGeometry,
+        init_logging,
+        phys::{Physics, handle::Handle, object::SceneObject},
+    };
+
+    use super::*;
+
+    const MATERIAL: Material = Material {
+        restitution: 0.3,
+        static_friction: 0.6,
+        dynamic_friction: 0.5,
+        stickiness: 0.0,
+    };
+
+    /// A floor with walls on both sides.
+    fn container(half_width: f32) -> Vec<SceneObject> {
+        let (l, r) = (-half_width, half_width);
+        [
+            ((l, 0.0), (r, 0.0)),
+            ((l, 0.0), (l, 20.0)),
+            ((r, 0.0), (r, 20.0)),
+        ]
+        .map(|(a, b)| SceneObject::new_segment(a.into(), b.into()).with_material(MATERIAL))
+        .into()
+    }
+
+    fn advance(physics: &mut Physics<()>, scene: &[SceneObject], steps: usize) {
+        for _ in 0..steps {
+            physics.advance_by(scene, Duration::from_millis(10));
+        }
+    }
+
+    /// Largest overlap of any two discs, and of any disc with the floor.
+    fn overlaps(physics: &Physics<()>, discs: &[Handle], radius: f32) -> (f32, f32) {
+        let centers: Vec<_> = discs
+            .iter()
+            .map(|h| physics.get(*h).unwrap().center)
+            .collect();
+
+        let mut between: f32 = 0.0;
+        for (i, a) in centers.iter().enumerate() {
+            for b in &centers[i + 1..] {
+                between = between.max(2.0 * radius - a.dist(*b));
+            }
+        }
+
+        let floor = centers
+            .iter()
+            .map(|c| radius - c.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        (between, floor)
+    }
+
+    #[test]
+    fn solver_disc_column() {
+        init_logging();
+
+        // Barely wider than the discs, so they stand on top of each other
+        let radius = 0.25;
+        let scene = container(radius + 0.01);
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let discs: Vec<_> = (0..8)
+            .map(|i| {
+                let y = radius + i as f32 * (2.0 * radius + 0.1);
+                physics
+                    .add(PhysObject::new_disc((0.0, y).into(), radius, 1.0).with_material(MATERIAL))
+            })
+            .collect();
+
+        advance(&mut physics, &scene, 300);
+
+        let (between, floor) = overlaps(&physics, &discs, radius);
+        assert!(between < 0.02, "{between}");
+        assert!(floor < 0.02, "{floor}");
+
+        // The whole column rests, and the top is where it should be
+        for handle in &discs {
+            let disc = physics.get(*handle).unwrap();
+            assert!(disc.velocity_linear.length() < 0.01, "{disc:?}");
+        }
+        let top = physics.get(discs[7]).unwrap().center.y;
+        assert!((top - 15.0 * radius).abs() < 0.1, "{top}");
+    }
+
+    #[test]
+    fn solver_disc_pile() {
+        init_logging();
+
+        // Dropped from a grid into a container that fits four discs side by side
+        let radius = 0.25;
+        let scene = container(4.0 * radius + 0.05);
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let mut discs = Vec::new();
+        for row in 0..5 {
+            for column in 0..4 {
+                // Every other row is shifted, so that the discs fall into the gaps
+                let shift = if row % 2 == 0 { -0.05 } else { 0.05 };
+                let x = (column as f32 - 1.5) * 2.0 * radius + shift;
+                let y = 1.0 + row as f32 * 0.7;
+                let disc = PhysObject::new_disc((x, y).into(), radius, 1.0).with_material(MATERIAL);
+                discs.push(physics.add(disc));
+            }
+        }
+
+        let mut worst: f32 = 0.0;
+        for _ in 0..500 {
+            advance(&mut physics, &scene, 1);
+            worst = worst.max(overlaps(&physics, &discs, radius).0);
+        }
+
+        // Never sinks into each other much, even while falling onto each other
+        assert!(worst < 0.05, "{worst}");
+
+        // And settles without overlapping
+        let (between, floor) = overlaps(&physics, &discs, radius);
+        assert!(between < 0.02, "{between}");
+        assert!(floor < 0.02, "{floor}");
+        for handle in &discs {
+            let disc = physics.get(*handle).unwrap();
+            assert!(disc.velocity_linear.length() < 0.01, "{disc:?}");
+            assert!(disc.center.x.abs() < 4.0 * radius, "{disc:?}");
+        }
+    }
+
+    #[test]
+    fn solver_box_stack() {
+        init_logging();
+
+        // Boxes stand on each other on their flat sides, without tipping over
+        let scene = container(10.0);
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        let boxes: Vec<_> = (0..5)
+            .map(|i| {
+                let center = (0.0, 0.5 + i as f32 * 1.05).into();
+                physics.add(PhysObject::new_box(center, 0.5, 0.5, 1.0).with_material(MATERIAL))
+            })
+            .collect();
+
+        advance(&mut physics, &scene, 500);
+
+        for (i, handle) in boxes.iter().enumerate() {
+            let b = physics.get(*handle).unwrap();
+            let expected = 0.5 + i as f32;
+            assert!(b.center.x.abs() < 0.01, "{b:?}");
+            assert!((b.center.y - expected).abs() < 0.05, "{b:?}");
+            let tilt = (b.orientation + 0.5).rem_euclid(TAU) - 0.5;
+            assert!(tilt.abs() < 0.01, "{b:?}");
+        }
+    }
+
+    #[test]
+    fn solver_sticky_ceiling() {
+        init_logging();
+
+        // Discs touching a ceiling stay on it while it is stickier than the gravity is strong
+        for (stickiness, sticks) in [(10.0, true), (1.0, false)] {
+            let material = Material {
+                restitution: 0.0,
+                stickiness,
+                ..MATERIAL
+            };
+            let ceiling = [
+                SceneObject::new_segment((-5.0, 2.0).into(), (5.0, 2.0).into())
+                    .with_material(material),
+            ];
+
+            let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+            let radius = 0.25;
+            let disc = physics.add(
+                PhysObject::new_disc((0.0, 2.0 - radius + 0.01).into(), radius, 1.0)
+                    .with_material(material),
+            );
+
+            advance(&mut physics, &ceiling, 100);
+
+            let disc = physics.get(disc).unwrap();
+            let hanging = (disc.center.y - (2.0 - radius)).abs() < 0.02;
+            assert_eq!(hanging, sticks, "{stickiness}: {disc:?}");
+        }
+    }
+
+    /// One step of the physics without gravity, the objects are returned in the same order.
+    fn step(objects: Vec<PhysObject<()>>, scene: &[SceneObject]) -> Vec<PhysObject<()>> {
+        let mut physics = Physics::new(0.01, ToricGeometry { x: 100.0, y: 100.0 });
+        physics.set_gravity(Vec2::ZERO);
+
+        let handles: Vec<_> = objects.into_iter().map(|obj| physics.add(obj)).collect();
+        physics.advance_by(scene, Duration::from_millis(10));
+
+        handles
+            .into_iter()
+            .map(|h| physics.get(h).unwrap().clone())
+            .collect()
+    }
+
+    fn step_pair(obj1: PhysObject<()>, obj2: PhysObject<()>) -> (PhysObject<()>, PhysObject<()>) {
+        let [obj1, obj2] = step(vec![obj1, obj2], &[]).try_into().unwrap();
+        (obj1, obj2)
+    }
+
+    fn step_scene(obj: PhysObject<()>, with: SceneObject) -> PhysObject<()> {
+        step(vec![obj], &[with]).pop().unwrap()
+    }
+
+    #[test]
+    fn solver_two_discs() {
+        init_logging();
+
+        let close = |a: Vec2, b: Vec2| a.abs_diff_eq(b, 1e-4);
+
+        {
+            // Touching, but not moving
+            let d1 = PhysObject::new_disc((0.0, 0.0).into(), 1.0, 1.0);
+            let d2 = PhysObject::new_disc((2.0, 0.0).into(), 1.0, 1.0);
+
+            let (d1, d2) = step_pair(d1, d2);
+
+            assert_eq!(d1.velocity_linear, vec2(0.0, 0.0));
+            assert_eq!(d2.velocity_linear, vec2(0.0, 0.0));
+        }
+
+        {
+            // The moving one stops and the other one takes over its velocity
+            let d1 =
+                PhysObject::new_disc((0.0, 0.0).into(), 1.0, 1.0).with_velocity(vec2(1.0, 0.0));
+            let d2 = PhysObject::new_disc((1.99, 0.0).into(), 1.0, 1.0);
+
+            let (d1, d2) = step_pair(d1, d2);
+
+            assert!(close(d1.velocity_linear, vec2(0.0, 0.0)), "{d1:?}");
+            assert!(close(d2.velocity_linear, vec2(1.0, 0.0)), "{d2:?}");
+        }
+
+        {
+            // Already moving apart
+            let d1 =
+                PhysObject::new_disc((0.0, 0.0).into(), 1.0, 1.0).with_velocity(vec2(-1.0, 0.0));
+            let d2 =
+                PhysObject::new_disc((1.99, 0.0).into(), 1.0, 1.0).with_velocity(vec2(1.0, 0.0));
+
+            let (d1, d2) = step_pair(d1, d2);
+
+            assert!(close(d1.velocity_linear, vec2(-1.0, 0.0)), "{d1:?}");
+            assert!(close(d2.velocity_linear, vec2(1.0, 0.0)), "{d2:?}");
+        }
+    }
+
+    #[test]
+    fn solver_disc_segment() {
+        init_logging();
+
+        {
+            let d = PhysObject::new_disc((0.0, 1.0).into(), 1.0, 1.0);
+            let s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into());
+
+            let d = step_scene(d, s);
+
+            assert_eq!(d.velocity_linear, vec2(0.0, 0.0));
+        }
+
+        {
+            let d =
+                PhysObject::new_disc((0.0, 0.99).into(), 1.0, 1.0).with_velocity(vec2(0.0, -1.0));
+            let s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into()).with_material(
+                Material {
+                    restitution: 0.9,
+                    ..Default::default()
+                },
+            );
+
+            let d = step_scene(d, s);
+
+            assert!(d.velocity_linear.abs_diff_eq(vec2(0.0, 0.9), 1e-5), "{d:?}");
+            assert_eq!(d.velocity_angular, 0.0);
+            // Moved out of the segment
+            assert!(d.center.y > 0.99, "{d:?}");
+        }
+
+        {
+            // Sliding along the floor while hitting it makes the disc spin
+            let d = PhysObject::new_disc((0.0, 0.99).into(), 1.0, 1.0)
+                .with_velocity(vec2(1.0, -1.0))
+                .with_material(ROUGH);
+            let s = SceneObject::new_segment((-1.0, 0.0).into(), (1.0, 0.0).into())
+                .with_material(ROUGH);
+
+            let d = step_scene(d, s);
+
+            // Friction only takes away horizontal velocity, restitution is 0.5 * 0.5
+            assert!((d.velocity_linear.y - 0.25).abs() < 1e-5, "{d:?}");
+            assert!(0.0 < d.velocity_linear.x && d.velocity_linear.x < 1.0);
+            // Rolling to the right is clockwise
+            assert!(d.velocity_angular < 0.0);
+        }
+    }
+
+    const ROUGH: Material = Material {
+        restitution: 0.5,
+        static_friction: 0.3,
+        dynamic_friction: 0.2,
+        stickiness: 0.0,
+    };
+
+    fn kinetic_energy(obj: &PhysObject<()>) -> f32 {
+        let linear = obj.mass * obj.velocity_linear.length_squared() / 2.0;
+        let angular = obj.inertia() * obj.velocity_angular.powi(2) / 2.0;
+        linear + angular
+    }
+
+    fn momentum(obj1: &PhysObject<()>, obj2: &PhysObject<()>) -> Vec2 {
+        obj1.velocity_linear * obj1.mass + obj2.velocity_linear * obj2.mass
+    }
+
+    #[test]
+    fn solver_momentum_conserved() {
+        init_logging();
+
+        let mut d1 = PhysObject::new_disc((0.0, 0.0).into(), 1.0, 2.0)
+            .with_velocity(vec2(1.5, 0.5))
+            .with_material(ROUGH);
+        d1.velocity_angular = 2.0;
+
+        let d2 = PhysObject::new_disc((1.2, 1.2).into(), 0.7, 0.5)
+            .with_velocity(vec2(-1.0, 0.2))
+            .with_material(ROUGH);
+
+        let momentum_before = momentum(&d1, &d2);
+        let energy_before = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+        let (d1, d2) = step_pair(d1, d2);
+
+        let momentum_after = momentum(&d1, &d2);
+        let energy_after = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+        assert!(
+            momentum_before.abs_diff_eq(momentum_after, 1e-5),
+            "{momentum_before} != {momentum_after}"
+        );
+        assert!(energy_after < energy_before);
+        // Friction made both of them spin
+        assert_ne!(d2.velocity_angular, 0.0);
+    }
+
+    #[test]
+    fn solver_energy_loss() {
+        init_logging();
+
+        // Head-on, so friction does not matter
+        let (m1, m2) = (3.0, 1.0);
+        let (v1, v2) = (2.0, -1.0);
+
+        for restitution in [0.0, 0.5, 1.0] {
+            let material = Material {
+                restitution,
+                ..ROUGH
+            };
+
+            let d1 = PhysObject::new_disc((0.0, 0.0).into(), 1.0, m1)
+                .with_velocity(vec2(v1, 0.0))
+                .with_material(Material {
+                    restitution: 1.0,
+                    ..material
+                });
+            let d2 = PhysObject::new_disc((1.9, 0.0).into(), 1.0, m2)
+                .with_velocity(vec2(v2, 0.0))
+                .with_material(material);
+
+            let energy_before = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+            let (d1, d2) = step_pair(d1, d2);
+
+            let energy_after = kinetic_energy(&d1) + kinetic_energy(&d2);
+
+            // Energy lost in a collision with the given coefficient of restitution
+            let reduced_mass = m1 * m2 / (m1 + m2);
+            let expected_loss =
+                reduced_mass * (v1 - v2).powi(2) * (1.0 - restitution.powi(2)) / 2.0;
+
+            assert!(
+                (energy_before - energy_after - expected_loss).abs() < 1e-4,
+                "restitution {restitution}: lost {}, expected {expected_loss}",
+                energy_before - energy_after
+            );
+
+            // Objects separate with the given share of the approach speed
+            let separation = d2.velocity_linear.x - d1.velocity_linear.x;
+            assert!((separation - restitution * (v1 - v2)).abs() < 1e-4);
+            assert_eq!(d1.velocity_angular, 0.0);
+        }
+    }
+
+    #[test]
+    fn solver_box_segment() {
+        init_logging();
+
+        let s = SceneObject::new_segment((-3.0, 0.0).into(), (3.0, 0.0).into());
+
+        // Landing flat bounces straight back up
+        let b =
+            PhysObject::new_box((0.0, 0.95).into(), 1.0, 1.0, 1.0).with_velocity(vec2(0.0, -2.0));
+        let b = step_scene(b, s.clone());
+        assert!(b.velocity_linear.abs_diff_eq(vec2(0.0, 2.0), 1e-3), "{b:?}");
+        assert!(b.velocity_angular.abs() < 1e-3, "{b:?}");
+        assert!(b.center.y > 0.95, "{b:?}");
+
+        // Landing on a corner makes it spin
+        let b = PhysObject::new_box((0.5, 1.2).into(), 1.0, 1.0, 1.0)
+            .with_orientation(0.3)
+            .with_velocity(vec2(0.0, -2.0));
+        let b = step_scene(b, s);
+        assert!(b.velocity_linear.y > 0.0, "{b:?}");
+        assert!(b.velocity_angular.abs() > 0.1, "{b:?}");
+    }
+
+    #[test]
+    fn solver_shapes_momentum_conserved() {
+        init_logging();
+
+        let triangle = vec![vec2(0.0, 1.0), vec2(-1.0, -0.5), vec2(1.0, -0.5)];
+        let shapes = [
+            PhysObject::new_capsule((0.0, 0.0).into(), 1.0, 0.5, 2.0).with_orientation(0.3),
+            PhysObject::new_polygon((0.0, 0.0).into(), triangle, 1.5),
+            PhysObject::new_box((0.0, 0.0).into(), 0.5, 0.8, 1.0).with_orientation(-0.2),
+        ];
+
+        for shape in shapes {
+            let d = PhysObject::new_disc((1.0, 0.6).into(), 0.7, 0.5)
+                .with_velocity(vec2(-1.0, -0.5))
+                .with_material(ROUGH);
+            let shape = shape.with_velocity(vec2(0.5, 0.2)).with_material(ROUGH);
+
+            let momentum_before = momentum(&shape, &d);
+            let energy_before = kinetic_energy(&shape) + kinetic_energy(&d);
+
+            let (shape, d) = step_pair(shape, d);
+
+            let momentum_after = momentum(&shape, &d);
+            let energy_after = kinetic_energy(&shape) + kinetic_energy(&d);
+
+            assert!(
+                momentum_before.abs_diff_eq(momentum_after, 1e-5),
+                "{momentum_before} != {momentum_after}"
+            );
+            assert!(energy_after < energy_before, "{shape:?}");
+            assert_ne!(shape.velocity_angular, 0.0, "{shape:?}");
+        }
+    }
+
+    #[test]
+    fn solver_disc_segment_axis_aligned() {
+        init_logging();
+
+        let discs = || {
+            [
+                // Hitting the inner part from either side
+                PhysObject::new_disc((0.3, 0.8).into(), 1.0, 1.0).with_velocity(vec2(0.5, -1.0)),
+                PhysObject::new_disc((-0.4, -0.6).into(), 1.0, 1.0).with_velocity(vec2(0.2, 1.5)),
+                // Hitting the ends
+                PhysObject::new_disc((1.5, 0.3).into(), 1.0, 1.0).with_velocity(vec2(-1.0, -0.2)),
+                PhysObject::new_disc((-1.6, -0.5).into(), 1.0, 1.0).with_velocity(vec2(1.0, 0.4)),
+                // Too far away
+                PhysObject::new_disc((0.0, 1.5).into(), 1.0, 1.0).with_velocity(vec2(0.0, -1.0)),
+                PhysObject::new_disc((2.5, 0.0).into(), 1.0, 1.0).with_velocity(vec2(-1.0, 0.0)),
+            ]
+            .map(|d| d.with_material(ROUGH))
+        };
+
+        // For vertical segments, the discs are turned by a quarter
+        let check = |fast: SceneObject, general: SceneObject, turn: bool| {
+            for mut d in discs() {
+                if turn {
+                    d.center = Point::new(-d.center.y, d.center.x);
+                    d.velocity_linear = d.velocity_linear.perp();
+                }
+
+                let d_fast = step_scene(d.clone(), fast.clone());
+                let d_general = step_scene(d, general.clone());
+
+                assert!(
+                    d_fast.center.dist(d_general.center) < 1e-5,
+                    "{d_fast:?} != {d_general:?}"
+                );
+                assert!(
+                    d_fast
+                        .velocity_linear
+                        .abs_diff_eq(d_general.velocity_linear, 1e-5),
+                    "{d_fast:?} != {d_general:?}"
+                );
+                assert!(
+                    (d_fast.velocity_angular - d_general.velocity_angular).abs() < 1e-5,
+                    "{d_fast:?} != {d_general:?}"
+                );
+            }
+        };
+
+        // Both directions of the segment behave the same
+        for (start, end) in [(-1.0, 1.0), (1.0, -1.0)] {
+            let h =
+                SceneObject::new_segment_h((start, 0.0).into(), end - start).with_material(ROUGH);
+            let general = SceneObject::new_segment((start, 0.0).into(), (end, 0.0).into())
+                .with_material(ROUGH);
+            check(h, general, false);
+
+            let v =
+                SceneObject::new_segment_v((0.0, start).into(), end - start).with_material(ROUGH);
+            let general = SceneObject::new_segment((0.0, start).into(), (0.0, end).into())
+                .with_material(ROUGH);
+            check(v, general, true);
+        }
+    }
+}
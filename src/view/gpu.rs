@@ -11,6 +11,8 @@ pub struct PipelineConfig<'s, 'v, 'g, 'o> {
     pub targets: &'o [wgpu::ColorTargetState],
     pub depth_stencil: Option<wgpu::DepthStencilState>,
     pub vertex_layout: wgpu::VertexBufferLayout<'v>,
+    /// For instanced drawing, see [`VertexData::get_instance_buffer`]
+    pub instance_layout: Option<wgpu::VertexBufferLayout<'v>>,
 }
 
 pub struct RenderPass<'desc, 'pip, 'gdat, 'vdat> {
@@ -97,6 +99,11 @@ impl GPU {
             .map(|target| Some(target.clone()))
             .collect();
 
+        let buffers: Vec<_> = [Some(config.vertex_layout), config.instance_layout]
+            .into_iter()
+            .flatten()
+            .collect();
+
         let pipeline = self
             .device
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -106,7 +113,7 @@ impl GPU {
                     module: &config.shader,
                     entry_point: None,
                     compilation_options: Default::default(),
-                    buffers: &[config.vertex_layout],
+                    buffers: &buffers,
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &config.shader,
@@ -135,13 +142,26 @@ impl GPU {
                 rpass.set_bind_group(index as u32, *group, &[]);
             }
 
+            // Empty buffers can't be bound, the pass still loads and clears its attachments
+            if pass.vdata.get_index_count() == 0 {
+                continue;
+            }
+
             rpass.set_vertex_buffer(0, pass.vdata.get_vertex_buffer().slice(..));
             rpass.set_index_buffer(
                 pass.vdata.get_index_buffer().slice(..),
                 pass.vdata.get_index_format(),
             );
 
-            rpass.draw_indexed(0..pass.vdata.get_index_count(), 0, 0..1);
+            if let Some(instances) = pass.vdata.get_instance_buffer() {
+                rpass.set_vertex_buffer(1, instances.slice(..));
+            }
+
+            rpass.draw_indexed(
+                0..pass.vdata.get_index_count(),
+                0,
+                0..pass.vdata.get_instance_count(),
+            );
         }
 
         self.queue.submit([encoder.finish()]);
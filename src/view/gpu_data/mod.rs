@@ -18,5 +18,6 @@ pub use texture_multiplexer::TextureMultiplexer;
 pub use uniform::UniformGroup;
 
 pub use vertex::IndexFormat;
+pub use vertex::InstanceBuffers;
 pub use vertex::VertexBuffers;
 pub use vertex::VertexData;
@@ -7,7 +7,9 @@ use wgpu::util::DeviceExt;
 use crate::view::{
     DeferredLight, Quad, QuadEmitter,
     gpu::GPU,
-    gpu_struct::vertex::{Vertex, VertexDeferred, VertexEmitter, VertexIndex},
+    gpu_struct::vertex::{
+        InstanceEmitter, Vertex, VertexCorner, VertexDeferred, VertexEmitter, VertexIndex,
+    },
 };
 
 pub trait IndexFormat: Pod {
@@ -37,6 +39,15 @@ pub trait VertexData {
     fn get_index_buffer(&self) -> &wgpu::Buffer;
     fn get_index_count(&self) -> u32;
     fn get_index_format(&self) -> wgpu::IndexFormat;
+
+    /// Per-instance data, bound after the vertex buffer
+    fn get_instance_buffer(&self) -> Option<&wgpu::Buffer> {
+        None
+    }
+
+    fn get_instance_count(&self) -> u32 {
+        1
+    }
 }
 
 pub struct VertexBuffers<V: Pod, I: IndexFormat> {
@@ -227,6 +238,103 @@ impl VertexBuffers<VertexEmitter, u16> {
     }
 }
 
+/// Many copies of the same quad, each with its own per-instance data.
+pub struct InstanceBuffers<T: Pod> {
+    quad: VertexBuffers<VertexCorner, u16>,
+    buffer: wgpu::Buffer,
+    count: usize,
+    format: PhantomData<T>,
+}
+
+impl<T: Pod> InstanceBuffers<T> {
+    fn new(gpu: &GPU, data: &[T]) -> Result<Self> {
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+            .map(|corner| VertexCorner { corner });
+        let quad = VertexBuffers::new(gpu, &corners, &[0, 1, 2, 2, 3, 0])?;
+
+        // Never empty, so that it can always be bound
+        let mut bytes = bytemuck::cast_slice(data).to_vec();
+        bytes.resize(bytes.len().max(size_of::<T>()), 0);
+
+        let buffer = VertexBuffers::<T, u16>::buffer_init(
+            gpu,
+            "Instance Buffer",
+            wgpu::BufferUsages::VERTEX,
+            &bytes,
+        );
+
+        Ok(Self {
+            quad,
+            buffer,
+            count: data.len(),
+            format: PhantomData,
+        })
+    }
+
+    fn update(&mut self, gpu: &GPU, data: &[T]) -> Result<()> {
+        VertexBuffers::<T, u16>::buffer_update(
+            &mut self.buffer,
+            gpu,
+            "Instance Buffer",
+            wgpu::BufferUsages::VERTEX,
+            bytemuck::cast_slice(data),
+        );
+
+        gpu.queue.submit([]);
+
+        self.count = data.len();
+
+        Ok(())
+    }
+}
+
+impl<T: Pod> VertexData for InstanceBuffers<T> {
+    fn get_vertex_buffer(&self) -> &wgpu::Buffer {
+        self.quad.get_vertex_buffer()
+    }
+
+    fn get_index_buffer(&self) -> &wgpu::Buffer {
+        self.quad.get_index_buffer()
+    }
+
+    fn get_index_count(&self) -> u32 {
+        self.quad.get_index_count()
+    }
+
+    fn get_index_format(&self) -> wgpu::IndexFormat {
+        self.quad.get_index_format()
+    }
+
+    fn get_instance_buffer(&self) -> Option<&wgpu::Buffer> {
+        Some(&self.buffer)
+    }
+
+    fn get_instance_count(&self) -> u32 {
+        self.count as u32
+    }
+}
+
+impl InstanceBuffers<InstanceEmitter> {
+    fn convert(quads: impl ExactSizeIterator<Item = QuadEmitter>) -> Vec<InstanceEmitter> {
+        quads.map(|quad| quad.instance_data()).collect()
+    }
+
+    pub fn new_emitters(
+        gpu: &GPU,
+        quads: impl ExactSizeIterator<Item = QuadEmitter>,
+    ) -> Result<Self> {
+        Self::new(gpu, &Self::convert(quads))
+    }
+
+    pub fn update_emitters(
+        &mut self,
+        gpu: &GPU,
+        quads: impl ExactSizeIterator<Item = QuadEmitter>,
+    ) -> Result<()> {
+        self.update(gpu, &Self::convert(quads))
+    }
+}
+
 impl VertexBuffers<VertexDeferred, u16> {
     fn convert(
         lights: impl ExactSizeIterator<Item = DeferredLight>,
@@ -1,8 +1,9 @@
-use glam::{Vec2, Vec3, vec2};
+use glam::{Vec2, Vec3, Vec4, vec2};
 
 use crate::view::gpu_struct::vertex::Vertex;
 use crate::view::gpu_struct::vertex::VertexIndex;
 
+#[derive(Debug, PartialEq)]
 pub struct Quad {
     /// Position of the center of the quad
     pub pos: Vec3,
@@ -19,11 +20,18 @@ pub struct Quad {
     pub tex_pos: Vec2,
     /// Width and Height of the corresponding texture quad
     pub tex_dim: Vec2,
+
+    /// Tint, the alpha channel is the opacity
+    pub tint: Vec4,
+    /// How fast the quad moves relative to the camera (1.0 is the default speed)
+    pub parallax: Vec2,
 }
 
 impl Quad {
     pub fn vertex_data(&self) -> [Vertex; 4] {
         let tex_num = self.tex_num;
+        let tint = self.tint.into();
+        let parallax = self.parallax.into();
 
         let w2 = self.dim.x / 2.0;
         let h2 = self.dim.y / 2.0;
@@ -47,21 +55,29 @@ impl Quad {
                 pos: vpos[0].into(),
                 tex_num,
                 tex_coord: tpos[0].into(),
+                tint,
+                parallax,
             },
             Vertex {
                 pos: vpos[1].into(),
                 tex_num,
                 tex_coord: tpos[1].into(),
+                tint,
+                parallax,
             },
             Vertex {
                 pos: vpos[2].into(),
                 tex_num,
                 tex_coord: tpos[2].into(),
+                tint,
+                parallax,
             },
             Vertex {
                 pos: vpos[3].into(),
                 tex_num,
                 tex_coord: tpos[3].into(),
+                tint,
+                parallax,
             },
         ]
     }
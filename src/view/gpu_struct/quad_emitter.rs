@@ -1,5 +1,6 @@
-use glam::{Vec2, Vec3, vec2};
+use glam::{Vec2, Vec3, Vec4, vec2};
 
+use crate::view::gpu_struct::vertex::InstanceEmitter;
 use crate::view::gpu_struct::vertex::VertexEmitter;
 use crate::view::gpu_struct::vertex::VertexIndex;
 
@@ -19,8 +20,8 @@ pub struct QuadEmitter {
     /// Width and Height of the corresponding texture quad
     pub tex_dim: Vec2,
 
-    /// Tint
-    pub tint: Vec3,
+    /// Tint, the alpha multiplies the alpha of the texture
+    pub tint: Vec4,
 }
 
 impl QuadEmitter {
@@ -44,7 +45,7 @@ impl QuadEmitter {
         let tpos = [vec2(0.0, th), vec2(tw, th), vec2(tw, 0.0), vec2(0.0, 0.0)]
             .map(|offset| self.tex_pos + offset);
 
-        let tint = self.tint.extend(1.0).into();
+        let tint = self.tint.into();
 
         [
             VertexEmitter {
@@ -74,6 +75,23 @@ impl QuadEmitter {
         ]
     }
 
+    pub fn instance_data(&self) -> InstanceEmitter {
+        let (rot_sin, rot_cos) = self.rot.sin_cos();
+        let rot = vec2(rot_cos, rot_sin);
+
+        let x = rot.rotate(vec2(self.dim.x / 2.0, 0.0));
+        let y = rot.rotate(vec2(0.0, self.dim.y / 2.0));
+
+        InstanceEmitter {
+            center: self.pos.extend(1.0).into(),
+            axes: [x.x, x.y, y.x, y.y],
+            tint: self.tint.into(),
+            tex_num: self.tex_num,
+            tex_pos: self.tex_pos.into(),
+            tex_dim: self.tex_dim.into(),
+        }
+    }
+
     pub fn index_data(&self, offset: VertexIndex) -> [VertexIndex; 6] {
         [
             offset + 0,
@@ -10,6 +10,8 @@ pub struct Vertex {
     pub pos: [f32; 4],
     pub tex_num: u32,
     pub tex_coord: [f32; 2],
+    pub tint: [f32; 4],
+    pub parallax: [f32; 2],
 }
 
 impl Vertex {
@@ -32,6 +34,16 @@ impl Vertex {
                 offset: offset_of!(Self, tex_coord) as u64,
                 shader_location: 2,
             },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: offset_of!(Self, tint) as u64,
+                shader_location: 3,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: offset_of!(Self, parallax) as u64,
+                shader_location: 4,
+            },
         ],
     };
 }
@@ -74,6 +86,77 @@ impl VertexEmitter {
     };
 }
 
+/// Corner of the quad that every instance is drawn with, from `-1` to `1` along each axis.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct VertexCorner {
+    pub corner: [f32; 2],
+}
+
+impl VertexCorner {
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &[wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x2,
+            offset: offset_of!(Self, corner) as u64,
+            shader_location: 0,
+        }],
+    };
+}
+
+/// A [`QuadEmitter`](crate::view::QuadEmitter) drawn as an instance of the quad of [`VertexCorner`]s.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct InstanceEmitter {
+    pub center: [f32; 4],
+    /// Half of the width and half of the height of the quad, as rotated vectors
+    pub axes: [f32; 4],
+    pub tint: [f32; 4],
+    pub tex_num: u32,
+    pub tex_pos: [f32; 2],
+    pub tex_dim: [f32; 2],
+}
+
+impl InstanceEmitter {
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: offset_of!(Self, center) as u64,
+                shader_location: 1,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: offset_of!(Self, axes) as u64,
+                shader_location: 2,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: offset_of!(Self, tint) as u64,
+                shader_location: 3,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint32,
+                offset: offset_of!(Self, tex_num) as u64,
+                shader_location: 4,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: offset_of!(Self, tex_pos) as u64,
+                shader_location: 5,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: offset_of!(Self, tex_dim) as u64,
+                shader_location: 6,
+            },
+        ],
+    };
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct VertexDeferred {
@@ -6,6 +6,7 @@ mod window;
 use std::sync::Arc;
 
 use anyhow::Result;
+use winit::dpi::PhysicalSize;
 
 use gpu::GPU;
 use window::Window;
@@ -16,10 +17,12 @@ use crate::{
     view::{
         gpu::{PipelineConfig, RenderConfig, RenderPass},
         gpu_data::{
-            DeferredInput, DeferredInputViews, DeferredTextureGroup, TextureDepth, TextureGroup,
-            TextureMultiplexer, UniformGroup, VertexBuffers,
+            DeferredInput, DeferredInputViews, DeferredTextureGroup, InstanceBuffers, TextureDepth,
+            TextureGroup, TextureMultiplexer, UniformGroup, VertexBuffers,
+        },
+        gpu_struct::vertex::{
+            InstanceEmitter, Vertex, VertexCorner, VertexDeferred, VertexEmitter,
         },
-        gpu_struct::vertex::{Vertex, VertexDeferred, VertexEmitter},
     },
 };
 
@@ -35,6 +38,8 @@ struct ViewGPUData {
 
     pub map_tmux: TextureMultiplexer,
     pub map_quads: VertexBuffers<Vertex, u16>,
+    /// Of the layers that are not fully opaque, they don't cover the lit colors of the layers behind them
+    pub map_quads_translucent: VertexBuffers<Vertex, u16>,
 
     pub deferred_textures: DeferredTextureGroup,
     pub deferred_inputs: DeferredInput,
@@ -42,6 +47,9 @@ struct ViewGPUData {
 
     pub light_emitters_tmux: TextureMultiplexer,
     pub light_emitters_quads: VertexBuffers<VertexEmitter, u16>,
+
+    /// Textured with the light emitters textures
+    pub particles: InstanceBuffers<InstanceEmitter>,
 }
 
 pub struct View {
@@ -51,8 +59,10 @@ pub struct View {
     gpu_data: ViewGPUData,
 
     pipeline_prepare_map: wgpu::RenderPipeline,
+    pipeline_prepare_map_translucent: wgpu::RenderPipeline,
     pipeline_deferred: wgpu::RenderPipeline,
     pipeline_light_emitters: wgpu::RenderPipeline,
+    pipeline_particles: wgpu::RenderPipeline,
 }
 
 impl View {
@@ -71,7 +81,13 @@ impl View {
             }
             let map_tmux = TextureMultiplexer::new(&gpu, main_tmux)?;
 
-            let map_quads = VertexBuffers::new_quads(&gpu, &game.map.quads()?)?;
+            let (translucent, opaque): (Vec<_>, Vec<_>) = game
+                .map
+                .quads()?
+                .into_iter()
+                .partition(|quad| quad.tint.w < 1.0);
+            let map_quads = VertexBuffers::new_quads(&gpu, &opaque)?;
+            let map_quads_translucent = VertexBuffers::new_quads(&gpu, &translucent)?;
 
             let camera_view = game.camera.matrix_view();
             let camera_proj = game.camera.matrix_proj(window.size());
@@ -106,6 +122,8 @@ impl View {
 
             let light_emitters_quads = VertexBuffers::new_emitters(&gpu, game.light_quad_data())?;
 
+            let particles = InstanceBuffers::new_emitters(&gpu, game.particle_quad_data())?;
+
             ViewGPUData {
                 camera,
 
@@ -113,6 +131,7 @@ impl View {
 
                 map_tmux,
                 map_quads,
+                map_quads_translucent,
 
                 deferred_textures,
                 deferred_inputs,
@@ -120,6 +139,8 @@ impl View {
 
                 light_emitters_tmux,
                 light_emitters_quads,
+
+                particles,
             }
         };
 
@@ -136,8 +157,13 @@ impl View {
                     gpu_data.map_tmux.get_bind_group_layout(),
                 ],
                 targets: &[
-                    window.output_format().into(),                    // Color Ambient
-                    DeferredTextureGroup::FORMAT_COLOR.into(),        // Color
+                    // Color Ambient
+                    wgpu::ColorTargetState {
+                        format: window.output_format(),
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    },
+                    DeferredTextureGroup::FORMAT_COLOR.into(), // Color
                     DeferredTextureGroup::FORMAT_NORMAL_DEPTH.into(), // Normal & Depth
                 ],
                 depth_stencil: Some(wgpu::DepthStencilState {
@@ -148,6 +174,50 @@ impl View {
                     bias: Default::default(),
                 }),
                 vertex_layout: Vertex::LAYOUT,
+                instance_layout: None,
+            });
+
+            pipeline
+        };
+
+        // Only adds ambient light, the layers behind keep their colors, normals and depths
+        let pipeline_prepare_map_translucent = {
+            let shader_name = "prepare-map";
+            let shader_source = assets.find_shader(shader_name)?;
+            let shader = gpu.create_shader(shader_name, shader_source);
+
+            let keep = |format| wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            };
+
+            let pipeline = gpu.create_pipeline(PipelineConfig {
+                label: "Prepare Map Translucent",
+                shader: &shader,
+                groups: &[
+                    gpu_data.camera.get_bind_group_layout(),
+                    gpu_data.map_tmux.get_bind_group_layout(),
+                ],
+                targets: &[
+                    // Color Ambient
+                    wgpu::ColorTargetState {
+                        format: window.output_format(),
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    },
+                    keep(DeferredTextureGroup::FORMAT_COLOR), // Color
+                    keep(DeferredTextureGroup::FORMAT_NORMAL_DEPTH), // Normal & Depth
+                ],
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureDepth::FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                vertex_layout: Vertex::LAYOUT,
+                instance_layout: None,
             });
 
             pipeline
@@ -181,6 +251,7 @@ impl View {
                 targets: &[target],
                 depth_stencil: None,
                 vertex_layout: VertexDeferred::LAYOUT,
+                instance_layout: None,
             });
 
             pipeline
@@ -213,18 +284,60 @@ impl View {
                     bias: Default::default(),
                 }),
                 vertex_layout: VertexEmitter::LAYOUT,
+                instance_layout: None,
             });
 
             pipeline
         };
 
+        let pipeline_particles = {
+            let shader_name = "particles";
+            let shader_source = assets.find_shader(shader_name)?;
+            let shader = gpu.create_shader(shader_name, shader_source);
+
+            // Particles glow, so they add up instead of covering each other
+            let target = wgpu::ColorTargetState {
+                format: window.output_format(),
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::SrcAlpha,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::OVER,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            };
+
+            gpu.create_pipeline(PipelineConfig {
+                label: "Particles",
+                shader: &shader,
+                groups: &[
+                    gpu_data.camera.get_bind_group_layout(),
+                    gpu_data.light_emitters_tmux.get_bind_group_layout(),
+                ],
+                targets: &[target],
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureDepth::FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                vertex_layout: VertexCorner::LAYOUT,
+                instance_layout: Some(InstanceEmitter::LAYOUT),
+            })
+        };
+
         Ok(Self {
             gpu,
             window,
             gpu_data,
             pipeline_prepare_map,
+            pipeline_prepare_map_translucent,
             pipeline_deferred,
             pipeline_light_emitters,
+            pipeline_particles,
         })
     }
 
@@ -232,6 +345,10 @@ impl View {
         self.window.request_redraw();
     }
 
+    pub fn window_size(&self) -> PhysicalSize<u32> {
+        self.window.size()
+    }
+
     pub fn resize(&mut self) -> Result<()> {
         self.window.configure(&self.gpu);
 
@@ -277,6 +394,12 @@ impl View {
         Ok(())
     }
 
+    pub fn update_particles(&mut self, game: &Game) -> Result<()> {
+        self.gpu_data
+            .particles
+            .update_emitters(&self.gpu, game.particle_quad_data())
+    }
+
     pub fn render(&self) -> Result<()> {
         let (window_texture, window_view) = self.window.texture()?;
 
@@ -330,6 +453,54 @@ impl View {
             vdata: &self.gpu_data.map_quads,
         };
 
+        let rpass_prepare_translucent = RenderPass {
+            descriptor: &wgpu::RenderPassDescriptor {
+                label: Some("Render ambient light of the translucent layers of the map"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &window_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gpu_data.deferred_textures.color_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gpu_data.deferred_textures.normal_depth_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.gpu_data.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            },
+            pipeline: &self.pipeline_prepare_map_translucent,
+            gdata: &[
+                self.gpu_data.camera.get_bind_group(),
+                self.gpu_data.map_tmux.get_bind_group(),
+            ],
+            vdata: &self.gpu_data.map_quads_translucent,
+        };
+
         let rpass_deferred = RenderPass {
             descriptor: &wgpu::RenderPassDescriptor {
                 label: Some("Render deferred diffuse and specular light"),
@@ -368,7 +539,7 @@ impl View {
                     view: &self.gpu_data.depth.texture_view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Discard,
+                        store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
                 }),
@@ -383,8 +554,44 @@ impl View {
             vdata: &self.gpu_data.light_emitters_quads,
         };
 
+        let rpass_particles = RenderPass {
+            descriptor: &wgpu::RenderPassDescriptor {
+                label: Some("Render particles"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &window_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.gpu_data.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            },
+            pipeline: &self.pipeline_particles,
+            gdata: &[
+                self.gpu_data.camera.get_bind_group(),
+                self.gpu_data.light_emitters_tmux.get_bind_group(),
+            ],
+            vdata: &self.gpu_data.particles,
+        };
+
         self.gpu.render(&RenderConfig {
-            passes: &[&rpass_prepare, &rpass_deferred, &rpass_light_emitters],
+            passes: &[
+                &rpass_prepare,
+                &rpass_prepare_translucent,
+                &rpass_deferred,
+                &rpass_light_emitters,
+                &rpass_particles,
+            ],
         })?;
 
         self.window.pre_present_notify();